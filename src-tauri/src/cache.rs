@@ -0,0 +1,474 @@
+// -----------------------
+// パッケージキャッシュ（内容ハッシュで管理するダウンロード済みアーカイブ）
+// -----------------------
+//
+// - 保存先は AppData/package-cache。objects/<xxh3_128> に本体、index.json に一覧を置く
+// - 取得元のキー（URL / drive:<id>）または期待ハッシュ（xxh3_128 / sha256）が一致すれば再ダウンロードしない
+// - 合計サイズが上限を超えたら最後に使われた日時が古いものから削除する（LRU）
+// - ファイル全体のコピーとハッシュは lock の外で行う（並列ダウンロードが 1 件のコピーを待たないように）
+//   どれもブロッキングの処理なので、非同期の呼び出し元は spawn_blocking で実行する
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::download::ExpectedDigest;
+
+// 上限の既定値（settings.json の package_cache_limit_mb が 0 の場合）
+const DEFAULT_LIMIT_MB: u64 = 2048;
+
+/// キャッシュ 1 件分
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    pub xxh3_128: String,
+    pub sha256: String,
+    pub size: u64,
+    // 最初に保存したときのファイル名（復元時の既定名）
+    pub file_name: String,
+    // 取得元のキー（URL / drive:<id>）
+    pub keys: Vec<String>,
+    pub added_at: i64,
+    pub last_used: i64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheIndex {
+    entries: Vec<CacheEntry>,
+}
+
+/// cache_list の戻り値
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSummary {
+    pub dir: String,
+    pub total_size: u64,
+    pub limit: u64,
+    pub entries: Vec<CacheEntry>,
+}
+
+/// cache_prune の戻り値
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneResult {
+    pub removed: usize,
+    pub freed: u64,
+}
+
+/// Tauri の State として保持するキャッシュ
+/// - index.json の読み書きは lock で直列化する（並列ダウンロードからの同時追加に備える）
+/// - lock を持つのは index.json を読み書きする間だけで、本体のコピーは lock を離してから行う
+pub struct PackageCache {
+    root: PathBuf,
+    limit: AtomicU64,
+    lock: Mutex<()>,
+}
+
+impl PackageCache {
+    pub fn new(app: &AppHandle, limit_mb: u64) -> Self {
+        let root = app.path().app_data_dir().unwrap_or_else(|_| crate::app_config_dir(app)).join("package-cache");
+        Self { root, limit: AtomicU64::new(limit_bytes(limit_mb)), lock: Mutex::new(()) }
+    }
+
+    pub fn set_limit_mb(&self, limit_mb: u64) {
+        self.limit.store(limit_bytes(limit_mb), Ordering::SeqCst);
+        let _ = self.prune_to(self.limit.load(Ordering::SeqCst));
+    }
+
+    fn object_path(&self, xxh3: &str) -> PathBuf {
+        self.root.join("objects").join(xxh3)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root.join("index.json")
+    }
+
+    fn load_index(&self) -> CacheIndex {
+        fs::read_to_string(self.index_path()).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default()
+    }
+
+    /// 一時ファイルに書いてから置き換える（書き込み途中で落ちても一覧が壊れないように）
+    fn save_index(&self, index: &CacheIndex) -> io::Result<()> {
+        fs::create_dir_all(&self.root)?;
+        let tmp = self.root.join("index.json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(index)?)?;
+        fs::rename(tmp, self.index_path())
+    }
+
+    fn locked(&self) -> io::Result<std::sync::MutexGuard<'_, ()>> {
+        self.lock.lock().map_err(|_| io::Error::other("cache lock poisoned"))
+    }
+
+    /// キーまたは期待ハッシュが一致するキャッシュを探し、dest_dir に配置する
+    /// - 期待値が指定されている場合は、キーが一致してもハッシュ・サイズが異なるものは使わない
+    /// - 戻り値は配置したパス
+    pub fn restore(&self, key: Option<&str>, expected: Option<&ExpectedDigest>, dest_dir: &Path, file_name: Option<&str>) -> Option<PathBuf> {
+        let want_xxh3 = expected.and_then(|e| e.xxh3_128.as_deref()).map(str::trim).filter(|s| !s.is_empty());
+        let want_sha256 = expected.and_then(|e| e.sha256.as_deref()).map(str::trim).filter(|s| !s.is_empty());
+        let want_size = expected.and_then(|e| e.size);
+        let hash_ok = |entry: &CacheEntry| {
+            want_xxh3.map(|h| h.eq_ignore_ascii_case(&entry.xxh3_128)).unwrap_or(true)
+                && want_sha256.map(|h| h.eq_ignore_ascii_case(&entry.sha256)).unwrap_or(true)
+                && want_size.map(|s| s == entry.size).unwrap_or(true)
+        };
+        let has_hash = want_xxh3.is_some() || want_sha256.is_some();
+
+        let entry = {
+            let _guard = self.locked().ok()?;
+            let mut index = self.load_index();
+            let pos = index.entries.iter().position(|entry| {
+                let key_hit = key.map(|k| entry.keys.iter().any(|e| e == k)).unwrap_or(false);
+                (key_hit || has_hash) && hash_ok(entry)
+            })?;
+            // 本体が消えている・サイズが違う場合は一覧から外す
+            if fs::metadata(self.object_path(&index.entries[pos].xxh3_128)).map(|m| m.len() != index.entries[pos].size).unwrap_or(true) {
+                index.entries.remove(pos);
+                let _ = self.save_index(&index);
+                return None;
+            }
+            index.entries[pos].clone()
+        };
+
+        // コピー中に削除された場合は失敗として扱い、通常どおりダウンロードさせる
+        let dest = dest_dir.join(crate::download::sanitize_filename(file_name.unwrap_or(&entry.file_name)));
+        fs::create_dir_all(dest_dir).ok()?;
+        place_file(&self.object_path(&entry.xxh3_128), &dest).ok()?;
+        if fs::metadata(&dest).map(|m| m.len() != entry.size).unwrap_or(true) {
+            let _ = fs::remove_file(&dest);
+            return None;
+        }
+
+        if let Ok(_guard) = self.locked() {
+            let mut index = self.load_index();
+            if let Some(current) = index.entries.iter_mut().find(|e| e.xxh3_128 == entry.xxh3_128) {
+                current.last_used = chrono::Utc::now().timestamp();
+                if let Some(k) = key.filter(|k| !current.keys.iter().any(|e| e == k)) {
+                    current.keys.push(k.to_string());
+                }
+                let _ = self.save_index(&index);
+            }
+        }
+        Some(dest)
+    }
+
+    /// ダウンロード済みのファイルをキャッシュに追加し、上限を超えた分を削除する
+    /// - 本体は objects/ の一時ファイルへ lock の外でコピーし、一覧に加えるときに名前を変える
+    pub fn insert(&self, file: &Path, key: &str) -> io::Result<()> {
+        let (xxh3, sha256, size) = hash_file(file)?;
+        let now = chrono::Utc::now().timestamp();
+        // キーは最新の内容だけを指すようにする（同じ URL の中身が更新された場合）
+        let update = |index: &mut CacheIndex| {
+            for entry in index.entries.iter_mut().filter(|e| e.xxh3_128 != xxh3) {
+                entry.keys.retain(|k| k != key);
+            }
+            let entry = index.entries.iter_mut().find(|e| e.xxh3_128 == xxh3)?;
+            if !entry.keys.iter().any(|k| k == key) {
+                entry.keys.push(key.to_string());
+            }
+            entry.last_used = now;
+            Some(())
+        };
+
+        {
+            let _guard = self.locked()?;
+            let mut index = self.load_index();
+            if update(&mut index).is_some() {
+                return self.save_index(&index);
+            }
+        }
+
+        let object = self.object_path(&xxh3);
+        if let Some(parent) = object.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // 同じ内容を並列に追加しても一時ファイルがぶつからないようにする
+        let tmp = object.with_extension(format!("{}.tmp", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()));
+        if let Err(e) = fs::copy(file, &tmp) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+
+        let _guard = self.locked()?;
+        let mut index = self.load_index();
+        if update(&mut index).is_some() {
+            // 待っている間に同じ内容が追加された
+            let _ = fs::remove_file(&tmp);
+        } else {
+            fs::rename(&tmp, &object)?;
+            index.entries.push(CacheEntry {
+                xxh3_128: xxh3.clone(),
+                sha256,
+                size,
+                file_name: file.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default(),
+                keys: vec![key.to_string()],
+                added_at: now,
+                last_used: now,
+            });
+        }
+        evict(&self.root, &mut index, self.limit.load(Ordering::SeqCst));
+        self.save_index(&index)
+    }
+
+    /// 合計サイズが target 以下になるまで古いものから削除
+    pub fn prune_to(&self, target: u64) -> io::Result<PruneResult> {
+        let _guard = self.locked()?;
+        let mut index = self.load_index();
+        let result = evict(&self.root, &mut index, target);
+        self.save_index(&index)?;
+        Ok(result)
+    }
+
+    pub fn summary(&self) -> CacheSummary {
+        let _guard = self.lock.lock();
+        let mut entries = self.load_index().entries;
+        entries.sort_by_key(|e| std::cmp::Reverse(e.last_used));
+        CacheSummary {
+            dir: self.root.to_string_lossy().to_string(),
+            total_size: entries.iter().map(|e| e.size).sum(),
+            limit: self.limit.load(Ordering::SeqCst),
+            entries,
+        }
+    }
+
+    /// キャッシュを dest_dir にファイル名付きでコピーし、一覧を cache-index.json として書き出す
+    /// - 書き出し先の既存ファイルは上書きしない（同じ内容ならそのまま使い、違う内容なら別の名前にする）
+    /// - 同じフォルダへの以前の書き出しの一覧は、ファイルが残っているものを引き継ぐ
+    pub fn export(&self, dest_dir: &Path) -> io::Result<usize> {
+        let entries = {
+            let _guard = self.locked()?;
+            self.load_index().entries
+        };
+        fs::create_dir_all(dest_dir)?;
+        let index_path = dest_dir.join("cache-index.json");
+        let previous: CacheIndex = fs::read_to_string(&index_path).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default();
+        let mut used: HashSet<String> = HashSet::from(["cache-index.json".to_string()]);
+        let mut exported = Vec::new();
+        for entry in &entries {
+            let object = self.object_path(&entry.xxh3_128);
+            if !object.is_file() {
+                continue;
+            }
+            let name = crate::download::sanitize_filename(&entry.file_name);
+            let prefix = &entry.xxh3_128[..8.min(entry.xxh3_128.len())];
+            let candidates = std::iter::once(name.clone()).chain(std::iter::once(format!("{}_{}", prefix, name))).chain((2..).map(|n| format!("{}_{}_{}", prefix, n, name)));
+            let mut chosen = None;
+            for candidate in candidates {
+                if used.contains(&candidate.to_lowercase()) {
+                    continue;
+                }
+                let dest = dest_dir.join(&candidate);
+                if !dest.exists() {
+                    // コピー中に削除されたものは書き出さない
+                    match place_file(&object, &dest) {
+                        Ok(()) => chosen = Some(candidate),
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e),
+                    }
+                    break;
+                }
+                if hash_file(&dest).map(|(xxh3, _, _)| xxh3 == entry.xxh3_128).unwrap_or(false) {
+                    chosen = Some(candidate);
+                    break;
+                }
+            }
+            let Some(name) = chosen else {
+                continue;
+            };
+            used.insert(name.to_lowercase());
+            let mut entry = entry.clone();
+            entry.file_name = name;
+            exported.push(entry);
+        }
+        let count = exported.len();
+        let exported_hashes: HashSet<String> = exported.iter().map(|e| e.xxh3_128.clone()).collect();
+        for entry in previous.entries {
+            if !exported_hashes.contains(&entry.xxh3_128) && !used.contains(&entry.file_name.to_lowercase()) && dest_dir.join(&entry.file_name).is_file() {
+                used.insert(entry.file_name.to_lowercase());
+                exported.push(entry);
+            }
+        }
+        let tmp = dest_dir.join("cache-index.json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&CacheIndex { entries: exported })?)?;
+        fs::rename(tmp, index_path)?;
+        Ok(count)
+    }
+}
+
+fn limit_bytes(limit_mb: u64) -> u64 {
+    (if limit_mb == 0 { DEFAULT_LIMIT_MB } else { limit_mb }).saturating_mul(1024 * 1024)
+}
+
+/// 最終使用日時の古い順に削除して target 以下にする
+fn evict(root: &Path, index: &mut CacheIndex, target: u64) -> PruneResult {
+    let mut result = PruneResult::default();
+    let mut total: u64 = index.entries.iter().map(|e| e.size).sum();
+    index.entries.sort_by_key(|e| e.last_used);
+    while total > target && !index.entries.is_empty() {
+        let entry = index.entries.remove(0);
+        let _ = fs::remove_file(root.join("objects").join(&entry.xxh3_128));
+        total = total.saturating_sub(entry.size);
+        result.removed += 1;
+        result.freed += entry.size;
+    }
+    result
+}
+
+/// コピーで配置する（ハードリンクだと配置先の編集がキャッシュ本体に及ぶため使わない）
+fn place_file(src: &Path, dst: &Path) -> io::Result<()> {
+    let tmp = dst.with_extension("cache-tmp");
+    fs::copy(src, &tmp)?;
+    fs::rename(&tmp, dst)
+}
+
+/// (xxh3_128, sha256, サイズ)
+fn hash_file(path: &Path) -> io::Result<(String, String, u64)> {
+    use sha2::Digest;
+    let mut f = File::open(path)?;
+    let mut xxh3 = xxhash_rust::xxh3::Xxh3::new();
+    let mut sha = sha2::Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        xxh3.update(&buf[..n]);
+        sha.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((format!("{:032x}", xxh3.digest128()), format!("{:x}", sha.finalize()), size))
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+// キャッシュの処理はブロッキングなので、コマンドのスレッドを塞がないように spawn_blocking で実行する
+async fn run_blocking<T: Send + 'static>(app: &AppHandle, f: impl FnOnce(&PackageCache) -> io::Result<T> + Send + 'static) -> Result<T, String> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || f(&handle.state::<PackageCache>())).await.map_err(|e| format!("task join error: {e}"))?.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn cache_list(app: AppHandle) -> Result<CacheSummary, String> {
+    run_blocking(&app, |cache| Ok(cache.summary())).await
+}
+
+/// max_bytes 以下になるまで古いものから削除（省略時は設定の上限、0 なら全削除）
+#[tauri::command]
+pub async fn cache_prune(app: AppHandle, max_bytes: Option<u64>) -> Result<PruneResult, String> {
+    run_blocking(&app, move |cache| cache.prune_to(max_bytes.unwrap_or_else(|| cache.limit.load(Ordering::SeqCst)))).await
+}
+
+/// 別の PC へ持ち出せるようにフォルダへ書き出す（書き出した件数を返す）
+#[tauri::command]
+pub async fn cache_export(app: AppHandle, dest_path: String) -> Result<usize, String> {
+    if dest_path.trim().is_empty() {
+        return Err("dest_path must not be empty".to_string());
+    }
+    let dest = crate::resolve_rel_to_app_config(&app, &dest_path);
+    run_blocking(&app, move |cache| cache.export(&dest)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_in(dir: &Path, limit: u64) -> PackageCache {
+        PackageCache {
+            root: dir.join("package-cache"),
+            limit: AtomicU64::new(limit),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn write(dir: &Path, name: &str, body: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, body).unwrap();
+        path
+    }
+
+    #[test]
+    fn insert_then_restore_by_key_and_hash() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = cache_in(tmp.path(), u64::MAX);
+        let file = write(tmp.path(), "plugin.zip", b"v1 body");
+        cache.insert(&file, "https://example.com/plugin.zip").unwrap();
+        let entry = cache.summary().entries.remove(0);
+
+        let out = tmp.path().join("out");
+        let restored = cache.restore(Some("https://example.com/plugin.zip"), None, &out, None).unwrap();
+        assert_eq!(restored, out.join("plugin.zip"));
+        assert_eq!(fs::read(&restored).unwrap(), b"v1 body");
+
+        // キーが違ってもハッシュが一致すれば使い、キーを追加する
+        let expected = ExpectedDigest { xxh3_128: Some(entry.xxh3_128.to_uppercase()), sha256: None, size: Some(7) };
+        let restored = cache.restore(Some("drive:abc"), Some(&expected), &out, Some("renamed.zip")).unwrap();
+        assert_eq!(fs::read(restored).unwrap(), b"v1 body");
+        assert_eq!(cache.summary().entries[0].keys, vec!["https://example.com/plugin.zip", "drive:abc"]);
+
+        // ハッシュが違うものは使わない
+        let wrong = ExpectedDigest { xxh3_128: Some("0".repeat(32)), sha256: None, size: None };
+        assert!(cache.restore(Some("https://example.com/plugin.zip"), Some(&wrong), &out, None).is_none());
+    }
+
+    #[test]
+    fn key_moves_to_newest_content_and_lru_is_evicted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = cache_in(tmp.path(), 10);
+        cache.insert(&write(tmp.path(), "a.zip", b"aaaaaa"), "key").unwrap();
+        cache.insert(&write(tmp.path(), "b.zip", b"bbbbbb"), "key").unwrap();
+        let summary = cache.summary();
+        // 上限 10 バイトに 6 + 6 は入らないので古い a が消える
+        assert_eq!(summary.entries.len(), 1);
+        assert_eq!(summary.entries[0].file_name, "b.zip");
+        assert_eq!(summary.entries[0].keys, vec!["key"]);
+        assert_eq!(fs::read_dir(cache.root.join("objects")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn restore_drops_entry_whose_object_is_gone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = cache_in(tmp.path(), u64::MAX);
+        cache.insert(&write(tmp.path(), "a.zip", b"aaaa"), "key").unwrap();
+        fs::remove_file(cache.object_path(&cache.summary().entries[0].xxh3_128)).unwrap();
+        assert!(cache.restore(Some("key"), None, &tmp.path().join("out"), None).is_none());
+        assert!(cache.summary().entries.is_empty());
+    }
+
+    #[test]
+    fn export_does_not_overwrite_existing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = cache_in(tmp.path(), u64::MAX);
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("other")).unwrap();
+        cache.insert(&write(&src, "plugin.zip", b"first"), "k1").unwrap();
+        cache.insert(&write(&src.join("other"), "plugin.zip", b"second"), "k2").unwrap();
+
+        let dest = tmp.path().join("export");
+        fs::create_dir_all(&dest).unwrap();
+        write(&dest, "plugin.zip", b"user file");
+        assert_eq!(cache.export(&dest).unwrap(), 2);
+        assert_eq!(fs::read(dest.join("plugin.zip")).unwrap(), b"user file");
+
+        let index: CacheIndex = serde_json::from_str(&fs::read_to_string(dest.join("cache-index.json")).unwrap()).unwrap();
+        let names: HashSet<&str> = index.entries.iter().map(|e| e.file_name.as_str()).collect();
+        assert_eq!(names.len(), 2);
+        assert!(!names.contains("plugin.zip"));
+        for entry in &index.entries {
+            let (xxh3, _, _) = hash_file(&dest.join(&entry.file_name)).unwrap();
+            assert_eq!(xxh3, entry.xxh3_128);
+        }
+
+        // 同じフォルダに書き出し直しても増えない
+        let before = fs::read_dir(&dest).unwrap().count();
+        assert_eq!(cache.export(&dest).unwrap(), 2);
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), before);
+    }
+}
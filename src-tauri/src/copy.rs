@@ -0,0 +1,480 @@
+// -----------------------
+// ファイルのコピー（インストーラの copy ステップ）
+// -----------------------
+//
+// - from はファイル・フォルダのパスか glob パターン（* ? [...] {a,b} **）
+//   * と ? は区切りをまたがず、** は任意の階層に一致する。大文字小文字は区別しない（Windows のファイル名に合わせる）
+// - 実在するパスはパターンとして解釈しない（[v1.0] のような名前のフォルダをそのまま扱うため）
+// - パターンに何も一致しなければ COPY_NO_MATCH で失敗する（optional のステップは何もせずに成功する）
+// - フォルダを直接指定した場合は中身を to にコピーし、パターンに一致したものは名前ごと to の直下に置く（cp -r と同じ）
+// - 既存のファイルの扱いは OverwritePolicy で指定する（バックアップは設定フォルダの backups/<日時>/ に元の絶対パスの形で置く）
+// - コピー・スキップ・バックアップしたファイルの一覧を返す
+// - 書き込みは placement で一時ファイル経由で行い、ステップの途中で失敗したら既存のファイルを元に戻す
+//   インストール中は journal にも記録し、後のステップで失敗したときもインストール前の状態に戻せるようにする
+
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tauri::AppHandle;
+use walkdir::WalkDir;
+
+use crate::journal::Journal;
+use crate::placement::Placement;
+
+// パターンとして扱う文字
+const GLOB_CHARS: [char; 4] = ['*', '?', '[', '{'];
+
+/// コピー時のエラー（Display の先頭はフロントで判定するためのコード）
+#[derive(Debug, thiserror::Error)]
+pub enum CopyError {
+    #[error("COPY_INVALID_PATTERN: {0}")]
+    InvalidPattern(String),
+    #[error("COPY_SOURCE_NOT_FOUND: {0}")]
+    SourceNotFound(String),
+    #[error("COPY_NO_MATCH: no files matched the pattern: {0}")]
+    NoMatch(String),
+    #[error("COPY_CONFLICT: existing files differ from the package: {}", describe_files(.0))]
+    Conflict(Vec<String>),
+    #[error("COPY_IO_ERROR: {0}")]
+    Io(String),
+}
+
+impl CopyError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CopyError::InvalidPattern(_) => "COPY_INVALID_PATTERN",
+            CopyError::SourceNotFound(_) => "COPY_SOURCE_NOT_FOUND",
+            CopyError::NoMatch(_) => "COPY_NO_MATCH",
+            CopyError::Conflict(_) => "COPY_CONFLICT",
+            CopyError::Io(_) => "COPY_IO_ERROR",
+        }
+    }
+}
+
+impl Serialize for CopyError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("CopyError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("files", if let CopyError::Conflict(files) = self { files.as_slice() } else { &[] })?;
+        s.end()
+    }
+}
+
+fn describe_files(files: &[String]) -> String {
+    const SHOWN: usize = 5;
+    let mut list = files.iter().take(SHOWN).cloned().collect::<Vec<_>>().join(", ");
+    if files.len() > SHOWN {
+        list.push_str(&format!(", ... {} more", files.len() - SHOWN));
+    }
+    list
+}
+
+impl From<io::Error> for CopyError {
+    fn from(e: io::Error) -> Self {
+        CopyError::Io(e.to_string())
+    }
+}
+
+impl From<walkdir::Error> for CopyError {
+    fn from(e: walkdir::Error) -> Self {
+        CopyError::Io(e.to_string())
+    }
+}
+
+/// 既存のファイルがあるときの扱い
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverwritePolicy {
+    // そのまま上書きする
+    #[default]
+    Overwrite,
+    // 既存のファイルは残してコピーしない
+    SkipIfExists,
+    // 既存のファイルをバックアップしてから上書きする
+    BackupThenOverwrite,
+    // 内容の異なる既存のファイルが 1 つでもあれば何もコピーせずに失敗する
+    FailIfDifferent,
+}
+
+/// copy_item の結果
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyReport {
+    // コピーしたファイル（コピー先のパス）
+    pub copied: Vec<String>,
+    // skip-if-exists で残した既存のファイル
+    pub skipped: Vec<String>,
+    pub backups: Vec<BackupEntry>,
+}
+
+/// 上書き前に退避したファイル
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupEntry {
+    pub original: String,
+    pub backup: String,
+}
+
+/// コピーする内容（フォルダは空のものも作るため別に持つ）
+#[derive(Default)]
+struct CopyPlan {
+    dirs: Vec<PathBuf>,
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+/// from（パスまたは glob パターン）を dst にコピーする
+/// - optional: パターンに何も一致しなくても失敗せず、空の結果を返す
+/// - backup_root: backup-then-overwrite のバックアップを置くフォルダ（最初に退避するときに作る）
+/// - journal: インストール中のコピーで渡す（置き換えをインストール全体の巻き戻しの対象にする）
+pub fn copy_item(from: &str, dst: &Path, policy: OverwritePolicy, optional: bool, backup_root: &Path, journal: Option<Arc<Journal>>) -> Result<CopyReport, CopyError> {
+    let mut plan = CopyPlan::default();
+    let path = Path::new(from);
+    if path.exists() || !from.contains(GLOB_CHARS) {
+        plan_path(path, dst, &mut plan)?;
+    } else {
+        match plan_matches(from, dst, &mut plan) {
+            Err(CopyError::NoMatch(_)) if optional => return Ok(CopyReport::default()),
+            result => result?,
+        }
+    }
+    apply_plan(plan, policy, backup_root, Placement::with_journal(journal))
+}
+
+/// ファイルは dst の直下に、フォルダは中身を dst にコピーする
+fn plan_path(src: &Path, dst: &Path, plan: &mut CopyPlan) -> Result<(), CopyError> {
+    if src.is_file() {
+        let file_name = src.file_name().ok_or_else(|| CopyError::SourceNotFound(format!("failed to get file name: {}", src.display())))?;
+        plan.files.push((src.to_path_buf(), dst.join(file_name)));
+        return Ok(());
+    }
+    if src.is_dir() {
+        return plan_dir(src, dst, plan);
+    }
+    Err(CopyError::SourceNotFound(format!("source is neither a file nor a directory: {}", src.display())))
+}
+
+/// フォルダの中身を階層を保ったまま dst にコピーする
+fn plan_dir(src: &Path, dst: &Path, plan: &mut CopyPlan) -> Result<(), CopyError> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(src).map_err(|_| CopyError::Io(format!("failed to calculate relative path: {}", entry.path().display())))?;
+        let dest = dst.join(rel);
+        if entry.file_type().is_dir() {
+            plan.dirs.push(dest);
+        } else {
+            plan.files.push((entry.path().to_path_buf(), dest));
+        }
+    }
+    Ok(())
+}
+
+/// パターンに一致したファイル・フォルダを dst の直下にコピーする
+/// - 一致したフォルダは中身ごとコピーし、その下はそれ以上照合しない
+/// - 何も一致しなければ NoMatch（to のフォルダも作らない）
+fn plan_matches(pattern: &str, dst: &Path, plan: &mut CopyPlan) -> Result<(), CopyError> {
+    let (base, matcher) = compile_pattern(pattern)?;
+    if !base.is_dir() {
+        return Err(CopyError::SourceNotFound(format!("base directory of pattern not found: {}", base.display())));
+    }
+    let mut matched = false;
+    plan.dirs.push(dst.to_path_buf());
+    let mut walker = WalkDir::new(&base).min_depth(1).sort_by_file_name().into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(&base).unwrap_or(entry.path());
+        let key = rel.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/");
+        if !matcher.is_match(&key) {
+            continue;
+        }
+        matched = true;
+        let dest = dst.join(entry.file_name());
+        if entry.file_type().is_dir() {
+            plan_dir(entry.path(), &dest, plan)?;
+            walker.skip_current_dir();
+        } else {
+            plan.files.push((entry.path().to_path_buf(), dest));
+        }
+    }
+    if !matched {
+        return Err(CopyError::NoMatch(pattern.to_string()));
+    }
+    Ok(())
+}
+
+/// 上書きの扱いに従ってコピーする
+fn apply_plan(plan: CopyPlan, policy: OverwritePolicy, backup_root: &Path, mut placement: Placement) -> Result<CopyReport, CopyError> {
+    // fail-if-different は書き込む前に全ファイルを確認する
+    if policy == OverwritePolicy::FailIfDifferent {
+        let mut conflicts = Vec::new();
+        for (src, dest) in &plan.files {
+            if dest.is_file() && !same_content(src, dest)? {
+                conflicts.push(dest.to_string_lossy().into_owned());
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(CopyError::Conflict(conflicts));
+        }
+    }
+
+    let mut report = CopyReport::default();
+    for dir in &plan.dirs {
+        placement.create_dir_all(dir)?;
+    }
+    // 先にすべてを一時ファイルに書き込み、そろってから置き換える（途中で失敗しても既存のファイルは変わらない）
+    let mut staged = Vec::with_capacity(plan.files.len());
+    if let Err(e) = stage_files(plan.files, policy, backup_root, &placement, &mut report, &mut staged) {
+        for (tmp, _) in &staged {
+            let _ = fs::remove_file(tmp);
+        }
+        return Err(e);
+    }
+    for (i, (tmp, dest)) in staged.iter().enumerate() {
+        if let Err(e) = placement.place(tmp, dest) {
+            for (tmp, _) in &staged[i..] {
+                let _ = fs::remove_file(tmp);
+            }
+            placement.rollback();
+            return Err(e.into());
+        }
+        report.copied.push(dest.to_string_lossy().into_owned());
+    }
+    placement.commit();
+    Ok(report)
+}
+
+/// コピー元を置き場所の隣の一時ファイルに書き込む（skip-if-exists の判定と上書き前のバックアップもここで行う）
+fn stage_files(
+    files: Vec<(PathBuf, PathBuf)>,
+    policy: OverwritePolicy,
+    backup_root: &Path,
+    placement: &Placement,
+    report: &mut CopyReport,
+    staged: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), CopyError> {
+    let mut backup_dir: Option<PathBuf> = None;
+    for (src, dest) in files {
+        if dest.is_file() {
+            match policy {
+                OverwritePolicy::SkipIfExists => {
+                    report.skipped.push(dest.to_string_lossy().into_owned());
+                    continue;
+                }
+                OverwritePolicy::BackupThenOverwrite if !same_content(&src, &dest)? => {
+                    let dir = match &backup_dir {
+                        Some(dir) => dir.clone(),
+                        None => backup_dir.insert(new_backup_dir(backup_root)?).clone(),
+                    };
+                    let backup = dir.join(backup_rel_path(&dest));
+                    if let Some(parent) = backup.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(&dest, &backup)?;
+                    report.backups.push(BackupEntry {
+                        original: dest.to_string_lossy().into_owned(),
+                        backup: backup.to_string_lossy().into_owned(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        let tmp = placement.stage_copy(&src, &dest)?;
+        staged.push((tmp, dest));
+    }
+    Ok(())
+}
+
+/// 2 つのファイルの内容が同じか
+fn same_content(a: &Path, b: &Path) -> Result<bool, CopyError> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let (mut ra, mut rb) = (BufReader::new(File::open(a)?), BufReader::new(File::open(b)?));
+    let (mut ba, mut bb) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let n = ra.read(&mut ba)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        rb.read_exact(&mut bb[..n])?;
+        if ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// 今回のコピー用のバックアップフォルダ（backups/20250101-120000、同じ秒に作られたものがあれば _1, _2, ...）
+fn new_backup_dir(root: &Path) -> Result<PathBuf, CopyError> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut dir = root.join(&stamp);
+    let mut n = 1;
+    while dir.exists() {
+        dir = root.join(format!("{stamp}_{n}"));
+        n += 1;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// バックアップフォルダ内の置き場所（元の絶対パスをドライブ名から辿る形。C:\aviutl2\Plugin\a.auo → C/aviutl2/Plugin/a.auo）
+fn backup_rel_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().replace([':', '\\', '?'], "")).filter(|s| !s.is_empty()).map(PathBuf::from),
+            Component::Normal(part) => Some(PathBuf::from(part)),
+            Component::RootDir | Component::CurDir | Component::ParentDir => None,
+        })
+        .collect()
+}
+
+/// パターンをワイルドカードを含まない先頭のフォルダと、そこからの相対パターンに分ける
+/// - 区切りは Path の解釈に任せる（Windows では / と \ の両方）。\ はエスケープとして扱わない
+fn compile_pattern(pattern: &str) -> Result<(PathBuf, GlobMatcher), CopyError> {
+    let mut base = PathBuf::new();
+    let mut rest: Vec<String> = Vec::new();
+    for component in Path::new(pattern).components() {
+        let part = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !part.contains(GLOB_CHARS) {
+            base.push(component);
+        } else {
+            rest.push(part.into_owned());
+        }
+    }
+    let glob = GlobBuilder::new(&rest.join("/")).case_insensitive(true).literal_separator(true).build().map_err(|e| CopyError::InvalidPattern(format!("{}: {}", pattern, e)))?;
+    Ok((base, glob.compile_matcher()))
+}
+
+/// from（パスまたは glob パターン）を to にコピーし、コピー・スキップ・バックアップしたファイルを返す
+/// - policy: 既存のファイルの扱い（省略時は overwrite）。バックアップは設定フォルダの backups/ に置く
+/// - optional: true ならパターンに何も一致しなくても失敗しない
+#[tauri::command]
+pub fn copy_item_js(app: AppHandle, src_str: String, dst_str: String, policy: Option<OverwritePolicy>, optional: Option<bool>) -> Result<CopyReport, CopyError> {
+    let backup_root = crate::app_config_dir(&app).join("backups");
+    copy_item(&src_str, Path::new(&dst_str), policy.unwrap_or_default(), optional.unwrap_or(false), &backup_root, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(dir: &Path, rest: &str) -> String {
+        format!("{}/{}", dir.to_string_lossy().replace('\\', "/"), rest)
+    }
+
+    // src/ に a.txt（new-a）・b.txt（same）・c.txt（new-c）を、dst/ に a.txt（old-a）・b.txt（same）を置く
+    fn setup(root: &Path) -> (PathBuf, PathBuf) {
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&dst).unwrap();
+        fs::write(src.join("a.txt"), b"new-a").unwrap();
+        fs::write(src.join("b.txt"), b"same").unwrap();
+        fs::write(src.join("c.txt"), b"new-c").unwrap();
+        fs::write(dst.join("a.txt"), b"old-a").unwrap();
+        fs::write(dst.join("b.txt"), b"same").unwrap();
+        (src, dst)
+    }
+
+    fn copy_with(root: &Path, policy: OverwritePolicy) -> Result<CopyReport, CopyError> {
+        let (src, dst) = setup(root);
+        copy_item(&src.to_string_lossy(), &dst, policy, false, &root.join("backups"), None)
+    }
+
+    fn names(paths: &[String]) -> Vec<String> {
+        let mut names: Vec<String> = paths.iter().map(|p| Path::new(p).file_name().unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn overwrite_replaces_existing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = copy_with(tmp.path(), OverwritePolicy::Overwrite).unwrap();
+        assert_eq!(names(&report.copied), ["a.txt", "b.txt", "c.txt"]);
+        assert!(report.skipped.is_empty() && report.backups.is_empty());
+        assert_eq!(fs::read(tmp.path().join("dst").join("a.txt")).unwrap(), b"new-a");
+        assert!(!tmp.path().join("backups").exists());
+    }
+
+    #[test]
+    fn skip_if_exists_keeps_existing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = copy_with(tmp.path(), OverwritePolicy::SkipIfExists).unwrap();
+        assert_eq!(names(&report.copied), ["c.txt"]);
+        assert_eq!(names(&report.skipped), ["a.txt", "b.txt"]);
+        assert_eq!(fs::read(tmp.path().join("dst").join("a.txt")).unwrap(), b"old-a");
+        assert_eq!(fs::read(tmp.path().join("dst").join("c.txt")).unwrap(), b"new-c");
+    }
+
+    #[test]
+    fn backup_then_overwrite_backs_up_only_changed_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = copy_with(tmp.path(), OverwritePolicy::BackupThenOverwrite).unwrap();
+        assert_eq!(names(&report.copied), ["a.txt", "b.txt", "c.txt"]);
+        // 内容が同じ b.txt はバックアップしない
+        assert_eq!(report.backups.len(), 1);
+        let backup = &report.backups[0];
+        assert_eq!(Path::new(&backup.original), tmp.path().join("dst").join("a.txt"));
+        assert!(Path::new(&backup.backup).starts_with(tmp.path().join("backups")));
+        assert_eq!(fs::read(&backup.backup).unwrap(), b"old-a");
+        assert_eq!(fs::read(tmp.path().join("dst").join("a.txt")).unwrap(), b"new-a");
+    }
+
+    #[test]
+    fn fail_if_different_writes_nothing_on_conflict() {
+        let tmp = tempfile::tempdir().unwrap();
+        let Err(CopyError::Conflict(files)) = copy_with(tmp.path(), OverwritePolicy::FailIfDifferent) else {
+            panic!("expected COPY_CONFLICT");
+        };
+        assert_eq!(names(&files), ["a.txt"]);
+        assert_eq!(fs::read(tmp.path().join("dst").join("a.txt")).unwrap(), b"old-a");
+        assert!(!tmp.path().join("dst").join("c.txt").exists());
+
+        // 内容が同じファイルしか無ければコピーする
+        fs::write(tmp.path().join("dst").join("a.txt"), b"new-a").unwrap();
+        let (src, dst) = (tmp.path().join("src"), tmp.path().join("dst"));
+        let report = copy_item(&src.to_string_lossy(), &dst, OverwritePolicy::FailIfDifferent, false, &tmp.path().join("backups"), None).unwrap();
+        assert_eq!(names(&report.copied), ["a.txt", "b.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn overwrite_policy_names() {
+        let parse = |name: &str| serde_json::from_str::<OverwritePolicy>(&format!("\"{}\"", name)).unwrap();
+        assert_eq!(parse("overwrite"), OverwritePolicy::Overwrite);
+        assert_eq!(parse("skip-if-exists"), OverwritePolicy::SkipIfExists);
+        assert_eq!(parse("backup-then-overwrite"), OverwritePolicy::BackupThenOverwrite);
+        assert_eq!(parse("fail-if-different"), OverwritePolicy::FailIfDifferent);
+        assert!(serde_json::from_str::<OverwritePolicy>("\"replace\"").is_err());
+    }
+
+    #[test]
+    fn pattern_without_matches_fails_unless_optional() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("Plugin")).unwrap();
+        fs::write(src.join("Plugin").join("a.auf"), b"a").unwrap();
+        let dst = tmp.path().join("dst");
+        let backup_root = tmp.path().join("backups");
+
+        let from = pattern(&src, "*/*.aui2");
+        let err = copy_item(&from, &dst, OverwritePolicy::Overwrite, false, &backup_root, None).unwrap_err();
+        assert_eq!(err.code(), "COPY_NO_MATCH");
+        assert!(!dst.exists());
+
+        // optional なら何もせずに成功する（to のフォルダも作らない）
+        let report = copy_item(&from, &dst, OverwritePolicy::Overwrite, true, &backup_root, None).unwrap();
+        assert!(report.copied.is_empty() && report.skipped.is_empty());
+        assert!(!dst.exists());
+
+        // パターンの起点のフォルダが無いのは optional でも失敗する
+        let missing = pattern(&tmp.path().join("missing"), "*.auf");
+        assert_eq!(copy_item(&missing, &dst, OverwritePolicy::Overwrite, true, &backup_root, None).unwrap_err().code(), "COPY_SOURCE_NOT_FOUND");
+
+        let report = copy_item(&pattern(&src, "*/*.AUF"), &dst, OverwritePolicy::Overwrite, false, &backup_root, None).unwrap();
+        assert_eq!(report.copied.len(), 1);
+        assert_eq!(fs::read(dst.join("a.auf")).unwrap(), b"a");
+    }
+}
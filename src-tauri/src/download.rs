@@ -0,0 +1,2009 @@
+// -----------------------
+// ダウンロード処理（direct / GitHub / Google Drive / BOOTH 共通）
+// -----------------------
+//
+// - ソースごとの違い（URL の解決、Cookie、レスポンスの検査）は Source に閉じ込める
+// - 保存ファイル名は Content-Disposition → リダイレクト後の URL → 元の URL の順に決める
+// - それ以外（レジューム、ハッシュ検証、イベント送信）は download() で共通に処理する
+// - イベントは download:progress / download:done / download:error が基本で、すべて taskId をキーにする
+// - 実行中のタスクは TaskRegistry に登録され、cancel_download / pause_download / resume_download で操作できる
+// - 同時実行数（全体・ホストごと）と帯域の上限は DownloadQueue で管理し、download:queue で状態を通知する
+// - 一時的な失敗（接続エラー・5xx・429）は指数バックオフで再試行し、それでも駄目ならミラーを順に試す
+
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+use url::Url;
+
+use crate::cache::PackageCache;
+use crate::tasks::{Interrupt, TaskControl, TaskRegistry};
+
+const USER_AGENT: &str = "AviUtl2Catalog";
+
+// エラー型定義
+// - Display の先頭はフロント側で判定に使うエラーコード（AUTH_REQUIRED など）
+#[derive(thiserror::Error, Debug)]
+pub enum DownloadError {
+    #[error("INVALID_REQUEST: {0}")]
+    InvalidRequest(String),
+    #[error("NETWORK_ERROR: {0}")]
+    Net(String),
+    #[error("HTTP_ERROR:{status} {message}")]
+    Http { status: u16, message: String, retry_after: Option<Duration> },
+    #[error("IO_ERROR: {0}")]
+    Io(String),
+    #[error("AUTH_REQUIRED")]
+    AuthRequired,
+    #[error("AUTH_WINDOW_MISSING")]
+    AuthWindowMissing,
+    #[error("AUTH_COOKIE_FETCH_FAILED: {0}")]
+    AuthCookieFetchFailed(String),
+    #[error("SOURCE_UNRESOLVED: {0}")]
+    SourceUnresolved(String),
+    #[error("DRIVE_CONFIRM_FAILED: {0}")]
+    DriveConfirmFailed(String),
+    #[error("SIZE_MISMATCH: expected {expected} bytes, got {actual} bytes")]
+    SizeMismatch { expected: u64, actual: u64 },
+    #[error("HASH_MISMATCH: expected {algorithm}={expected}, got {actual}")]
+    HashMismatch { algorithm: &'static str, expected: String, actual: String },
+    // 一時停止（run_task の外には出ない。保存先が決まっていれば .part のパスを持つ）
+    #[error("PAUSED")]
+    Paused(Option<PathBuf>),
+    #[error("CANCELLED")]
+    Cancelled,
+}
+
+impl DownloadError {
+    /// download:error イベントの code に入れる値
+    pub fn code(&self) -> &'static str {
+        match self {
+            DownloadError::InvalidRequest(_) => "INVALID_REQUEST",
+            DownloadError::Net(_) => "NETWORK_ERROR",
+            DownloadError::Http { .. } => "HTTP_ERROR",
+            DownloadError::Io(_) => "IO_ERROR",
+            DownloadError::AuthRequired => "AUTH_REQUIRED",
+            DownloadError::AuthWindowMissing => "AUTH_WINDOW_MISSING",
+            DownloadError::AuthCookieFetchFailed(_) => "AUTH_COOKIE_FETCH_FAILED",
+            DownloadError::SourceUnresolved(_) => "SOURCE_UNRESOLVED",
+            DownloadError::DriveConfirmFailed(_) => "DRIVE_CONFIRM_FAILED",
+            DownloadError::SizeMismatch { .. } => "SIZE_MISMATCH",
+            DownloadError::HashMismatch { .. } => "HASH_MISMATCH",
+            DownloadError::Paused(_) => "PAUSED",
+            DownloadError::Cancelled => "CANCELLED",
+        }
+    }
+
+    /// 時間をおけば成功する可能性がある失敗か（接続エラー・5xx・429）
+    fn is_transient(&self) -> bool {
+        match self {
+            DownloadError::Net(_) => true,
+            DownloadError::Http { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+
+    /// 別のミラーで成功する可能性がある失敗か（ローカルの I/O や認証の問題は除く）
+    fn allows_mirror_fallback(&self) -> bool {
+        !matches!(
+            self,
+            DownloadError::Io(_)
+                | DownloadError::AuthRequired
+                | DownloadError::AuthWindowMissing
+                | DownloadError::AuthCookieFetchFailed(_)
+                | DownloadError::Paused(_)
+                | DownloadError::Cancelled
+        )
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(e: io::Error) -> Self {
+        DownloadError::Io(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        DownloadError::Net(e.to_string())
+    }
+}
+
+// 通信エラーと 5xx は再試行の対象にし、それ以外（パターン不一致やレート制限）は解決失敗として扱う
+impl From<crate::github::GithubError> for DownloadError {
+    fn from(e: crate::github::GithubError) -> Self {
+        use crate::github::GithubError;
+        match e {
+            GithubError::Net(message) => DownloadError::Net(message),
+            GithubError::Http(status, message) if status >= 500 => DownloadError::Http { status, message, retry_after: None },
+            other => DownloadError::SourceUnresolved(other.to_string()),
+        }
+    }
+}
+
+// -----------------------
+// 共有 HTTP クライアントとキュー（Tauri の State として保持）
+// -----------------------
+
+/// ダウンロード全体で共有する状態（接続プールを使い回すため Client は 1 つだけ作る）
+pub struct DownloadManager {
+    client: reqwest::Client,
+    queue: DownloadQueue,
+    throttle: Throttle,
+}
+
+impl DownloadManager {
+    pub fn new(limits: DownloadLimits) -> reqwest::Result<Self> {
+        let client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
+        Ok(Self { client, queue: DownloadQueue::new(limits), throttle: Throttle::default() })
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// 設定変更を実行中のキューに反映する
+    pub fn set_limits(&self, limits: DownloadLimits) {
+        self.queue.set_limits(limits);
+    }
+}
+
+// -----------------------
+// 同時実行数と帯域の制御
+// -----------------------
+
+/// settings.json から読み込む上限値
+#[derive(Clone, Copy, Debug)]
+pub struct DownloadLimits {
+    pub max_parallel: usize,
+    pub per_host: usize,
+    // 全タスク合計の bytes/sec（0 は無制限）
+    pub bytes_per_sec: u64,
+}
+
+impl DownloadLimits {
+    /// 0 は既定値（同時 3 件・同一ホスト 2 件・帯域無制限）として扱う
+    pub fn from_settings(settings: &crate::paths::Settings) -> Self {
+        let or_default = |v: u32, default: usize| if v == 0 { default } else { v as usize };
+        Self {
+            max_parallel: or_default(settings.download_max_parallel, 3),
+            per_host: or_default(settings.download_per_host_limit, 2),
+            bytes_per_sec: settings.download_bandwidth_limit,
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueCounts {
+    queued: usize,
+    active: usize,
+    // キューが空になってから完了した件数（「全部更新」の進捗表示用）
+    done: usize,
+    per_host: HashMap<String, usize>,
+}
+
+/// 全体とホストごとの同時実行数を制限する待ち行列
+/// - 上限は実行中でも変更できるよう、セマフォではなくカウンタと Notify で管理する
+struct DownloadQueue {
+    state: Mutex<(DownloadLimits, QueueCounts)>,
+    notify: Notify,
+}
+
+/// キューの状態（download:queue のペイロード）
+#[derive(Clone, Copy)]
+struct QueueSnapshot {
+    queued: usize,
+    active: usize,
+    done: usize,
+}
+
+impl DownloadQueue {
+    fn new(limits: DownloadLimits) -> Self {
+        Self { state: Mutex::new((limits, QueueCounts::default())), notify: Notify::new() }
+    }
+
+    fn set_limits(&self, limits: DownloadLimits) {
+        if let Ok(mut state) = self.state.lock() {
+            state.0 = limits;
+        }
+        self.notify.notify_waiters();
+    }
+
+    fn bytes_per_sec(&self) -> u64 {
+        self.state.lock().map(|s| s.0.bytes_per_sec).unwrap_or(0)
+    }
+
+    fn snapshot(counts: &QueueCounts) -> QueueSnapshot {
+        QueueSnapshot { queued: counts.queued, active: counts.active, done: counts.done }
+    }
+
+    /// 空きが出るまで待って実行枠を確保する（戻り値が破棄されると枠を返す）
+    async fn acquire<'a>(&'a self, host: &str, events: &TaskEvents<'_>) -> QueueSlot<'a> {
+        let mut slot = QueueSlot { queue: self, host: host.to_string(), active: false };
+        let snapshot = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let counts = &mut state.1;
+            // 空のキューに追加された場合は完了件数を数え直す
+            if counts.queued == 0 && counts.active == 0 {
+                counts.done = 0;
+            }
+            counts.queued += 1;
+            Self::snapshot(counts)
+        };
+        events.queue("queued", snapshot);
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                let (limits, counts) = &mut *state;
+                let host_active = counts.per_host.get(host).copied().unwrap_or(0);
+                if counts.active < limits.max_parallel.max(1) && host_active < limits.per_host.max(1) {
+                    counts.queued -= 1;
+                    counts.active += 1;
+                    *counts.per_host.entry(host.to_string()).or_insert(0) += 1;
+                    slot.active = true;
+                    let snapshot = Self::snapshot(counts);
+                    drop(state);
+                    events.queue("active", snapshot);
+                    return slot;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// タスクの終了を記録する
+    fn finish(&self) -> QueueSnapshot {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.1.done += 1;
+        Self::snapshot(&state.1)
+    }
+}
+
+/// 確保した実行枠（待機中に破棄された場合は待ち件数だけ戻す）
+struct QueueSlot<'a> {
+    queue: &'a DownloadQueue,
+    host: String,
+    active: bool,
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.queue.state.lock() {
+            let counts = &mut state.1;
+            if !self.active {
+                counts.queued = counts.queued.saturating_sub(1);
+            } else {
+                counts.active = counts.active.saturating_sub(1);
+                if let Some(n) = counts.per_host.get_mut(&self.host) {
+                    *n = n.saturating_sub(1);
+                    if *n == 0 {
+                        counts.per_host.remove(&self.host);
+                    }
+                }
+            }
+        }
+        self.queue.notify.notify_waiters();
+    }
+}
+
+/// 全タスク共通の帯域制限
+/// - 受信したバイト数に応じて送信枠の終了時刻を予約し、その時刻まで待つ
+#[derive(Default)]
+struct Throttle {
+    next_free: Mutex<Option<tokio::time::Instant>>,
+}
+
+impl Throttle {
+    async fn consume(&self, bytes: usize, bytes_per_sec: u64) {
+        if bytes_per_sec == 0 || bytes == 0 {
+            return;
+        }
+        let until = {
+            let mut next_free = self.next_free.lock().unwrap_or_else(|e| e.into_inner());
+            let now = tokio::time::Instant::now();
+            let start = next_free.filter(|t| *t > now).unwrap_or(now);
+            let end = start + Duration::from_secs_f64(bytes as f64 / bytes_per_sec as f64);
+            *next_free = Some(end);
+            end
+        };
+        tokio::time::sleep_until(until).await;
+    }
+}
+
+// -----------------------
+// ソースの定義と解決
+// -----------------------
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GithubSource {
+    pub owner: String,
+    pub repo: String,
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DriveSource {
+    pub id: String,
+}
+
+/// カタログの installer.source をそのまま受け取る形
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct InstallerSource {
+    pub direct: Option<String>,
+    pub booth: Option<String>,
+    pub github: Option<GithubSource>,
+    #[serde(rename = "GoogleDrive")]
+    pub google_drive: Option<DriveSource>,
+    pub expected: Option<ExpectedDigest>,
+    // 本来のソースで失敗したときに順に試す URL
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+impl InstallerSource {
+    /// Drive → BOOTH → direct → GitHub の優先順位でソースを決定（従来の JS 版と同じ順）
+    pub fn to_source(&self, session_label: Option<String>) -> Result<Source, DownloadError> {
+        let non_empty = |s: &Option<String>| s.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+        if let Some(drive) = self.google_drive.as_ref().filter(|d| !d.id.trim().is_empty()) {
+            return Ok(Source::GoogleDrive { id: drive.id.trim().to_string() });
+        }
+        if let Some(url) = non_empty(&self.booth) {
+            return Ok(Source::Booth { url, session_label: booth_session_label(session_label) });
+        }
+        if let Some(url) = non_empty(&self.direct) {
+            return Ok(Source::Direct { url });
+        }
+        if let Some(gh) = self.github.as_ref().filter(|g| !g.owner.is_empty() && !g.repo.is_empty()) {
+            return Ok(Source::Github(gh.clone()));
+        }
+        Err(DownloadError::InvalidRequest("download source is not specified".to_string()))
+    }
+
+    /// 本来のソースとミラーを試す順に並べる（本来のソースが無い場合はミラーだけ）
+    pub fn to_sources(&self, session_label: Option<String>) -> Result<(Source, Vec<Source>), DownloadError> {
+        let mut mirrors: Vec<Source> = self.mirrors.iter().map(|m| m.trim()).filter(|m| !m.is_empty()).map(|url| Source::Direct { url: url.to_string() }).collect();
+        match self.to_source(session_label) {
+            Ok(primary) => Ok((primary, mirrors)),
+            Err(_) if !mirrors.is_empty() => {
+                let primary = mirrors.remove(0);
+                Ok((primary, mirrors))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// ダウンロード元（新しい配布元はここに追加する）
+#[derive(Clone, Debug)]
+pub enum Source {
+    Direct { url: String },
+    Github(GithubSource),
+    GoogleDrive { id: String },
+    Booth { url: String, session_label: String },
+}
+
+/// 解決済みのリクエスト内容
+struct Resolved {
+    url: String,
+    cookie: Option<String>,
+    // 元の URL から決まるファイル名（Content-Disposition が無い場合の候補。None の場合は URL からは決めない）
+    name_hint: Option<String>,
+    // .part の再開判定に使うキー（確認ページで URL が変わる Google Drive 以外は URL と同じ）
+    resume_key: String,
+}
+
+impl Resolved {
+    fn request(&self, client: &reqwest::Client) -> reqwest::RequestBuilder {
+        let req = client.get(&self.url);
+        match &self.cookie {
+            Some(cookie) => req.header(reqwest::header::COOKIE, cookie.as_str()),
+            None => req,
+        }
+    }
+}
+
+fn booth_session_label(label: Option<String>) -> String {
+    let label = label.unwrap_or_default().trim().to_string();
+    if label.is_empty() {
+        "booth-auth".to_string()
+    } else {
+        label
+    }
+}
+
+/// https:// のみ許可して URL を解析
+fn parse_https(url: &str) -> Result<Url, DownloadError> {
+    if !url.trim_start().to_ascii_lowercase().starts_with("https://") {
+        return Err(DownloadError::InvalidRequest("Only https:// is permitted".to_string()));
+    }
+    Url::parse(url.trim()).map_err(|e| DownloadError::InvalidRequest(format!("invalid url: {}", e)))
+}
+
+impl Source {
+    async fn resolve(&self, app: &AppHandle, client: &reqwest::Client) -> Result<Resolved, DownloadError> {
+        match self {
+            Source::Direct { url } => {
+                let parsed = parse_https(url)?;
+                Ok(Resolved {
+                    url: parsed.to_string(),
+                    cookie: None,
+                    name_hint: Some(filename_from_url(&parsed)),
+                    resume_key: parsed.to_string(),
+                })
+            }
+            Source::Github(gh) => {
+                let asset = crate::github::resolve_asset(app, client, &gh.owner, &gh.repo, gh.pattern.as_deref()).await?;
+                let parsed = parse_https(&asset.download_url)?;
+                Ok(Resolved {
+                    url: parsed.to_string(),
+                    cookie: None,
+                    name_hint: Some(filename_from_url(&parsed)),
+                    resume_key: parsed.to_string(),
+                })
+            }
+            Source::GoogleDrive { id } => {
+                let url = format!("https://drive.google.com/uc?export=download&id={}", id);
+                Ok(Resolved { url, cookie: None, name_hint: None, resume_key: format!("drive:{}", id) })
+            }
+            Source::Booth { url, session_label } => {
+                let parsed = parse_https(url)?;
+                // Webview の Cookie を Rust 側リクエストに引き継ぐ
+                let session_window = app.get_webview_window(session_label).ok_or(DownloadError::AuthWindowMissing)?;
+                let cookies = session_window.cookies_for_url(parsed.clone()).map_err(|e| DownloadError::AuthCookieFetchFailed(e.to_string()))?;
+                let cookie = cookies.iter().map(|c| format!("{}={}", c.name(), c.value())).collect::<Vec<_>>().join("; ");
+                Ok(Resolved {
+                    url: parsed.to_string(),
+                    cookie: if cookie.is_empty() { None } else { Some(cookie) },
+                    name_hint: Some(filename_from_url(&parsed)),
+                    resume_key: parsed.to_string(),
+                })
+            }
+        }
+    }
+
+    /// 保存前のレスポンス検査（BOOTH の未ログイン判定など）
+    fn check_response(&self, response: &reqwest::Response) -> Result<(), DownloadError> {
+        match self {
+            Source::Booth { .. } => {
+                // 未ログインのリダイレクトは保存しない
+                if crate::is_booth_login_url(response.url()) {
+                    return Err(DownloadError::AuthRequired);
+                }
+                // HTML かつ Content-Disposition が無い場合はログイン画面とみなす
+                if is_html_page(response) {
+                    return Err(DownloadError::AuthRequired);
+                }
+            }
+            // 確認ページを通過した後も HTML の場合はファイルとして保存しない
+            Source::GoogleDrive { .. } if is_html_page(response) => {
+                return Err(DownloadError::DriveConfirmFailed("Google Drive returned an HTML page instead of the file".to_string()));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Google Drive の大きなファイルで返るウイルススキャン確認ページを通過する
+    /// - 確認ページでなければレスポンスをそのまま返す
+    /// - 確認フォームの送信先を resolved.url に反映する（再開判定は resume_key で行うので影響しない）
+    async fn pass_confirmation(&self, client: &reqwest::Client, resolved: &mut Resolved, response: reqwest::Response) -> Result<reqwest::Response, DownloadError> {
+        let Source::GoogleDrive { id } = self else {
+            return Ok(response);
+        };
+        if !is_html_page(&response) {
+            return Ok(response);
+        }
+        let warning = download_warning_cookie(response.headers());
+        let page_url = response.url().clone();
+        let html = response.text().await?;
+        let confirm_url = drive_confirm_url(&page_url, id, &html, warning.as_ref().map(|(_, token)| token.as_str()))
+            .ok_or_else(|| DownloadError::DriveConfirmFailed("confirmation form not found (the file may be private or over its download quota)".to_string()))?;
+        resolved.url = confirm_url;
+        if let Some((pair, _)) = warning {
+            resolved.cookie = Some(pair);
+        }
+        let response = ensure_success(resolved.request(client).send().await?).await?;
+        self.check_response(&response)?;
+        Ok(response)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Source::Direct { url } | Source::Booth { url, .. } => url.clone(),
+            Source::Github(gh) => format!("github:{}/{}", gh.owner, gh.repo),
+            Source::GoogleDrive { id } => format!("drive:{}", id),
+        }
+    }
+}
+
+/// HTML かつ Content-Disposition が無いレスポンス（ファイルではなくページが返っている）
+fn is_html_page(response: &reqwest::Response) -> bool {
+    use reqwest::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or("").to_ascii_lowercase();
+    content_type.contains("text/html") && response.headers().get(CONTENT_DISPOSITION).is_none()
+}
+
+/// 旧形式の確認ページで送られる download_warning_* Cookie（"名前=値" と値）
+fn download_warning_cookie(headers: &reqwest::header::HeaderMap) -> Option<(String, String)> {
+    headers.get_all(reqwest::header::SET_COOKIE).iter().filter_map(|v| v.to_str().ok()).find_map(|raw| {
+        let pair = raw.split(';').next()?.trim();
+        let (name, value) = pair.split_once('=')?;
+        name.starts_with("download_warning").then(|| (pair.to_string(), value.to_string()))
+    })
+}
+
+/// 確認ページの HTML から実際のダウンロード URL を組み立てる
+/// - 新形式: <form id="download-form" action="..."> と hidden input（id / export / confirm / uuid など）
+/// - 旧形式: リンクの confirm=XXXX、または download_warning Cookie の値
+fn drive_confirm_url(page_url: &Url, id: &str, html: &str, warning_token: Option<&str>) -> Option<String> {
+    use regex::Regex;
+    let unescape = |s: &str| s.replace("&amp;", "&");
+    let attr = |tag: &str, name: &str| Regex::new(&format!(r#"(?i)\b{}\s*=\s*"([^"]*)""#, name)).ok().and_then(|re| re.captures(tag).map(|c| unescape(&c[1])));
+
+    let form_re = Regex::new(r"(?is)<form\b([^>]*)>(.*?)</form>").ok()?;
+    let input_re = Regex::new(r"(?is)<input\b[^>]*>").ok()?;
+    for form in form_re.captures_iter(html) {
+        let Some(action) = attr(&form[1], "action") else {
+            continue;
+        };
+        if !form[1].contains("download-form") && !action.contains("download") {
+            continue;
+        }
+        let Ok(mut url) = page_url.join(&action) else {
+            continue;
+        };
+        {
+            let mut query = url.query_pairs_mut();
+            for input in input_re.find_iter(&form[2]) {
+                if let (Some(name), Some(value)) = (attr(input.as_str(), "name"), attr(input.as_str(), "value")) {
+                    query.append_pair(&name, &value);
+                }
+            }
+        }
+        return Some(url.to_string());
+    }
+
+    let token = Regex::new(r"confirm=([0-9A-Za-z_-]+)").ok()?.captures(html).map(|c| c[1].to_string()).or_else(|| warning_token.map(str::to_string))?;
+    Some(format!("https://drive.google.com/uc?export=download&confirm={}&id={}", token, id))
+}
+
+// -----------------------
+// ファイル名の決定
+// -----------------------
+
+/// 保存に使えるファイル名に整える（使えない場合は download.bin）
+pub(crate) fn sanitize_filename(name: &str) -> String {
+    portable_filename(name).unwrap_or_else(|| String::from("download.bin"))
+}
+
+// Windows でファイル名に使えないデバイス名（拡張子が付いていても使えない）
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// パス区切りと Windows の予約文字を _ に置き換える
+/// - 空・"." / ".."・デバイス名・（前後の空白を除いて）末尾がドットの名前は保存先を指せないので None
+fn portable_filename(name: &str) -> Option<String> {
+    let mut out = String::new();
+    for ch in name.chars() {
+        // forbid separators and reserved Windows characters
+        if matches!(ch, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || ch.is_control() {
+            out.push('_');
+        } else {
+            out.push(ch);
+        }
+    }
+    // 前後の空白は取り除き、それでも末尾がドットなら使わない
+    let trimmed = out.trim();
+    let stem = trimmed.split('.').next().unwrap_or_default().trim_end();
+    if trimmed.is_empty() || trimmed.ends_with('.') || RESERVED_NAMES.iter().any(|r| stem.eq_ignore_ascii_case(r)) {
+        return None;
+    }
+    Some(trimmed.to_string())
+}
+
+/// URL 末尾のパス要素からファイル名を推定
+fn filename_from_url(url: &Url) -> String {
+    url_last_segment(url).unwrap_or_else(|| "download.bin".to_string())
+}
+
+fn url_last_segment(url: &Url) -> Option<String> {
+    let raw = url.path_segments()?.rfind(|s| !s.is_empty())?;
+    portable_filename(&percent_decode_str(raw).decode_utf8_lossy())
+}
+
+/// 保存ファイル名を決める（Content-Disposition → リダイレクト後の URL → 元の URL）
+/// - URL から決める場合は拡張子のある方を優先する（/download や /latest のような名前で保存しないため）
+/// - name_hint が無いソース（Google Drive）は URL からは決めない
+fn choose_filename(response: &reqwest::Response, name_hint: Option<&str>) -> Option<String> {
+    if let Some(name) = filename_from_headers(response.headers()) {
+        return Some(name);
+    }
+    let original = name_hint?;
+    let candidates: Vec<String> = url_last_segment(response.url()).into_iter().chain(std::iter::once(original.to_string())).collect();
+    candidates.iter().find(|name| Path::new(name).extension().is_some()).or(candidates.first()).cloned()
+}
+
+/// Content-Disposition（RFC 6266）の filename* / filename からファイル名を取得
+/// - filename* を優先し、RFC 5987 の charset（UTF-8 / ISO-8859-1 など）で復号する
+/// - パス区切りを含む場合は最後の要素だけを使い、保存に使えない名前（".." やデバイス名など）は None
+fn filename_from_headers(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let raw = headers.get(reqwest::header::CONTENT_DISPOSITION)?;
+    let value = String::from_utf8_lossy(raw.as_bytes());
+    let params = disposition_params(&value);
+    let find = |key: &str| params.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.as_str());
+
+    let name = find("filename*").and_then(decode_ext_value).or_else(|| find("filename").map(str::to_string))?;
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(&name);
+    portable_filename(base)
+}
+
+/// Content-Disposition のパラメータを (名前, 値) に分解する（引用符内の ; と \" に対応）
+fn disposition_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    // 先頭の disposition-type（attachment / inline）は読み飛ばす
+    let Some((_, rest)) = value.split_once(';') else {
+        return params;
+    };
+    let mut chars = rest.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ';').is_some() {}
+        let key: String = std::iter::from_fn(|| chars.next_if(|c| *c != '=' && *c != ';')).collect();
+        if key.is_empty() && chars.peek().is_none() {
+            return params;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut val = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => val.extend(chars.next()),
+                    '"' => break,
+                    _ => val.push(c),
+                }
+            }
+        } else {
+            val = std::iter::from_fn(|| chars.next_if(|c| *c != ';')).collect::<String>().trim().to_string();
+        }
+        params.push((key.trim().to_string(), val));
+    }
+}
+
+/// RFC 5987 の拡張値（charset'lang'%XX...）を復号
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _lang, encoded) = (parts.next()?, parts.next()?, parts.next()?);
+    let bytes: Vec<u8> = percent_encoding::percent_decode_str(encoded).collect();
+    if charset.eq_ignore_ascii_case("utf-8") || charset.is_empty() {
+        return Some(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    let encoding = encoding_rs::Encoding::for_label(charset.as_bytes())?;
+    Some(encoding.decode_without_bom_handling(&bytes).0.into_owned())
+}
+
+// -----------------------
+// ハッシュ検証
+// -----------------------
+
+/// ダウンロード後に検証する期待値（カタログの XXH3_128 / sha256 とサイズ）
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct ExpectedDigest {
+    #[serde(alias = "XXH3_128")]
+    pub xxh3_128: Option<String>,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// ストリーミング中に計算するハッシュ（期待値が指定されたものだけ計算する）
+struct DigestState {
+    xxh3: Option<xxhash_rust::xxh3::Xxh3>,
+    sha256: Option<sha2::Sha256>,
+}
+
+impl DigestState {
+    fn new(expected: Option<&ExpectedDigest>) -> Self {
+        use sha2::Digest;
+        let expected = expected.cloned().unwrap_or_default();
+        Self {
+            xxh3: expected.xxh3_128.filter(|h| !h.trim().is_empty()).map(|_| xxhash_rust::xxh3::Xxh3::new()),
+            sha256: expected.sha256.filter(|h| !h.trim().is_empty()).map(|_| sha2::Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        if let Some(h) = self.xxh3.as_mut() {
+            h.update(data);
+        }
+        if let Some(h) = self.sha256.as_mut() {
+            h.update(data);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.xxh3.is_some() || self.sha256.is_some()
+    }
+
+    /// 再開時は既存の .part の内容を先にハッシュへ流し込む
+    fn feed_file(&mut self, path: &Path) -> io::Result<()> {
+        use std::io::Read;
+        if !self.is_active() {
+            return Ok(());
+        }
+        let mut f = File::open(path)?;
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let n = f.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.update(&buf[..n]);
+        }
+    }
+
+    /// 期待値と比較する
+    fn verify(self, expected: Option<&ExpectedDigest>, size: u64) -> Result<(), DownloadError> {
+        use sha2::Digest;
+        let Some(expected) = expected else {
+            return Ok(());
+        };
+        if let Some(want) = expected.size {
+            if want != size {
+                return Err(DownloadError::SizeMismatch { expected: want, actual: size });
+            }
+        }
+        if let (Some(h), Some(want)) = (self.xxh3, expected.xxh3_128.as_deref()) {
+            let got = format!("{:032x}", h.digest128());
+            if !got.eq_ignore_ascii_case(want.trim()) {
+                return Err(DownloadError::HashMismatch { algorithm: "xxh3_128", expected: want.trim().to_string(), actual: got });
+            }
+        }
+        if let (Some(h), Some(want)) = (self.sha256, expected.sha256.as_deref()) {
+            let got = format!("{:x}", h.finalize());
+            if !got.eq_ignore_ascii_case(want.trim()) {
+                return Err(DownloadError::HashMismatch { algorithm: "sha256", expected: want.trim().to_string(), actual: got });
+            }
+        }
+        Ok(())
+    }
+}
+
+// -----------------------
+// .part への書き込みとレジューム
+// -----------------------
+
+/// .part の横に保存するレジューム用メタ情報（If-Range に使う検証子）
+#[derive(serde::Serialize, Deserialize, Default)]
+struct PartMeta {
+    // 再開キー（Resolved::resume_key）
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // 前回実際に要求した URL（Google Drive の確認後の URL。再開時は確認ページを経由せずここへ Range を送る）
+    #[serde(default)]
+    request_url: Option<String>,
+}
+
+/// 最終ファイルに対応する .part と .part.json のパス
+fn part_paths(final_path: &Path) -> (PathBuf, PathBuf) {
+    let mut part = final_path.as_os_str().to_owned();
+    part.push(".part");
+    let mut meta = final_path.as_os_str().to_owned();
+    meta.push(".part.json");
+    (PathBuf::from(part), PathBuf::from(meta))
+}
+
+/// 途中ファイルとメタ情報を削除
+fn discard_part(final_path: &Path) {
+    let (part, meta) = part_paths(final_path);
+    let _ = fs::remove_file(part);
+    let _ = fs::remove_file(meta);
+}
+
+/// 前回の .part から再開できる場合の情報
+struct ResumeState {
+    offset: u64,
+    // If-Range の値
+    validator: String,
+    request_url: Option<String>,
+}
+
+/// 前回の .part から再開できるか調べる
+/// - 再開キー（通常は URL）が異なる、または検証子（強い ETag / Last-Modified）が無い場合は再開しない
+fn resume_state(final_path: &Path, resume_key: &str) -> Option<ResumeState> {
+    let (part, meta) = part_paths(final_path);
+    let len = fs::metadata(&part).ok()?.len();
+    if len == 0 {
+        return None;
+    }
+    let meta: PartMeta = serde_json::from_str(&fs::read_to_string(meta).ok()?).ok()?;
+    if meta.url != resume_key {
+        return None;
+    }
+    let validator = meta.etag.filter(|e| !e.starts_with("W/")).or(meta.last_modified)?;
+    Some(ResumeState { offset: len, validator, request_url: meta.request_url })
+}
+
+/// Content-Range ("bytes START-END/TOTAL") から開始位置と全体サイズを取り出す
+fn parse_content_range(headers: &reqwest::header::HeaderMap) -> Option<(u64, Option<u64>)> {
+    let raw = headers.get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = raw.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse::<u64>().ok()?;
+    Some((start, total.trim().parse::<u64>().ok()))
+}
+
+/// 失敗ステータスをエラーに変換（本文の先頭 500 文字を添える）
+async fn ensure_success(response: reqwest::Response) -> Result<reqwest::Response, DownloadError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = parse_retry_after(response.headers());
+    let text = response.text().await.unwrap_or_default();
+    let snippet: String = text.chars().take(500).collect();
+    // ステータスコードは Display（HTTP_ERROR:{status}）で付くので、message には理由と本文だけを入れる
+    let reason = status.canonical_reason().unwrap_or_default();
+    Err(DownloadError::Http {
+        status: status.as_u16(),
+        message: if snippet.trim().is_empty() { reason.to_string() } else { format!("{}: {}", reason, snippet) },
+        retry_after,
+    })
+}
+
+/// Retry-After（秒数または HTTP 日付）を待ち時間に変換
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let raw = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = raw.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(raw).ok()?;
+    let secs = (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds().max(0);
+    Some(Duration::from_secs(secs as u64))
+}
+
+// -----------------------
+// 再試行の間隔
+// -----------------------
+
+// 1 ソースあたりの再試行回数（初回を含めて最大 5 回）
+const MAX_RETRIES: u32 = 4;
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+// Retry-After が極端に長い場合の上限
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(120);
+
+/// 次の再試行までの待ち時間
+/// - Retry-After があればそれに従い、無ければ指数バックオフの後半 50〜100% を jitter で選ぶ
+fn retry_delay(retry: u32, retry_after: Option<Duration>) -> Duration {
+    if let Some(wait) = retry_after {
+        return wait.min(RETRY_AFTER_MAX);
+    }
+    let exp = BACKOFF_BASE.saturating_mul(1 << retry.saturating_sub(1).min(16)).min(BACKOFF_MAX);
+    let seed = chrono::Utc::now().timestamp_subsec_nanos() as u64;
+    exp.mul_f64(0.5 + 0.5 * jitter(seed, retry))
+}
+
+/// 0.0〜1.0 の値（時刻の seed と再試行回数を splitmix64 で混ぜる）
+/// - 同時に失敗した複数のダウンロードの再試行が揃わなければよいので、暗号的な乱数は使わない
+fn jitter(seed: u64, retry: u32) -> f64 {
+    let mut z = seed ^ (retry as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// -----------------------
+// イベント送信
+// -----------------------
+
+/// 1 タスク分のイベント送信（download:* 共通スキーマ）
+pub struct TaskEvents<'a> {
+    // None の場合は送信しない（テスト用）
+    app: Option<&'a AppHandle>,
+    task_id: &'a str,
+}
+
+impl<'a> TaskEvents<'a> {
+    pub fn new(app: &'a AppHandle, task_id: &'a str) -> Self {
+        Self { app: Some(app), task_id }
+    }
+
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        if let Some(app) = self.app {
+            let _ = app.emit(event, payload);
+        }
+    }
+
+    fn progress(&self, read: u64, total: Option<u64>, resumed_from: u64) {
+        self.emit("download:progress", serde_json::json!({ "taskId": self.task_id, "read": read, "total": total, "resumedFrom": resumed_from }));
+    }
+
+    fn done(&self, path: &Path) {
+        let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        self.emit("download:done", serde_json::json!({ "taskId": self.task_id, "path": path.to_string_lossy(), "name": name }));
+    }
+
+    fn error(&self, e: &DownloadError, attempts: u32) {
+        self.emit("download:error", serde_json::json!({ "taskId": self.task_id, "message": e.to_string(), "code": e.code(), "attempts": attempts }));
+    }
+
+    fn retry(&self, attempt: u32, delay: Duration, e: &DownloadError) {
+        self.emit(
+            "download:retry",
+            serde_json::json!({ "taskId": self.task_id, "attempt": attempt, "delayMs": delay.as_millis() as u64, "message": e.to_string(), "code": e.code() }),
+        );
+    }
+
+    fn paused(&self) {
+        self.emit("download:paused", serde_json::json!({ "taskId": self.task_id }));
+    }
+
+    fn resumed(&self) {
+        self.emit("download:resumed", serde_json::json!({ "taskId": self.task_id }));
+    }
+
+    fn cancelled(&self) {
+        self.emit("download:cancelled", serde_json::json!({ "taskId": self.task_id }));
+    }
+
+    fn queue(&self, state: &str, snapshot: QueueSnapshot) {
+        self.emit("download:queue", serde_json::json!({ "taskId": self.task_id, "state": state, "queued": snapshot.queued, "active": snapshot.active, "done": snapshot.done }));
+    }
+}
+
+// -----------------------
+// ダウンロード本体
+// -----------------------
+
+/// 1 件のダウンロード要求
+pub struct DownloadTask {
+    pub task_id: String,
+    pub source: Source,
+    // source で失敗したときに順に試す代替ソース
+    pub mirrors: Vec<Source>,
+    pub dest_dir: PathBuf,
+    // 保存ファイル名を固定する場合に指定
+    pub file_name: Option<String>,
+    pub expected: Option<ExpectedDigest>,
+}
+
+/// ダウンロードを実行し、done / error / cancelled イベントを送信する
+/// - 一時停止中は .part を残して接続を切り、再開時に Range 付きで取り直す
+pub async fn run_task(app: &AppHandle, manager: &DownloadManager, task: &DownloadTask) -> Result<PathBuf, DownloadError> {
+    let registry = app.state::<TaskRegistry>();
+    let control = registry.register_pausable(&task.task_id);
+    let events = TaskEvents::new(app, &task.task_id);
+    let result = run_task_inner(app, manager, task, &events, &control).await;
+    events.queue("done", manager.queue.finish());
+    result
+}
+
+/// 実行中タスクの共有情報（download() 以下に渡す）
+struct TaskContext<'a> {
+    manager: &'a DownloadManager,
+    events: &'a TaskEvents<'a>,
+    control: &'a TaskControl,
+}
+
+async fn run_task_inner(app: &AppHandle, manager: &DownloadManager, task: &DownloadTask, events: &TaskEvents<'_>, control: &TaskControl) -> Result<PathBuf, DownloadError> {
+    let ctx = TaskContext { manager, events, control };
+    let sources: Vec<&Source> = std::iter::once(&task.source).chain(task.mirrors.iter()).collect();
+    let mut index = 0;
+    // 全ソース合計の試行回数と、現在のソースでの再試行回数
+    let mut attempts: u32 = 0;
+    let mut retries: u32 = 0;
+    let mut backoff: Option<Duration> = None;
+    // 期待ハッシュが一致するキャッシュがあれば URL 解決も省略する
+    if let Some(path) = restore_cached(app, &ctx, task, None).await {
+        events.done(&path);
+        return Ok(path);
+    }
+    loop {
+        let source = sources[index];
+        let delay = backoff.take();
+        let result = tokio::select! {
+            biased;
+            r = async {
+                if let Some(delay) = delay {
+                    tokio::time::sleep(delay).await;
+                }
+                download(app, &ctx, task, source).await
+            } => r,
+            // 再試行の待機中・接続中・URL 解決中に中断された場合（保存先はまだ決まっていない）
+            reason = control.interrupted() => Err(match reason {
+                Interrupt::Paused => DownloadError::Paused(None),
+                Interrupt::Cancelled => DownloadError::Cancelled,
+            }),
+        };
+        match result {
+            Ok(path) => {
+                events.done(&path);
+                return Ok(path);
+            }
+            Err(DownloadError::Paused(final_path)) => {
+                events.paused();
+                if control.wait_resumed().await {
+                    events.resumed();
+                    continue;
+                }
+                if let Some(final_path) = final_path {
+                    discard_part(&final_path);
+                }
+                events.cancelled();
+                return Err(DownloadError::Cancelled);
+            }
+            Err(DownloadError::Cancelled) => {
+                events.cancelled();
+                return Err(DownloadError::Cancelled);
+            }
+            Err(e) => {
+                attempts += 1;
+                if e.is_transient() && retries < MAX_RETRIES {
+                    retries += 1;
+                    let delay = retry_delay(retries, if let DownloadError::Http { retry_after, .. } = &e { *retry_after } else { None });
+                    crate::log_info(app, &format!("download retry (task={}, source={}, attempt={}, wait={:?}): {}", task.task_id, source.describe(), attempts, delay, e));
+                    events.retry(attempts, delay, &e);
+                    backoff = Some(delay);
+                    continue;
+                }
+                if e.allows_mirror_fallback() && index + 1 < sources.len() {
+                    index += 1;
+                    retries = 0;
+                    crate::log_info(
+                        app,
+                        &format!("download falling back to mirror (task={}, failed={}, next={}): {}", task.task_id, source.describe(), sources[index].describe(), e),
+                    );
+                    continue;
+                }
+                crate::log_error(app, &format!("download failed (task={}, source={}, attempts={}): {}", task.task_id, source.describe(), attempts, e));
+                events.error(&e, attempts);
+                return Err(e);
+            }
+        }
+    }
+}
+
+async fn download(app: &AppHandle, ctx: &TaskContext<'_>, task: &DownloadTask, source: &Source) -> Result<PathBuf, DownloadError> {
+    let client = ctx.manager.client();
+    fs::create_dir_all(&task.dest_dir).map_err(|e| DownloadError::Io(format!("failed to prepare destination directory: {}", e)))?;
+    let mut resolved = source.resolve(app, client).await?;
+    if let Some(path) = restore_cached(app, ctx, task, Some(&resolved.resume_key)).await {
+        return Ok(path);
+    }
+    let final_path = fetch(ctx, source, &mut resolved, task).await?;
+    store_in_cache(app, &final_path, &resolved.resume_key).await;
+    Ok(final_path)
+}
+
+/// 実行枠を確保してから保存する（一時停止中は枠を返し、再開時に並び直す）
+async fn fetch(ctx: &TaskContext<'_>, source: &Source, resolved: &mut Resolved, task: &DownloadTask) -> Result<PathBuf, DownloadError> {
+    let host = Url::parse(&resolved.url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default();
+    let _slot = ctx.manager.queue.acquire(&host, ctx.events).await;
+    let (final_path, response, requested_offset) = open_download(ctx.manager.client(), source, resolved, task).await?;
+    stream_to_part_file(ctx, resolved, &final_path, response, requested_offset, task.expected.as_ref()).await?;
+    Ok(final_path)
+}
+
+/// 保存先と保存するレスポンス、再開のために要求したオフセットを決める
+async fn open_download(client: &reqwest::Client, source: &Source, resolved: &mut Resolved, task: &DownloadTask) -> Result<(PathBuf, reqwest::Response, u64), DownloadError> {
+    Ok(match &task.file_name {
+        // 保存先が先に決まるので、.part があれば最初から Range 付きで要求する
+        // 再開できなければ、Google Drive の確認ページの通過と HTML の検査をファイル名が無い場合と同じように行う
+        Some(name) => {
+            let final_path = task.dest_dir.join(sanitize_filename(name));
+            match request_resume(client, source, resolved, &final_path, false).await? {
+                Some((response, offset)) => (final_path, response, offset),
+                None => (final_path, open_response(client, source, resolved).await?, 0),
+            }
+        }
+        // 保存ファイル名はレスポンスから決まるので、.part があればその後で取り直す
+        None => {
+            let response = open_response(client, source, resolved).await?;
+            let name = choose_filename(&response, resolved.name_hint.as_deref()).ok_or_else(|| DownloadError::SourceUnresolved("missing filename in response".to_string()))?;
+            let final_path = task.dest_dir.join(name);
+            match request_resume(client, source, resolved, &final_path, true).await? {
+                Some((resumed, offset)) => (final_path, resumed, offset),
+                None => (final_path, response, 0),
+            }
+        }
+    })
+}
+
+/// 最初のレスポンスを取得する（確認ページを通過し、保存してはいけないレスポンスはここで弾く）
+async fn open_response(client: &reqwest::Client, source: &Source, resolved: &mut Resolved) -> Result<reqwest::Response, DownloadError> {
+    let first = ensure_success(resolved.request(client).send().await?).await?;
+    let response = source.pass_confirmation(client, resolved, first).await?;
+    source.check_response(&response)?;
+    Ok(response)
+}
+
+/// 途中ファイルがあれば Range 付きで要求する
+/// - confirmed が false（まだ確認ページを通っていない）なら前回要求した URL を使い、true なら確認後の resolved.url を使う
+/// - 206 なら続きから追記し、200（If-Range の不一致や Range 非対応）なら返ってきた本体を先頭から保存する
+/// - 一時的な失敗（5xx・429）は .part を残して再試行に任せる
+/// - それ以外（412 や、確認 URL の期限切れで返る HTML など）は .part を消して None（通常のリクエストからやり直す）
+async fn request_resume(
+    client: &reqwest::Client,
+    source: &Source,
+    resolved: &mut Resolved,
+    final_path: &Path,
+    confirmed: bool,
+) -> Result<Option<(reqwest::Response, u64)>, DownloadError> {
+    use reqwest::header::{IF_RANGE, RANGE};
+
+    let Some(state) = resume_state(final_path, &resolved.resume_key) else {
+        return Ok(None);
+    };
+    let original_url = resolved.url.clone();
+    if let Some(url) = state.request_url.filter(|_| !confirmed) {
+        resolved.url = url;
+    }
+    let res = resolved.request(client).header(RANGE, format!("bytes={}-", state.offset)).header(IF_RANGE, state.validator).send().await?;
+    let status = res.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        // ensure_success は失敗ステータスを必ずエラーにする
+        return ensure_success(res).await.map(|_| None);
+    }
+    if status.is_success() && !is_html_page(&res) {
+        source.check_response(&res)?;
+        return Ok(Some((res, state.offset)));
+    }
+    resolved.url = original_url;
+    discard_part(final_path);
+    Ok(None)
+}
+
+/// パッケージキャッシュに一致するものがあれば保存先に配置する（コピーは spawn_blocking で行う）
+async fn restore_cached(app: &AppHandle, ctx: &TaskContext<'_>, task: &DownloadTask, key: Option<&str>) -> Option<PathBuf> {
+    app.try_state::<PackageCache>()?;
+    let handle = app.clone();
+    let (key, expected, dest_dir, file_name) = (key.map(str::to_string), task.expected.clone(), task.dest_dir.clone(), task.file_name.clone());
+    let path =
+        tauri::async_runtime::spawn_blocking(move || handle.state::<PackageCache>().restore(key.as_deref(), expected.as_ref(), &dest_dir, file_name.as_deref())).await.ok()??;
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    ctx.events.progress(size, Some(size), 0);
+    crate::log_info(app, &format!("download served from package cache (task={}, path={})", task.task_id, path.display()));
+    Some(path)
+}
+
+/// ダウンロードしたファイルをパッケージキャッシュに追加する（失敗してもダウンロード自体は成功扱い）
+async fn store_in_cache(app: &AppHandle, path: &Path, key: &str) {
+    if app.try_state::<PackageCache>().is_none() {
+        return;
+    }
+    let handle = app.clone();
+    let path_buf = path.to_path_buf();
+    let key = key.to_string();
+    let result = tauri::async_runtime::spawn_blocking(move || handle.state::<PackageCache>().insert(&path_buf, &key)).await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => crate::log_error(app, &format!("failed to store download in package cache ({}): {}", path.display(), e)),
+        Err(e) => crate::log_error(app, &format!("failed to store download in package cache ({}): {}", path.display(), e)),
+    }
+}
+
+/// レスポンス本体を .part に書き込み、完了後に最終パスへリネームする
+/// - 206 かつ Content-Range が要求オフセットと一致する場合のみ追記し、それ以外は先頭から書き直す
+/// - 失敗時は .part を残し、次回のダウンロードで再開できるようにする
+/// - 期待値が指定されている場合は書き込みと同時にハッシュを計算し、不一致なら .part を削除する
+/// - 一時停止時は .part を残し、キャンセル時は削除する
+async fn stream_to_part_file(
+    ctx: &TaskContext<'_>,
+    resolved: &Resolved,
+    final_path: &Path,
+    mut response: reqwest::Response,
+    requested_offset: u64,
+    expected: Option<&ExpectedDigest>,
+) -> Result<(), DownloadError> {
+    use reqwest::header::{ETAG, LAST_MODIFIED};
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    let (part_path, meta_path) = part_paths(final_path);
+    let content_range = parse_content_range(response.headers());
+    let resumed = requested_offset > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT && content_range.map(|(start, _)| start) == Some(requested_offset);
+    let offset = if resumed { requested_offset } else { 0 };
+    let total_opt = if resumed { content_range.and_then(|(_, total)| total).or_else(|| response.content_length().map(|len| offset + len)) } else { response.content_length() };
+
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(&part_path)
+    } else {
+        // 新規ダウンロード時は検証子を保存しておく
+        let header_str = |name: reqwest::header::HeaderName| response.headers().get(name).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+        let meta = PartMeta {
+            url: resolved.resume_key.clone(),
+            etag: header_str(ETAG),
+            last_modified: header_str(LAST_MODIFIED),
+            request_url: Some(resolved.url.clone()),
+        };
+        let _ = fs::write(&meta_path, serde_json::to_string(&meta).unwrap_or_default());
+        OpenOptions::new().create(true).truncate(true).write(true).open(&part_path)
+    }
+    .map_err(|e| DownloadError::Io(format!("failed to open destination file: {}", e)))?;
+
+    let mut digest = DigestState::new(expected);
+    if resumed {
+        digest.feed_file(&part_path).map_err(|e| DownloadError::Io(format!("failed to read partial file: {}", e)))?;
+    }
+
+    let mut written: u64 = offset;
+    let mut last_len = 0;
+    loop {
+        // 前回のチャンク分だけ帯域制限で待ってから次を読む
+        let next_chunk = async {
+            ctx.manager.throttle.consume(last_len, ctx.manager.queue.bytes_per_sec()).await;
+            response.chunk().await
+        };
+        let chunk = tokio::select! {
+            biased;
+            reason = ctx.control.interrupted() => {
+                drop(file);
+                return Err(match reason {
+                    Interrupt::Paused => DownloadError::Paused(Some(final_path.to_path_buf())),
+                    Interrupt::Cancelled => {
+                        discard_part(final_path);
+                        DownloadError::Cancelled
+                    }
+                });
+            }
+            chunk = next_chunk => chunk?,
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        last_len = chunk.len();
+        file.write_all(&chunk).map_err(|e| DownloadError::Io(format!("write error: {}", e)))?;
+        digest.update(&chunk);
+        written += chunk.len() as u64;
+        ctx.events.progress(written, total_opt, offset);
+    }
+    file.sync_all().map_err(|e| DownloadError::Io(format!("write error: {}", e)))?;
+    drop(file);
+
+    // 壊れたファイルは再開せずに削除する
+    if let Err(e) = digest.verify(expected, written) {
+        discard_part(final_path);
+        return Err(e);
+    }
+
+    fs::rename(&part_path, final_path).map_err(|e| DownloadError::Io(format!("failed to finalize download: {}", e)))?;
+    let _ = fs::remove_file(&meta_path);
+    Ok(())
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+fn new_task_id(task_id: Option<String>) -> String {
+    task_id.filter(|s| !s.trim().is_empty()).unwrap_or_else(|| format!("download-{}", chrono::Utc::now().timestamp_micros()))
+}
+
+fn dest_dir_of(app: &AppHandle, dest_path: &str) -> Result<PathBuf, String> {
+    if dest_path.trim().is_empty() {
+        return Err("dest_path must not be empty".to_string());
+    }
+    Ok(crate::resolve_rel_to_app_config(app, dest_path))
+}
+
+async fn run_command(app: &AppHandle, manager: &DownloadManager, task: DownloadTask) -> Result<String, String> {
+    run_task(app, manager, &task).await.map(|p| p.to_string_lossy().to_string()).map_err(|e| e.to_string())
+}
+
+/// 実行中のダウンロードをキャンセル（.part を削除して download:cancelled を送る。該当タスクが無ければ false）
+#[tauri::command]
+pub fn cancel_download(tasks: tauri::State<'_, TaskRegistry>, task_id: String) -> bool {
+    tasks.cancel(&task_id)
+}
+
+/// 実行中のダウンロードを一時停止（.part は残る。ダウンロード以外のタスクは一時停止できないので false）
+#[tauri::command]
+pub fn pause_download(tasks: tauri::State<'_, TaskRegistry>, task_id: String) -> bool {
+    tasks.pause(&task_id)
+}
+
+/// 一時停止中のダウンロードを再開
+#[tauri::command]
+pub fn resume_download(tasks: tauri::State<'_, TaskRegistry>, task_id: String) -> bool {
+    tasks.resume(&task_id)
+}
+
+/// カタログの installer.source を受け取ってダウンロード
+#[tauri::command]
+pub async fn download_from_source(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    source: InstallerSource,
+    dest_path: String,
+    task_id: Option<String>,
+    session_window_label: Option<String>,
+) -> Result<String, String> {
+    let (primary, mirrors) = source.to_sources(session_window_label).map_err(|e| e.to_string())?;
+    let task = DownloadTask {
+        task_id: new_task_id(task_id),
+        source: primary,
+        mirrors,
+        dest_dir: dest_dir_of(&app, &dest_path)?,
+        file_name: None,
+        expected: source.expected.clone(),
+    };
+    run_command(&app, &manager, task).await
+}
+
+#[tauri::command]
+pub async fn download_file_to_path(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    url: String,
+    dest_path: String,
+    task_id: Option<String>,
+    expected: Option<ExpectedDigest>,
+) -> Result<String, String> {
+    let task = DownloadTask {
+        task_id: new_task_id(task_id),
+        source: Source::Direct { url },
+        mirrors: Vec::new(),
+        dest_dir: dest_dir_of(&app, &dest_path)?,
+        file_name: None,
+        expected,
+    };
+    run_command(&app, &manager, task).await
+}
+
+// ファイルダウンロード（BOOTH認証セッション対応版）
+#[tauri::command]
+pub async fn download_file_to_path_booth(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    url: String,
+    dest_path: String,
+    task_id: Option<String>,
+    session_window_label: Option<String>,
+    expected: Option<ExpectedDigest>,
+) -> Result<String, String> {
+    let task = DownloadTask {
+        task_id: new_task_id(task_id),
+        source: Source::Booth { url, session_label: booth_session_label(session_window_label) },
+        mirrors: Vec::new(),
+        dest_dir: dest_dir_of(&app, &dest_path)?,
+        file_name: None,
+        expected,
+    };
+    run_command(&app, &manager, task).await
+}
+
+// Google Drive のファイルをダウンロード
+// - dest_path がディレクトリ（または download.bin / fileId のプレースホルダ）ならレスポンスのファイル名で保存する
+#[tauri::command]
+pub async fn drive_download_to_file(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    file_id: String,
+    dest_path: String,
+    task_id: Option<String>,
+    expected: Option<ExpectedDigest>,
+) -> Result<String, String> {
+    let dest_abs = dest_dir_of(&app, &dest_path)?;
+    let looks_dir = dest_path.ends_with('/') || dest_path.ends_with('\\') || dest_abs.is_dir();
+    let is_placeholder = dest_abs.file_name().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("download.bin") || s == file_id).unwrap_or(true);
+    let (dest_dir, file_name) = if looks_dir {
+        (dest_abs, None)
+    } else {
+        let parent = dest_abs.parent().map(Path::to_path_buf).unwrap_or_else(|| dest_abs.clone());
+        let name = if is_placeholder { None } else { dest_abs.file_name().map(|s| s.to_string_lossy().to_string()) };
+        (parent, name)
+    };
+    let task = DownloadTask {
+        task_id: new_task_id(task_id),
+        source: Source::GoogleDrive { id: file_id },
+        mirrors: Vec::new(),
+        dest_dir,
+        file_name,
+        expected,
+    };
+    run_command(&app, &manager, task).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const FILE_ID: &str = "FILEID";
+    const CONTENT: &[u8] = b"PK\x03\x04 plugin archive body";
+    const ETAG: &str = "\"v1\"";
+
+    /// 受け取ったリクエスト（パスと小文字にしたヘッダー）
+    type Requests = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
+
+    /// 応答内容（ステータス行以降のヘッダーと本体）
+    type Reply = (String, Vec<u8>);
+
+    /// handler で応答するローカルサーバー
+    async fn serve(handler: impl Fn(&str, &str, &HashMap<String, String>) -> Reply + Send + Sync + 'static) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Requests = Arc::default();
+        let (server_base, log) = (base.clone(), requests.clone());
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !raw.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let text = String::from_utf8_lossy(&raw).into_owned();
+                let mut lines = text.lines();
+                let path = lines.next().unwrap_or_default().split(' ').nth(1).unwrap_or_default().to_string();
+                let headers: HashMap<String, String> = lines.filter_map(|l| l.split_once(':')).map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string())).collect();
+                let (head, body) = handler(&server_base, &path, &headers);
+                log.lock().unwrap().push((path, headers));
+                let response = format!("HTTP/1.1 {head}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.write_all(&body).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        (base, requests)
+    }
+
+    /// ETag が etag のファイル本体（If-Range が一致する Range 要求には 206、それ以外は 200 で全体を返す）
+    fn file_reply(headers: &HashMap<String, String>, etag: &str, body: &[u8]) -> Reply {
+        let range = headers.get("range").and_then(|r| r.strip_prefix("bytes=")?.strip_suffix('-')?.parse::<usize>().ok());
+        let if_range_ok = headers.get("if-range").is_none_or(|v| v == etag);
+        match range.filter(|_| if_range_ok) {
+            Some(start) => (
+                format!("206 Partial Content\r\nContent-Type: application/zip\r\nETag: {etag}\r\nContent-Range: bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                body[start..].to_vec(),
+            ),
+            None => (format!("200 OK\r\nContent-Type: application/zip\r\nETag: {etag}\r\nContent-Disposition: attachment; filename=\"plugin.zip\""), body.to_vec()),
+        }
+    }
+
+    /// Google Drive の代わりに応答するサーバー
+    /// - /uc: ウイルススキャンの確認ページ（form の送信先は confirm_path）
+    /// - /download: ファイル本体（Range に対応）
+    /// - それ以外: HTML
+    async fn drive_server(confirm_path: &'static str) -> (String, Requests) {
+        serve(move |base, path, headers| {
+            if path.starts_with("/uc") {
+                let html = format!(
+                    r#"<html><body><form id="download-form" action="{base}{confirm_path}" method="get"><input type="submit" id="uc-download-link" value="Download anyway"/><input type="hidden" name="id" value="{FILE_ID}"><input type="hidden" name="export" value="download"><input type="hidden" name="confirm" value="t"><input type="hidden" name="uuid" value="abc-123"></form></body></html>"#
+                );
+                ("200 OK\r\nContent-Type: text/html; charset=utf-8".to_string(), html.into_bytes())
+            } else if path.starts_with("/download") {
+                file_reply(headers, ETAG, CONTENT)
+            } else {
+                ("200 OK\r\nContent-Type: text/html".to_string(), b"<html>quota exceeded</html>".to_vec())
+            }
+        })
+        .await
+    }
+
+    /// /file で etag・body のファイルを返すサーバー
+    async fn file_server(etag: &'static str, body: &'static [u8]) -> (String, Requests) {
+        serve(move |_, _, headers| file_reply(headers, etag, body)).await
+    }
+
+    fn drive_resolved(base: &str) -> Resolved {
+        Resolved {
+            url: format!("{base}/uc?export=download&id={FILE_ID}"),
+            cookie: None,
+            name_hint: None,
+            resume_key: format!("drive:{FILE_ID}"),
+        }
+    }
+
+    fn direct_resolved(base: &str) -> Resolved {
+        let url = format!("{base}/file");
+        Resolved {
+            url: url.clone(),
+            cookie: None,
+            name_hint: Some("file".to_string()),
+            resume_key: url,
+        }
+    }
+
+    fn task_into(dir: &Path, file_name: Option<&str>) -> DownloadTask {
+        DownloadTask {
+            task_id: "test".to_string(),
+            source: Source::Direct { url: String::new() },
+            mirrors: Vec::new(),
+            dest_dir: dir.to_path_buf(),
+            file_name: file_name.map(str::to_string),
+            expected: None,
+        }
+    }
+
+    /// 前回の途中ファイル（.part と .part.json）を置く
+    fn write_part(final_path: &Path, data: &[u8], meta: PartMeta) {
+        let (part, meta_path) = part_paths(final_path);
+        fs::write(part, data).unwrap();
+        fs::write(meta_path, serde_json::to_string(&meta).unwrap()).unwrap();
+    }
+
+    fn part_meta(url: &str, etag: &str, request_url: Option<String>) -> PartMeta {
+        PartMeta {
+            url: url.to_string(),
+            etag: Some(etag.to_string()),
+            last_modified: None,
+            request_url,
+        }
+    }
+
+    /// イベントを送らない TaskContext で stream_to_part_file を実行する
+    async fn stream(resolved: &Resolved, final_path: &Path, response: reqwest::Response, offset: u64, expected: Option<&ExpectedDigest>) -> Result<(), DownloadError> {
+        let manager = DownloadManager::new(DownloadLimits { max_parallel: 1, per_host: 1, bytes_per_sec: 0 }).unwrap();
+        let events = TaskEvents { app: None, task_id: "test" };
+        let control = TaskControl::default();
+        let ctx = TaskContext { manager: &manager, events: &events, control: &control };
+        stream_to_part_file(&ctx, resolved, final_path, response, offset, expected).await
+    }
+
+    #[tokio::test]
+    async fn drive_interstitial_is_followed_to_the_file() {
+        let (base, requests) = drive_server("/download").await;
+        let client = reqwest::Client::new();
+        let source = Source::GoogleDrive { id: FILE_ID.to_string() };
+        let mut resolved = drive_resolved(&base);
+
+        let response = open_response(&client, &source, &mut resolved).await.unwrap();
+        assert_eq!(choose_filename(&response, None).as_deref(), Some("plugin.zip"));
+        assert_eq!(response.bytes().await.unwrap().as_ref(), CONTENT);
+        assert!(resolved.url.starts_with(&format!("{base}/download?")), "{}", resolved.url);
+        assert!(resolved.url.contains("confirm=t") && resolved.url.contains("uuid=abc-123") && resolved.url.contains(&format!("id={FILE_ID}")));
+        let paths: Vec<String> = requests.lock().unwrap().iter().map(|(p, _)| p.clone()).collect();
+        assert!(paths[0].starts_with("/uc") && paths[1].starts_with("/download"), "{paths:?}");
+    }
+
+    #[tokio::test]
+    async fn drive_html_after_confirmation_is_rejected() {
+        let (base, _) = drive_server("/still-html").await;
+        let client = reqwest::Client::new();
+        let source = Source::GoogleDrive { id: FILE_ID.to_string() };
+        let err = open_response(&client, &source, &mut drive_resolved(&base)).await.unwrap_err();
+        assert_eq!(err.code(), "DRIVE_CONFIRM_FAILED");
+    }
+
+    #[tokio::test]
+    async fn drive_resume_requests_range_from_confirmed_url() {
+        let (base, requests) = drive_server("/download").await;
+        let client = reqwest::Client::new();
+        let source = Source::GoogleDrive { id: FILE_ID.to_string() };
+        let mut resolved = drive_resolved(&base);
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("fixed-name.zip");
+        write_part(&final_path, &CONTENT[..4], part_meta(&resolved.resume_key, ETAG, None));
+
+        open_response(&client, &source, &mut resolved).await.unwrap();
+        let (response, offset) = request_resume(&client, &source, &mut resolved, &final_path, true).await.unwrap().expect("resumable");
+        assert_eq!(offset, 4);
+        assert_eq!(response.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), &CONTENT[4..]);
+
+        let requests = requests.lock().unwrap();
+        let (path, headers) = requests.last().unwrap();
+        assert!(path.starts_with("/download"), "{path}");
+        assert_eq!(headers.get("range").map(String::as_str), Some("bytes=4-"));
+        assert_eq!(headers.get("if-range").map(String::as_str), Some(ETAG));
+        assert!(requests.iter().all(|(p, h)| !p.starts_with("/uc") || !h.contains_key("range")));
+    }
+
+    // 保存ファイル名が決まっていれば、確認ページも通常の GET も送らずに前回の URL へ Range を送る
+    #[tokio::test]
+    async fn fixed_name_resume_sends_only_the_range_request() {
+        let (base, requests) = drive_server("/download").await;
+        let client = reqwest::Client::new();
+        let source = Source::GoogleDrive { id: FILE_ID.to_string() };
+        let mut resolved = drive_resolved(&base);
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("fixed-name.zip");
+        write_part(&final_path, &CONTENT[..4], part_meta(&resolved.resume_key, ETAG, Some(format!("{base}/download?id={FILE_ID}&confirm=t"))));
+
+        let (path, response, offset) = open_download(&client, &source, &mut resolved, &task_into(dir.path(), Some("fixed-name.zip"))).await.unwrap();
+        assert_eq!((path.as_path(), offset), (final_path.as_path(), 4));
+        stream(&resolved, &final_path, response, offset, None).await.unwrap();
+        assert_eq!(fs::read(&final_path).unwrap(), CONTENT);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1, "{:?}", requests.iter().map(|(p, _)| p).collect::<Vec<_>>());
+        assert!(requests[0].0.starts_with("/download"));
+        assert_eq!(requests[0].1.get("range").map(String::as_str), Some("bytes=4-"));
+    }
+
+    // 前回の確認 URL が期限切れ（HTML が返る）なら .part を捨てて確認ページからやり直す
+    #[tokio::test]
+    async fn expired_confirmed_url_falls_back_to_the_confirmation_page() {
+        let (base, requests) = drive_server("/download").await;
+        let client = reqwest::Client::new();
+        let source = Source::GoogleDrive { id: FILE_ID.to_string() };
+        let mut resolved = drive_resolved(&base);
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("fixed-name.zip");
+        write_part(&final_path, &CONTENT[..4], part_meta(&resolved.resume_key, ETAG, Some(format!("{base}/expired"))));
+
+        let (_, response, offset) = open_download(&client, &source, &mut resolved, &task_into(dir.path(), Some("fixed-name.zip"))).await.unwrap();
+        assert_eq!(offset, 0);
+        assert!(!part_paths(&final_path).0.exists());
+        stream(&resolved, &final_path, response, offset, None).await.unwrap();
+        assert_eq!(fs::read(&final_path).unwrap(), CONTENT);
+        let paths: Vec<String> = requests.lock().unwrap().iter().map(|(p, _)| p.clone()).collect();
+        assert!(paths[0] == "/expired" && paths[1].starts_with("/uc") && paths[2].starts_with("/download"), "{paths:?}");
+    }
+
+    // 206 の続きを .part の後ろに追記し、完了後に .part と .part.json を消す
+    #[tokio::test]
+    async fn resume_appends_to_the_part_file_at_the_offset() {
+        let (base, requests) = file_server(ETAG, CONTENT).await;
+        let client = reqwest::Client::new();
+        let source = Source::Direct { url: String::new() };
+        let mut resolved = direct_resolved(&base);
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("plugin.zip");
+        write_part(&final_path, &CONTENT[..10], part_meta(&resolved.resume_key, ETAG, None));
+
+        let (path, response, offset) = open_download(&client, &source, &mut resolved, &task_into(dir.path(), None)).await.unwrap();
+        assert_eq!((path.as_path(), offset), (final_path.as_path(), 10));
+        assert_eq!(response.status(), reqwest::StatusCode::PARTIAL_CONTENT);
+        stream(&resolved, &final_path, response, offset, None).await.unwrap();
+
+        assert_eq!(fs::read(&final_path).unwrap(), CONTENT);
+        let (part, meta) = part_paths(&final_path);
+        assert!(!part.exists() && !meta.exists());
+        assert_eq!(requests.lock().unwrap().last().unwrap().1.get("range").map(String::as_str), Some("bytes=10-"));
+    }
+
+    // If-Range が一致せず 200 で全体が返った場合は、.part を先頭から書き直す
+    #[tokio::test]
+    async fn full_response_to_a_range_request_restarts_from_zero() {
+        const NEW_CONTENT: &[u8] = b"PK\x03\x04 rebuilt archive with different bytes";
+        let (base, _) = file_server("\"v2\"", NEW_CONTENT).await;
+        let client = reqwest::Client::new();
+        let source = Source::Direct { url: String::new() };
+        let mut resolved = direct_resolved(&base);
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("plugin.zip");
+        write_part(&final_path, b"stale prefix", part_meta(&resolved.resume_key, ETAG, None));
+
+        let (_, response, offset) = open_download(&client, &source, &mut resolved, &task_into(dir.path(), Some("plugin.zip"))).await.unwrap();
+        assert_eq!(offset, 12);
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        stream(&resolved, &final_path, response, offset, None).await.unwrap();
+        assert_eq!(fs::read(&final_path).unwrap(), NEW_CONTENT);
+    }
+
+    // 再開キーが違う・弱い ETag しか無い .part.json では再開しない
+    #[tokio::test]
+    async fn stale_part_meta_is_not_resumed() {
+        let (base, requests) = file_server(ETAG, CONTENT).await;
+        let client = reqwest::Client::new();
+        let source = Source::Direct { url: String::new() };
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("plugin.zip");
+
+        write_part(&final_path, b"other", part_meta("https://example.com/other.zip", ETAG, None));
+        assert!(resume_state(&final_path, &direct_resolved(&base).resume_key).is_none());
+        write_part(&final_path, b"weak", part_meta(&direct_resolved(&base).resume_key, "W/\"v1\"", None));
+        assert!(resume_state(&final_path, &direct_resolved(&base).resume_key).is_none());
+
+        let mut resolved = direct_resolved(&base);
+        let (_, response, offset) = open_download(&client, &source, &mut resolved, &task_into(dir.path(), Some("plugin.zip"))).await.unwrap();
+        assert_eq!(offset, 0);
+        stream(&resolved, &final_path, response, offset, None).await.unwrap();
+        assert_eq!(fs::read(&final_path).unwrap(), CONTENT);
+        assert!(requests.lock().unwrap().iter().all(|(_, h)| !h.contains_key("range")));
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        use sha2::Digest;
+        format!("{:x}", sha2::Sha256::digest(data))
+    }
+
+    fn xxh3_hex(data: &[u8]) -> String {
+        format!("{:032x}", xxhash_rust::xxh3::xxh3_128(data))
+    }
+
+    /// /file から全体を取得して保存する
+    async fn download_whole(expected: &ExpectedDigest) -> (tempfile::TempDir, PathBuf, Result<(), DownloadError>) {
+        let (base, _) = file_server(ETAG, CONTENT).await;
+        let resolved = direct_resolved(&base);
+        let response = resolved.request(&reqwest::Client::new()).send().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let final_path = dir.path().join("plugin.zip");
+        let result = stream(&resolved, &final_path, response, 0, Some(expected)).await;
+        (dir, final_path, result)
+    }
+
+    fn assert_discarded(final_path: &Path) {
+        let (part, meta) = part_paths(final_path);
+        assert!(!final_path.exists() && !part.exists() && !meta.exists());
+    }
+
+    #[tokio::test]
+    async fn matching_digests_pass() {
+        // 大文字の期待値や前後の空白も受け付ける
+        let expected = ExpectedDigest {
+            xxh3_128: Some(xxh3_hex(CONTENT).to_uppercase()),
+            sha256: Some(format!(" {} ", sha256_hex(CONTENT))),
+            size: Some(CONTENT.len() as u64),
+        };
+        let (_dir, final_path, result) = download_whole(&expected).await;
+        result.unwrap();
+        assert_eq!(fs::read(&final_path).unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn hash_mismatch_discards_the_part_file() {
+        for expected in [
+            ExpectedDigest { sha256: Some(sha256_hex(b"something else")), ..Default::default() },
+            ExpectedDigest { xxh3_128: Some(xxh3_hex(b"something else")), ..Default::default() },
+        ] {
+            let (_dir, final_path, result) = download_whole(&expected).await;
+            let err = result.unwrap_err();
+            assert_eq!(err.code(), "HASH_MISMATCH");
+            assert_discarded(&final_path);
+        }
+    }
+
+    #[tokio::test]
+    async fn size_mismatch_discards_the_part_file() {
+        let expected = ExpectedDigest {
+            sha256: Some(sha256_hex(CONTENT)),
+            size: Some(CONTENT.len() as u64 + 1),
+            ..Default::default()
+        };
+        let (_dir, final_path, result) = download_whole(&expected).await;
+        let err = result.unwrap_err();
+        assert!(matches!(err, DownloadError::SizeMismatch { expected, actual } if expected == actual + 1), "{err}");
+        assert_eq!(err.code(), "SIZE_MISMATCH");
+        assert_discarded(&final_path);
+    }
+
+    // 再開時は既存の .part を先にハッシュに流し込むので、壊れた先頭部分も検出できる
+    #[tokio::test]
+    async fn resumed_download_hashes_the_existing_prefix() {
+        let (base, _) = file_server(ETAG, CONTENT).await;
+        let client = reqwest::Client::new();
+        let source = Source::Direct { url: String::new() };
+        let expected = ExpectedDigest {
+            xxh3_128: Some(xxh3_hex(CONTENT)),
+            sha256: Some(sha256_hex(CONTENT)),
+            size: Some(CONTENT.len() as u64),
+        };
+
+        for (prefix, ok) in [(&CONTENT[..10], true), (&b"XXXXXXXXXX"[..], false)] {
+            let dir = tempfile::tempdir().unwrap();
+            let final_path = dir.path().join("plugin.zip");
+            let mut resolved = direct_resolved(&base);
+            write_part(&final_path, prefix, part_meta(&resolved.resume_key, ETAG, None));
+            let (_, response, offset) = open_download(&client, &source, &mut resolved, &task_into(dir.path(), Some("plugin.zip"))).await.unwrap();
+            assert_eq!(offset, 10);
+            let result = stream(&resolved, &final_path, response, offset, Some(&expected)).await;
+            if ok {
+                result.unwrap();
+                assert_eq!(fs::read(&final_path).unwrap(), CONTENT);
+            } else {
+                assert_eq!(result.unwrap_err().code(), "HASH_MISMATCH");
+                assert_discarded(&final_path);
+            }
+        }
+    }
+
+    const NO_EVENTS: TaskEvents<'static> = TaskEvents { app: None, task_id: "test" };
+
+    fn limits(max_parallel: usize, per_host: usize) -> DownloadLimits {
+        DownloadLimits { max_parallel, per_host, bytes_per_sec: 0 }
+    }
+
+    fn active_count(queue: &DownloadQueue) -> usize {
+        queue.state.lock().unwrap().1.active
+    }
+
+    /// 枠が空くまで待つかを 100ms で判定する（取れた場合はその枠を返す）
+    async fn try_acquire<'a>(queue: &'a DownloadQueue, host: &str) -> Option<QueueSlot<'a>> {
+        tokio::time::timeout(Duration::from_millis(100), queue.acquire(host, &NO_EVENTS)).await.ok()
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_beyond_the_parallel_limit() {
+        let queue = DownloadQueue::new(limits(2, 5));
+        let a = queue.acquire("a.example", &NO_EVENTS).await;
+        let _b = queue.acquire("b.example", &NO_EVENTS).await;
+        assert!(try_acquire(&queue, "c.example").await.is_none());
+        // 待機をやめた分は待ち件数から外れる
+        assert_eq!(queue.state.lock().unwrap().1.queued, 0);
+
+        let waiting = queue.acquire("c.example", &NO_EVENTS);
+        tokio::pin!(waiting);
+        assert!(tokio::time::timeout(Duration::from_millis(50), waiting.as_mut()).await.is_err());
+        drop(a);
+        let _c = tokio::time::timeout(Duration::from_secs(1), waiting).await.expect("released slot is handed over");
+        assert_eq!(active_count(&queue), 2);
+    }
+
+    #[tokio::test]
+    async fn acquire_limits_each_host() {
+        let queue = DownloadQueue::new(limits(5, 1));
+        let first = queue.acquire("files.example", &NO_EVENTS).await;
+        assert!(try_acquire(&queue, "files.example").await.is_none());
+        assert!(try_acquire(&queue, "other.example").await.is_some());
+        drop(first);
+        assert!(try_acquire(&queue, "files.example").await.is_some());
+        assert!(queue.state.lock().unwrap().1.per_host.is_empty());
+    }
+
+    #[tokio::test]
+    async fn raising_the_limit_wakes_waiting_tasks() {
+        let queue = DownloadQueue::new(limits(1, 1));
+        let _a = queue.acquire("a.example", &NO_EVENTS).await;
+        let waiting = queue.acquire("b.example", &NO_EVENTS);
+        tokio::pin!(waiting);
+        assert!(tokio::time::timeout(Duration::from_millis(50), waiting.as_mut()).await.is_err());
+        queue.set_limits(limits(2, 1));
+        assert!(tokio::time::timeout(Duration::from_secs(1), waiting).await.is_ok());
+    }
+
+    // 一時停止したダウンロードは .part を残して枠を返し、待っていた別のダウンロードが始まる
+    #[tokio::test]
+    async fn pausing_a_download_frees_its_slot() {
+        const BODY: &[u8] = &[7u8; 64 * 1024];
+        let (base, _) = file_server(ETAG, BODY).await;
+        // 1 byte/s に絞って、最初のチャンクの後で待たせる
+        let manager = DownloadManager::new(DownloadLimits { max_parallel: 1, per_host: 1, bytes_per_sec: 1 }).unwrap();
+        let registry = TaskRegistry::default();
+        let control = registry.register_pausable("test");
+        let ctx = TaskContext { manager: &manager, events: &NO_EVENTS, control: &control };
+        let dir = tempfile::tempdir().unwrap();
+        let task = task_into(dir.path(), Some("slow.bin"));
+        let source = Source::Direct { url: String::new() };
+        let mut resolved = direct_resolved(&base);
+
+        let paused = fetch(&ctx, &source, &mut resolved, &task);
+        let other = async {
+            while active_count(&manager.queue) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(try_acquire(&manager.queue, "other.example").await.is_none());
+            assert!(registry.pause("test"));
+            tokio::time::timeout(Duration::from_secs(2), manager.queue.acquire("other.example", &NO_EVENTS)).await.is_ok()
+        };
+        let (result, acquired) = tokio::join!(paused, other);
+        assert!(acquired, "slot was not released by the paused download");
+        assert!(matches!(result, Err(DownloadError::Paused(Some(ref path))) if path == &dir.path().join("slow.bin")), "{result:?}");
+        assert!(part_paths(&dir.path().join("slow.bin")).0.exists());
+    }
+
+    #[tokio::test]
+    async fn throttle_spreads_bytes_over_time() {
+        let throttle = Throttle::default();
+        let start = tokio::time::Instant::now();
+        throttle.consume(1000, 0).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+        // 1000 bytes/s で 100 bytes ずつ 3 回なら、少なくとも 300ms 待つ
+        for _ in 0..3 {
+            throttle.consume(100, 1000).await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(290), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn retry_delay_follows_retry_after_up_to_limit() {
+        assert_eq!(retry_delay(1, Some(Duration::from_secs(7))), Duration::from_secs(7));
+        assert_eq!(retry_delay(1, Some(Duration::from_secs(3600))), RETRY_AFTER_MAX);
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially_within_half_to_full() {
+        for (retry, exp) in [(1, 1), (2, 2), (3, 4), (4, 8), (6, 30), (40, 30)] {
+            let exp = Duration::from_secs(exp);
+            for _ in 0..20 {
+                let delay = retry_delay(retry, None);
+                assert!(delay >= exp / 2 && delay <= exp, "retry {retry}: {delay:?} not in {:?}..={exp:?}", exp / 2);
+            }
+        }
+    }
+
+    #[test]
+    fn jitter_is_in_unit_range_and_depends_on_retry() {
+        for seed in [0, 1, 12345, u64::MAX] {
+            for retry in 0..8 {
+                let j = jitter(seed, retry);
+                assert!((0.0..1.0).contains(&j));
+            }
+        }
+        assert_ne!(jitter(42, 1), jitter(42, 2));
+    }
+
+    fn disposition(value: &str) -> Option<String> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::CONTENT_DISPOSITION, reqwest::header::HeaderValue::from_bytes(value.as_bytes()).unwrap());
+        filename_from_headers(&headers)
+    }
+
+    #[test]
+    fn content_disposition_filename_variants() {
+        assert_eq!(disposition("attachment; filename=plugin.zip").as_deref(), Some("plugin.zip"));
+        assert_eq!(disposition(r#"attachment; filename="a; b \"c\".zip""#).as_deref(), Some("a; b _c_.zip"));
+        // filename* を優先し、charset で復号する
+        assert_eq!(disposition("attachment; filename=\"fallback.zip\"; filename*=UTF-8''%E3%83%97%E3%83%A9%E3%82%B0%E3%82%A4%E3%83%B3.zip").as_deref(), Some("プラグイン.zip"));
+        assert_eq!(disposition("attachment; filename*=iso-8859-1'en'caf%E9.zip").as_deref(), Some("café.zip"));
+        assert_eq!(disposition("attachment; FILENAME=\"upper.zip\"").as_deref(), Some("upper.zip"));
+    }
+
+    #[test]
+    fn content_disposition_strips_paths_and_rejects_empty() {
+        assert_eq!(disposition(r#"attachment; filename="..\\..\\evil.dll""#).as_deref(), Some("evil.dll"));
+        assert_eq!(disposition(r#"attachment; filename="dir/sub/name.zip""#).as_deref(), Some("name.zip"));
+        assert_eq!(disposition(r#"attachment; filename="""#), None);
+        assert_eq!(disposition("attachment"), None);
+        assert_eq!(disposition("inline; filename*=unknown-charset''x.zip"), None);
+    }
+
+    #[test]
+    fn unusable_names_fall_back() {
+        for name in [
+            ".",
+            "..",
+            "CON",
+            "nul.zip",
+            "Com1.txt",
+            "LPT9",
+            "plugin.zip.",
+            "plugin.zip. ",
+            "   ",
+        ] {
+            assert_eq!(disposition(&format!("attachment; filename=\"{name}\"")), None, "{name:?}");
+            assert_eq!(sanitize_filename(name), "download.bin", "{name:?}");
+        }
+        // デバイス名を含むだけの名前や、先頭・途中のドットは使える
+        assert_eq!(disposition("attachment; filename=\"console.zip\"").as_deref(), Some("console.zip"));
+        assert_eq!(disposition("attachment; filename=\".hidden\"").as_deref(), Some(".hidden"));
+        assert_eq!(sanitize_filename(" con-plugin.v2.zip "), "con-plugin.v2.zip");
+    }
+
+    // Content-Disposition の名前が使えなければ、リダイレクト後の URL、元の URL の名前の順に使う
+    #[tokio::test]
+    async fn unusable_disposition_name_falls_back_to_url_names() {
+        let (base, _) = serve(|_, path, _| {
+            let name = if path.starts_with("/release") { ".." } else { "NUL" };
+            (format!("200 OK\r\nContent-Type: application/zip\r\nContent-Disposition: attachment; filename=\"{name}\""), b"body".to_vec())
+        })
+        .await;
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{base}/release/plugin-1.0.zip")).send().await.unwrap();
+        assert_eq!(choose_filename(&response, Some("hint.zip")).as_deref(), Some("plugin-1.0.zip"));
+        let response = client.get(format!("{base}/%2E%2E")).send().await.unwrap();
+        assert_eq!(choose_filename(&response, Some("hint.zip")).as_deref(), Some("hint.zip"));
+        assert_eq!(filename_from_url(&Url::parse("https://example.com/files/%2E%2E").unwrap()), "download.bin");
+    }
+
+    #[test]
+    fn url_segment_is_decoded_and_sanitized() {
+        let url = Url::parse("https://example.com/releases/download/v1/My%20Plugin%3A1.zip?x=1").unwrap();
+        assert_eq!(filename_from_url(&url), "My Plugin_1.zip");
+        assert_eq!(filename_from_url(&Url::parse("https://example.com/").unwrap()), "download.bin");
+    }
+}
@@ -0,0 +1,1559 @@
+// -----------------------
+// アーカイブ展開（ZIP / 7z / tar.gz / tar.xz / 7z SFX）
+// -----------------------
+//
+// - extract_archive は拡張子やステップ名ではなく先頭のマジックバイトで形式を判定する
+//   7z SFX（exe）だけは埋め込まれた 7z シグネチャを探す必要があるため extract_7z_sfx で扱う
+// - 展開は spawn_blocking で行い、taskId が指定されていれば extract:progress を送る
+//   キャンセルはダウンロードと同じ TaskRegistry で受け付ける
+// - 各ファイルは placement で一時ファイルに書いてから置き換え、失敗・キャンセル時は書いたファイルを消して既存のファイルを元に戻す
+// - 書き込む前に全エントリを検査し、展開先の外を指すもの（..、絶対パス、ドライブ指定、外部を指すシンボリックリンク）が
+//   1 つでもあれば何も書き込まずに失敗する
+// - エントリ数と展開後の合計サイズに上限を設ける（zip bomb 対策）
+//   宣言サイズは偽装できるので、実際に書き込んだバイト数でも確認する
+// - 暗号化された ZIP（ZipCrypto / AES）と 7z（AES）はパスワードを受け取って展開する
+//   パスワードが無い・誤っている場合は専用のコードで失敗し、フロントで入力を求められるようにする
+// - include / exclude の glob と先頭 N 階層の除去で、必要なファイルだけを直接展開先に取り出せる
+//   （検査と上限はアーカイブ全体に対して行い、絞り込みは書き込む対象だけに効く）
+// - nestedDepth を指定すると、中に入っている zip / 7z / tar などを指定の深さまで続けて展開する
+
+use encoding_rs::{Encoding, BIG5, GBK, SHIFT_JIS};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use memchr::memmem::Finder;
+use memmap2::MmapOptions;
+use serde::{Deserialize, Serialize};
+use sevenz_rust2::{ArchiveReader, Password};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tar::EntryType;
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+use zip::read::{ZipArchive, ZipFile};
+
+use crate::journal::Journal;
+use crate::placement::Placement;
+use crate::tasks::{TaskControl, TaskRegistry};
+
+// 7z ファイルのシグネチャ
+const SEVENZ_SIGNATURE: &[u8] = b"\x37\x7A\xBC\xAF\x27\x1C";
+// extract:progress の送信間隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 展開時のエラー（Display の先頭はフロントで判定するためのコード）
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    #[error("EXTRACT_OPEN_FAILED: {0}")]
+    Open(String),
+    #[error("EXTRACT_UNSUPPORTED_FORMAT: {0}")]
+    UnsupportedFormat(String),
+    #[error("EXTRACT_INVALID_OPTION: {0}")]
+    InvalidOption(String),
+    #[error("EXTRACT_UNSAFE_ENTRY: archive contains entries outside the destination: {}", describe_entries(.0))]
+    UnsafeEntries(Vec<UnsafeEntry>),
+    #[error("EXTRACT_TOO_MANY_ENTRIES: {count} entries (limit {limit})")]
+    TooManyEntries { count: u64, limit: u64 },
+    #[error("EXTRACT_TOO_LARGE: uncompressed size exceeds {limit} bytes at {entry}")]
+    TooLarge { limit: u64, entry: String },
+    #[error("EXTRACT_ARCHIVE_ERROR: {0}")]
+    Archive(String),
+    #[error("EXTRACT_IO_ERROR: {0}")]
+    Io(String),
+    #[error("EXTRACT_PASSWORD_REQUIRED: archive is encrypted; a password is required")]
+    PasswordRequired,
+    #[error("EXTRACT_PASSWORD_INCORRECT: the password is incorrect")]
+    PasswordIncorrect,
+    #[error("EXTRACT_CANCELLED: extraction cancelled")]
+    Cancelled,
+}
+
+impl ExtractError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExtractError::Open(_) => "EXTRACT_OPEN_FAILED",
+            ExtractError::UnsupportedFormat(_) => "EXTRACT_UNSUPPORTED_FORMAT",
+            ExtractError::InvalidOption(_) => "EXTRACT_INVALID_OPTION",
+            ExtractError::UnsafeEntries(_) => "EXTRACT_UNSAFE_ENTRY",
+            ExtractError::TooManyEntries { .. } => "EXTRACT_TOO_MANY_ENTRIES",
+            ExtractError::TooLarge { .. } => "EXTRACT_TOO_LARGE",
+            ExtractError::Archive(_) => "EXTRACT_ARCHIVE_ERROR",
+            ExtractError::Io(_) => "EXTRACT_IO_ERROR",
+            ExtractError::PasswordRequired => "EXTRACT_PASSWORD_REQUIRED",
+            ExtractError::PasswordIncorrect => "EXTRACT_PASSWORD_INCORRECT",
+            ExtractError::Cancelled => "EXTRACT_CANCELLED",
+        }
+    }
+
+    /// 問題のあったエントリ（フロントで一覧表示する）
+    fn entries(&self) -> Vec<UnsafeEntry> {
+        match self {
+            ExtractError::UnsafeEntries(entries) => entries.clone(),
+            ExtractError::TooLarge { entry, .. } => vec![UnsafeEntry { name: entry.clone(), reason: "size-limit" }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Serialize for ExtractError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("ExtractError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("entries", &self.entries())?;
+        s.end()
+    }
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(e: io::Error) -> Self {
+        ExtractError::Io(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for ExtractError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => ExtractError::Io(e.to_string()),
+            zip::result::ZipError::InvalidPassword => ExtractError::PasswordIncorrect,
+            zip::result::ZipError::UnsupportedArchive(zip::result::ZipError::PASSWORD_REQUIRED) => ExtractError::PasswordRequired,
+            e => ExtractError::Archive(e.to_string()),
+        }
+    }
+}
+
+/// 展開を拒否したエントリ
+#[derive(Serialize, Clone, Debug)]
+pub struct UnsafeEntry {
+    pub name: String,
+    // parent-dir / absolute-path / drive-letter / invalid-name / symlink-outside / size-limit / duplicate-path
+    pub reason: &'static str,
+}
+
+fn describe_entries(entries: &[UnsafeEntry]) -> String {
+    const SHOWN: usize = 5;
+    let mut list = entries.iter().take(SHOWN).map(|e| format!("{} ({})", e.name, e.reason)).collect::<Vec<_>>().join(", ");
+    if entries.len() > SHOWN {
+        list.push_str(&format!(", ... {} more", entries.len() - SHOWN));
+    }
+    list
+}
+
+/// エントリ数・合計サイズの上限
+#[derive(Clone, Copy, Debug)]
+pub struct ExtractLimits {
+    pub max_entries: u64,
+    pub max_total_size: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self { max_entries: 65_536, max_total_size: 4 * 1024 * 1024 * 1024 }
+    }
+}
+
+/// 展開オプション（インストーラの extract ステップから指定される）
+#[derive(Clone, Debug, Default)]
+pub struct ExtractOptions {
+    pub limits: ExtractLimits,
+    pub encoding: NameEncoding,
+    // 暗号化されたアーカイブのパスワード（暗号化されていなければ無視する）
+    pub password: Option<String>,
+    pub filter: EntryFilter,
+    // 中に入っているアーカイブを続けて展開する深さ（0 なら展開しない）
+    pub nested_depth: u32,
+}
+
+impl ExtractOptions {
+    /// コマンド引数から組み立てる（空のパスワードは未指定として扱う）
+    pub fn from_args(encoding: Option<&str>, password: Option<String>) -> Result<Self, ExtractError> {
+        Ok(Self {
+            encoding: NameEncoding::from_label(encoding)?,
+            password: password.filter(|p| !p.is_empty()),
+            ..Default::default()
+        })
+    }
+}
+
+/// extract_archive に渡すオプション（フロントの extract ステップの指定をそのまま受け取る形）
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractArgs {
+    pub encoding: Option<String>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub strip_components: usize,
+    #[serde(default)]
+    pub nested_depth: u32,
+}
+
+impl ExtractArgs {
+    pub fn into_options(self) -> Result<ExtractOptions, ExtractError> {
+        let mut options = ExtractOptions::from_args(self.encoding.as_deref(), self.password)?;
+        options.filter = EntryFilter::new(&self.include, &self.exclude, self.strip_components)?;
+        options.nested_depth = self.nested_depth.min(MAX_NESTED_DEPTH);
+        Ok(options)
+    }
+}
+
+// -----------------------
+// 展開するエントリの絞り込み
+// -----------------------
+//
+// - パターンはアーカイブ内のパス（区切りは /）に対して照合する（例: */Plugin/*.aui2）
+// - * と ? は / をまたがず、** は任意の階層に一致する。大文字小文字は区別しない（Windows のファイル名に合わせる）
+// - 先頭 N 階層の除去は照合の後に行う。除去して何も残らないエントリ（上位のディレクトリ）は展開しない
+// - 除去によって別々のエントリが同じパスになる場合（ディレクトリ同士を除く）は、上書きせずに duplicate-path で拒否する
+
+/// include / exclude の glob と先頭階層の除去
+#[derive(Clone, Debug, Default)]
+pub struct EntryFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    strip_components: usize,
+}
+
+impl EntryFilter {
+    pub fn new(include: &[String], exclude: &[String], strip_components: usize) -> Result<Self, ExtractError> {
+        Ok(Self {
+            include: build_globset(include)?,
+            exclude: build_globset(exclude)?,
+            strip_components,
+        })
+    }
+
+    /// 検査済みの相対パスに絞り込みを適用する（None は展開しない）
+    fn apply(&self, entries: &[EntryInfo], paths: Vec<Option<PathBuf>>) -> Result<Vec<Option<PathBuf>>, ExtractError> {
+        // 除去後のパス（大文字小文字を区別しない）ごとに、元の相対パスとエントリの番号をまとめる
+        let mut targets: HashMap<String, Vec<(PathBuf, usize)>> = HashMap::new();
+        let selected: Vec<Option<PathBuf>> = paths
+            .into_iter()
+            .enumerate()
+            .map(|(i, rel)| {
+                let rel = rel?;
+                let stripped = self.select(rel.clone())?;
+                targets.entry(entry_key(&stripped).to_lowercase()).or_default().push((rel, i));
+                Some(stripped)
+            })
+            .collect();
+        if self.strip_components == 0 {
+            return Ok(selected);
+        }
+        // 元から同じパスのエントリ（アーカイブ内の重複）は除去とは関係ないので対象にしない
+        let mut duplicates: Vec<usize> = targets
+            .into_values()
+            .filter(|group| group.iter().any(|(rel, _)| *rel != group[0].0) && group.iter().any(|&(_, i)| !matches!(entries[i].kind, EntryKind::Dir)))
+            .flat_map(|group| group.into_iter().map(|(_, i)| i))
+            .collect();
+        if duplicates.is_empty() {
+            return Ok(selected);
+        }
+        duplicates.sort_unstable();
+        Err(ExtractError::UnsafeEntries(
+            duplicates
+                .into_iter()
+                .map(|i| UnsafeEntry {
+                    name: format!("{} -> {}", entries[i].name, selected[i].as_deref().map(entry_key).unwrap_or_default()),
+                    reason: "duplicate-path",
+                })
+                .collect(),
+        ))
+    }
+
+    fn select(&self, rel: PathBuf) -> Option<PathBuf> {
+        let key = entry_key(&rel);
+        if self.include.as_ref().is_some_and(|set| !set.is_match(&key)) || self.exclude.as_ref().is_some_and(|set| set.is_match(&key)) {
+            return None;
+        }
+        let stripped: PathBuf = rel.iter().skip(self.strip_components).collect();
+        if stripped.as_os_str().is_empty() {
+            None
+        } else {
+            Some(stripped)
+        }
+    }
+}
+
+/// 照合と表示に使うパス（区切りは /）
+fn entry_key(rel: &Path) -> String {
+    rel.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// パターンの一覧から GlobSet を作る（空なら None）
+fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>, ExtractError> {
+    let patterns: Vec<String> = patterns.iter().map(|p| p.trim().replace('\\', "/")).filter(|p| !p.is_empty()).collect();
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in &patterns {
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(true)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| ExtractError::InvalidOption(format!("invalid glob pattern {}: {}", pattern, e)))?;
+        builder.add(glob);
+    }
+    builder.build().map(Some).map_err(|e| ExtractError::InvalidOption(format!("invalid glob pattern: {e}")))
+}
+
+// -----------------------
+// ZIP / tar エントリ名の文字コード
+// -----------------------
+//
+// - UTF-8 フラグ（または Unicode Path 拡張フィールド）付きのエントリや、UTF-8 として正しいバイト列はそのまま使う
+// - それ以外は日本語版 Windows で作られたものが多いので CP932 を基本にし、GBK / Big5 とも比較して決める
+// - 文字コードはアーカイブ単位で 1 つに決める（エントリごとに判定すると同じフォルダ名が別の文字列になりうる）
+
+// 自動判定で試す順（同点なら先のものを優先する）
+const AUTO_CANDIDATES: [&Encoding; 3] = [SHIFT_JIS, GBK, BIG5];
+
+/// ZIP エントリ名の文字コード指定
+#[derive(Clone, Copy, Debug, Default)]
+pub enum NameEncoding {
+    // CP932 / GBK / Big5 から判定する
+    #[default]
+    Auto,
+    // ZIP の仕様上の既定（zip クレートの解釈をそのまま使う）
+    Cp437,
+    Fixed(&'static Encoding),
+}
+
+impl NameEncoding {
+    /// extract ステップの encoding 指定を解釈する（"auto" / "cp437" / "cp932" / "shift_jis" / "gbk" / "big5" / "utf-8" など）
+    pub fn from_label(label: Option<&str>) -> Result<Self, ExtractError> {
+        let label = label.map(str::trim).filter(|s| !s.is_empty()).map(str::to_ascii_lowercase);
+        match label.as_deref() {
+            None | Some("auto") => Ok(NameEncoding::Auto),
+            Some("cp437" | "ibm437") => Ok(NameEncoding::Cp437),
+            // encoding_rs の Shift_JIS は CP932 の拡張文字も扱う
+            Some("cp932" | "ms932" | "sjis") => Ok(NameEncoding::Fixed(SHIFT_JIS)),
+            Some(other) => Encoding::for_label(other.as_bytes()).map(NameEncoding::Fixed).ok_or_else(|| ExtractError::InvalidOption(format!("unknown encoding: {}", other))),
+        }
+    }
+}
+
+/// UTF-8 として読めないエントリ名をまとめて変換する
+/// - raw: (生のバイト列, zip クレートが CP437 として解釈した名前)
+fn decode_entry_names(raw: &[(Vec<u8>, String)], encoding: NameEncoding) -> Vec<String> {
+    let legacy: Vec<&[u8]> = raw.iter().filter(|(bytes, _)| std::str::from_utf8(bytes).is_err()).map(|(bytes, _)| bytes.as_slice()).collect();
+    let chosen = match encoding {
+        NameEncoding::Cp437 => None,
+        NameEncoding::Fixed(enc) => Some(enc),
+        NameEncoding::Auto if legacy.is_empty() => None,
+        NameEncoding::Auto => Some(guess_encoding(&legacy)),
+    };
+    raw.iter()
+        .map(|(bytes, cp437)| match (std::str::from_utf8(bytes), chosen) {
+            (Ok(s), _) => s.to_string(),
+            (Err(_), Some(enc)) => enc.decode_without_bom_handling(bytes).0.into_owned(),
+            (Err(_), None) => cp437.clone(),
+        })
+        .collect()
+}
+
+/// 候補の文字コードごとに全エントリ名を変換し、もっともらしいものを選ぶ
+/// - 変換できないバイト列を含む候補は除外する（全候補が失敗した場合は CP932）
+fn guess_encoding(names: &[&[u8]]) -> &'static Encoding {
+    let mut best: Option<(&'static Encoding, i64)> = None;
+    for enc in AUTO_CANDIDATES {
+        let mut total = 0i64;
+        let mut valid = true;
+        for bytes in names {
+            match enc.decode_without_bom_handling_and_without_replacement(bytes) {
+                Some(text) => total += score_name(&text),
+                None => {
+                    valid = false;
+                    break;
+                }
+            }
+        }
+        if valid && best.map(|(_, score)| total > score).unwrap_or(true) {
+            best = Some((enc, total));
+        }
+    }
+    best.map(|(enc, _)| enc).unwrap_or(SHIFT_JIS)
+}
+
+/// ファイル名らしさの点数
+/// - かなは日本語の名前にしか現れないので高く、誤判定で出やすい半角カナや記号類は減点する
+fn score_name(text: &str) -> i64 {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7E => 0,
+            0x3041..=0x30FF => 3,
+            0x4E00..=0x9FFF => 2,
+            0x3000..=0x303F | 0xFF01..=0xFF5E => 1,
+            0xE000..=0xF8FF => -5,
+            _ => -1,
+        })
+        .sum()
+}
+
+// -----------------------
+// エントリの検査
+// -----------------------
+
+enum EntryKind {
+    Dir,
+    File,
+    // リンク先（アーカイブに格納された文字列そのまま）
+    Symlink(String),
+    // tar のハードリンク（リンク先はアーカイブのルートからのパス）
+    HardLink(String),
+    // デバイスファイルなど展開しないもの
+    Other,
+}
+
+/// 展開前に集めるエントリ情報
+struct EntryInfo {
+    name: String,
+    kind: EntryKind,
+    size: u64,
+}
+
+/// エントリ名を展開先からの相対パスに変換する（展開先の外を指す場合は理由を返す）
+/// - 区切りは / と \ の両方を受け付ける（Windows で作られた ZIP に \ が含まれることがある）
+fn sanitize_entry_path(name: &str) -> Result<PathBuf, &'static str> {
+    if name.contains('\0') {
+        return Err("invalid-name");
+    }
+    let normalized = name.replace('\\', "/");
+    if normalized.starts_with('/') {
+        return Err("absolute-path");
+    }
+    if has_drive_prefix(&normalized) {
+        return Err("drive-letter");
+    }
+    let mut out = PathBuf::new();
+    for part in normalized.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err("parent-dir"),
+            // C:foo のような相対ドライブ指定や NTFS の代替データストリームを防ぐ
+            p if p.contains(':') => return Err("invalid-name"),
+            p => out.push(p),
+        }
+    }
+    Ok(out)
+}
+
+fn has_drive_prefix(path: &str) -> bool {
+    let b = path.as_bytes();
+    b.len() >= 2 && b[0].is_ascii_alphabetic() && b[1] == b':'
+}
+
+/// シンボリックリンクのリンク先が展開先の中に収まるか（字句的に解決して判定）
+fn symlink_stays_inside(link_rel: &Path, target: &str) -> bool {
+    let target = target.replace('\\', "/");
+    if target.starts_with('/') || has_drive_prefix(&target) {
+        return false;
+    }
+    let mut stack: Vec<String> = link_rel.parent().map(|p| p.iter().map(|s| s.to_string_lossy().to_string()).collect()).unwrap_or_default();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if stack.pop().is_none() {
+                    return false;
+                }
+            }
+            p => stack.push(p.to_string()),
+        }
+    }
+    true
+}
+
+/// 全エントリを検査し、展開先からの相対パスを返す（None は展開先そのものを指すエントリ）
+fn check_entries(entries: &[EntryInfo], limits: &ExtractLimits) -> Result<Vec<Option<PathBuf>>, ExtractError> {
+    let count = entries.len() as u64;
+    if count > limits.max_entries {
+        return Err(ExtractError::TooManyEntries { count, limit: limits.max_entries });
+    }
+    let mut unsafe_entries = Vec::new();
+    let mut paths = Vec::with_capacity(entries.len());
+    let mut total: u64 = 0;
+    for entry in entries {
+        total = total.saturating_add(entry.size);
+        if total > limits.max_total_size {
+            return Err(ExtractError::TooLarge { limit: limits.max_total_size, entry: entry.name.clone() });
+        }
+        match sanitize_entry_path(&entry.name) {
+            Ok(rel) => {
+                match &entry.kind {
+                    EntryKind::Symlink(target) if !symlink_stays_inside(&rel, target) => {
+                        unsafe_entries.push(UnsafeEntry { name: format!("{} -> {}", entry.name, target), reason: "symlink-outside" });
+                    }
+                    EntryKind::HardLink(target) if sanitize_entry_path(target).is_err() => {
+                        unsafe_entries.push(UnsafeEntry { name: format!("{} -> {}", entry.name, target), reason: "symlink-outside" });
+                    }
+                    _ => {}
+                }
+                paths.push(if rel.as_os_str().is_empty() { None } else { Some(rel) });
+            }
+            Err(reason) => unsafe_entries.push(UnsafeEntry { name: entry.name.clone(), reason }),
+        }
+    }
+    if !unsafe_entries.is_empty() {
+        return Err(ExtractError::UnsafeEntries(unsafe_entries));
+    }
+    Ok(paths)
+}
+
+// -----------------------
+// 進捗・キャンセル・書き込み量の上限
+// -----------------------
+
+/// 展開中の状態
+/// - 書き込んだバイト数で上限を確認し（宣言サイズの偽装対策）、あわせて進捗の送信とキャンセルの確認を行う
+pub struct ExtractMonitor<'a> {
+    app: Option<&'a AppHandle>,
+    task_id: Option<&'a str>,
+    control: Option<&'a TaskControl>,
+    limit: u64,
+    entries_done: u64,
+    entries_total: u64,
+    bytes_written: u64,
+    bytes_total: u64,
+    // 作成したファイル（入れ子のアーカイブを探すのに使う）
+    created: Vec<PathBuf>,
+    // 置き換えたファイル（失敗時に元に戻す）
+    placement: Placement,
+    last_emit: Option<Instant>,
+}
+
+impl<'a> ExtractMonitor<'a> {
+    /// journal を渡すと、書き込みをインストールのジャーナルに記録する
+    pub fn new(app: Option<&'a AppHandle>, task_id: Option<&'a str>, control: Option<&'a TaskControl>, journal: Option<Arc<Journal>>) -> Self {
+        Self {
+            app,
+            task_id,
+            control,
+            limit: u64::MAX,
+            entries_done: 0,
+            entries_total: 0,
+            bytes_written: 0,
+            bytes_total: 0,
+            created: Vec::new(),
+            placement: Placement::with_journal(journal),
+            last_emit: None,
+        }
+    }
+
+    /// 検査が済んだエントリ一覧から合計を加える（入れ子のアーカイブでは展開するたびに増える）
+    fn begin(&mut self, limits: &ExtractLimits, entries: &[EntryInfo], paths: &[Option<PathBuf>]) {
+        self.limit = limits.max_total_size;
+        let targets = || entries.iter().zip(paths).filter(|(_, rel)| rel.is_some()).map(|(info, _)| info);
+        self.entries_total += targets().count() as u64;
+        self.bytes_total += targets().map(|info| info.size).sum::<u64>();
+        self.emit(true);
+    }
+
+    fn check_cancelled(&self) -> Result<(), ExtractError> {
+        if self.control.map(|c| c.is_cancelled()).unwrap_or(false) {
+            return Err(ExtractError::Cancelled);
+        }
+        Ok(())
+    }
+
+    fn wrote(&mut self, n: u64, name: &str) -> Result<(), ExtractError> {
+        self.check_cancelled()?;
+        if self.bytes_written.saturating_add(n) > self.limit {
+            return Err(ExtractError::TooLarge { limit: self.limit, entry: name.to_string() });
+        }
+        self.bytes_written += n;
+        self.emit(false);
+        Ok(())
+    }
+
+    fn entry_done(&mut self) {
+        self.entries_done += 1;
+        self.emit(false);
+    }
+
+    /// 置いたファイルを確定する
+    fn finish(mut self) {
+        self.emit(true);
+        self.placement.commit();
+    }
+
+    /// 置いたファイルを消して既存のファイルを元に戻す（作成したディレクトリは残る）
+    fn rollback(self) {
+        self.placement.rollback();
+    }
+
+    fn emit(&mut self, force: bool) {
+        let (Some(app), Some(task_id)) = (self.app, self.task_id) else {
+            return;
+        };
+        if !force && self.last_emit.map(|t| t.elapsed() < PROGRESS_INTERVAL).unwrap_or(false) {
+            return;
+        }
+        self.last_emit = Some(Instant::now());
+        let _ = app.emit(
+            "extract:progress",
+            serde_json::json!({
+                "taskId": task_id,
+                "entriesDone": self.entries_done,
+                "entriesTotal": self.entries_total,
+                "bytesWritten": self.bytes_written,
+                "bytesTotal": self.bytes_total,
+            }),
+        );
+    }
+}
+
+/// エントリ本体を一時ファイルに書き込んでから path に置く（失敗時は一時ファイルを消す）
+fn write_entry(reader: &mut dyn Read, path: &Path, name: &str, monitor: &mut ExtractMonitor) -> Result<(), ExtractError> {
+    let (tmp, file) = monitor.placement.create_temp(path)?;
+    let mut out = BufWriter::new(file);
+    let written = copy_entry(reader, &mut out, name, monitor).and_then(|_| {
+        let file = out.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(())
+    });
+    if let Err(e) = written.and_then(|_| Ok(monitor.placement.place(&tmp, path)?)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    monitor.created.push(path.to_path_buf());
+    Ok(())
+}
+
+fn copy_entry(reader: &mut dyn Read, out: &mut impl Write, name: &str, monitor: &mut ExtractMonitor) -> Result<(), ExtractError> {
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        monitor.wrote(n as u64, name)?;
+        out.write_all(&buf[..n])?;
+    }
+}
+
+// -----------------------
+// ZIP
+// -----------------------
+
+/// ZIP を展開する
+/// - シンボリックリンクは検査のみ行い作成しない（展開先は Windows の AviUtl2 フォルダで、リンクを必要とするパッケージはない）
+pub fn extract_zip_to(zip_path: &Path, dest: &Path, options: &ExtractOptions, monitor: &mut ExtractMonitor) -> Result<(), ExtractError> {
+    let limits = &options.limits;
+    let file = File::open(zip_path).map_err(|e| ExtractError::Open(format!("{}: {}", zip_path.display(), e)))?;
+    let mut archive = ZipArchive::new(BufReader::new(file)).map_err(|e| ExtractError::Open(format!("{}: {}", zip_path.display(), e)))?;
+
+    // 本体を読まずに済むよう raw で情報だけ取得する（リンク先だけは展開して読む）
+    // 暗号化されたエントリはここでパスワードを検証し、誤っていれば何も書き込まずに失敗する
+    // （ZipCrypto の検証は 1 バイトしかないため、全エントリで確かめて誤判定を減らす）
+    let password = options.password.as_deref();
+    let mut raw_names = Vec::with_capacity(archive.len());
+    let mut raw_infos = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let (name_raw, name, is_symlink, size, encrypted) = {
+            let entry = archive.by_index_raw(i)?;
+            (entry.name_raw().to_vec(), entry.name().to_string(), entry.is_symlink(), entry.size(), entry.encrypted())
+        };
+        if encrypted {
+            open_zip_entry(&mut archive, i, password)?;
+        }
+        let target = if is_symlink {
+            let mut target = Vec::new();
+            open_zip_entry(&mut archive, i, password)?.read_to_end(&mut target)?;
+            Some(target)
+        } else {
+            None
+        };
+        raw_names.push((name_raw, name));
+        raw_infos.push((target, size));
+    }
+    let names = decode_entry_names(&raw_names, options.encoding);
+    let entries: Vec<EntryInfo> = names
+        .into_iter()
+        .zip(raw_infos)
+        .map(|(name, (target, size))| {
+            // CP932 の 2 バイト目が \ になる文字（ソ・表など）があるため、ディレクトリ判定は変換後の名前で行う
+            let kind = match target {
+                Some(target) => EntryKind::Symlink(decode_entry_names(&[(target, String::new())], options.encoding).remove(0)),
+                None if name.ends_with('/') || name.ends_with('\\') => EntryKind::Dir,
+                None => EntryKind::File,
+            };
+            EntryInfo { name, kind, size }
+        })
+        .collect();
+    let paths = options.filter.apply(&entries, check_entries(&entries, limits)?)?;
+
+    monitor.placement.create_dir_all(dest)?;
+    monitor.begin(limits, &entries, &paths);
+    for (i, (info, rel)) in entries.iter().zip(paths).enumerate() {
+        let Some(rel) = rel else { continue };
+        monitor.check_cancelled()?;
+        let path = dest.join(rel);
+        match info.kind {
+            EntryKind::Dir => monitor.placement.create_dir_all(&path)?,
+            EntryKind::File => write_entry(&mut open_zip_entry(&mut archive, i, password)?, &path, &info.name, monitor)?,
+            EntryKind::Symlink(_) | EntryKind::HardLink(_) | EntryKind::Other => {}
+        }
+        monitor.entry_done();
+    }
+    Ok(())
+}
+
+/// エントリを開く（パスワードは暗号化されたエントリにだけ使われる）
+fn open_zip_entry<'a, R: Read + Seek>(archive: &'a mut ZipArchive<R>, index: usize, password: Option<&str>) -> Result<ZipFile<'a, R>, ExtractError> {
+    let entry = match password {
+        Some(password) => archive.by_index_decrypt(index, password.as_bytes())?,
+        None => archive.by_index(index)?,
+    };
+    Ok(entry)
+}
+
+// -----------------------
+// tar（gzip / xz 圧縮を含む）
+// -----------------------
+
+/// tar を展開する（open は先頭から読み直すたびに呼ばれる）
+/// - tar は先頭から順に読むしかないので、検査用と書き込み用に 2 回読む
+/// - リンクは ZIP と同様に検査のみ行い作成しない
+fn extract_tar<F>(open: F, dest: &Path, options: &ExtractOptions, monitor: &mut ExtractMonitor) -> Result<(), ExtractError>
+where
+    F: Fn() -> Result<Box<dyn Read>, ExtractError>,
+{
+    let limits = &options.limits;
+    let mut raw_names = Vec::new();
+    let mut raw_infos = Vec::new();
+    {
+        let mut archive = tar::Archive::new(open()?);
+        for entry in archive.entries().map_err(tar_error)? {
+            let entry = entry.map_err(tar_error)?;
+            let name_raw = entry.path_bytes().into_owned();
+            let link = entry.link_name_bytes().map(|b| String::from_utf8_lossy(&b).into_owned()).unwrap_or_default();
+            let kind = match entry.header().entry_type() {
+                EntryType::Regular | EntryType::Continuous => EntryKind::File,
+                EntryType::Directory => EntryKind::Dir,
+                EntryType::Symlink => EntryKind::Symlink(link),
+                EntryType::Link => EntryKind::HardLink(link),
+                _ => EntryKind::Other,
+            };
+            raw_names.push((name_raw.clone(), String::from_utf8_lossy(&name_raw).into_owned()));
+            raw_infos.push((kind, entry.size()));
+        }
+    }
+    let names = decode_entry_names(&raw_names, options.encoding);
+    let entries: Vec<EntryInfo> = names.into_iter().zip(raw_infos).map(|(name, (kind, size))| EntryInfo { name, kind, size }).collect();
+    let paths = options.filter.apply(&entries, check_entries(&entries, limits)?)?;
+
+    monitor.placement.create_dir_all(dest)?;
+    monitor.begin(limits, &entries, &paths);
+    let mut archive = tar::Archive::new(open()?);
+    for ((entry, info), rel) in archive.entries().map_err(tar_error)?.zip(&entries).zip(paths) {
+        let mut entry = entry.map_err(tar_error)?;
+        let Some(rel) = rel else { continue };
+        monitor.check_cancelled()?;
+        let path = dest.join(rel);
+        match info.kind {
+            EntryKind::Dir => monitor.placement.create_dir_all(&path)?,
+            EntryKind::File => write_entry(&mut entry, &path, &info.name, monitor)?,
+            EntryKind::Symlink(_) | EntryKind::HardLink(_) | EntryKind::Other => {}
+        }
+        monitor.entry_done();
+    }
+    Ok(())
+}
+
+fn tar_error(e: io::Error) -> ExtractError {
+    ExtractError::Archive(format!("tar read error: {e}"))
+}
+
+// -----------------------
+// 形式の判定
+// -----------------------
+
+/// 先頭バイトから判定したアーカイブ形式
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveKind {
+    Zip,
+    SevenZip,
+    Tar,
+    TarGz,
+    TarXz,
+}
+
+/// マジックバイトで形式を判定する（RAR など対応していない形式は UnsupportedFormat）
+pub fn sniff_archive(path: &Path) -> Result<ArchiveKind, ExtractError> {
+    let mut head = Vec::with_capacity(512);
+    File::open(path).and_then(|f| f.take(512).read_to_end(&mut head)).map_err(|e| ExtractError::Open(format!("{}: {}", path.display(), e)))?;
+    let kind = if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") || head.starts_with(b"PK\x07\x08") {
+        ArchiveKind::Zip
+    } else if head.starts_with(SEVENZ_SIGNATURE) {
+        ArchiveKind::SevenZip
+    } else if head.starts_with(b"\x1F\x8B") {
+        ArchiveKind::TarGz
+    } else if head.starts_with(b"\xFD7zXZ\x00") {
+        ArchiveKind::TarXz
+    } else if head.get(257..262) == Some(b"ustar") {
+        ArchiveKind::Tar
+    } else if head.starts_with(b"Rar!\x1A\x07") {
+        return Err(ExtractError::UnsupportedFormat("RAR archives are not supported; please repackage as zip or 7z".to_string()));
+    } else if head.starts_with(b"MZ") {
+        return Err(ExtractError::UnsupportedFormat("executable file; use the extract_sfx step for 7z self-extracting archives".to_string()));
+    } else {
+        return Err(ExtractError::UnsupportedFormat(format!("unknown archive format: {}", path.display())));
+    };
+    Ok(kind)
+}
+
+/// 形式を判定して展開する
+pub fn extract_archive_to(path: &Path, dest: &Path, options: &ExtractOptions, monitor: &mut ExtractMonitor) -> Result<ArchiveKind, ExtractError> {
+    let kind = sniff_archive(path)?;
+    let open_file = || File::open(path).map(BufReader::new).map_err(|e| ExtractError::Open(format!("{}: {}", path.display(), e)));
+    match kind {
+        ArchiveKind::Zip => extract_zip_to(path, dest, options, monitor)?,
+        ArchiveKind::SevenZip => extract_7z_reader(open_file()?, dest, options, monitor)?,
+        ArchiveKind::Tar => extract_tar(|| Ok(Box::new(open_file()?)), dest, options, monitor)?,
+        ArchiveKind::TarGz => extract_tar(|| Ok(Box::new(flate2::read::MultiGzDecoder::new(open_file()?))), dest, options, monitor)?,
+        ArchiveKind::TarXz => extract_tar(|| Ok(Box::new(lzma_rust2::XzReader::new(open_file()?, true))), dest, options, monitor)?,
+    }
+    Ok(kind)
+}
+
+// -----------------------
+// 入れ子のアーカイブ
+// -----------------------
+//
+// - 展開したファイルのうち、拡張子とマジックバイトの両方がアーカイブのものを同じ場所のフォルダ（拡張子を除いた名前）に展開し、
+//   元のアーカイブは削除する。展開したフォルダの中をさらに調べ、nested_depth まで繰り返す
+// - 削除も placement で行う（ジャーナルに記録され、失敗時は展開したファイルと一緒に元に戻る）
+// - 内側のアーカイブには include / exclude / strip を適用しない（外側から取り出したものを丸ごと展開する）
+// - 書き込み量の上限は外側と合わせて数える（入れ子の zip bomb 対策）
+
+// 入れ子として展開する深さの上限
+const MAX_NESTED_DEPTH: u32 = 4;
+// 入れ子として扱う拡張子（中身が別形式のファイルを誤って展開しないよう、マジックバイトと両方で判定する）
+// tar でない単独の .gz / .xz は展開できないので含めない
+const NESTED_SUFFIXES: [&str; 7] = [".tar.gz", ".tar.xz", ".tgz", ".txz", ".zip", ".7z", ".tar"];
+
+/// 展開した内側のアーカイブ
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NestedArchive {
+    // 展開先からの相対パス（区切りは /）
+    pub archive: String,
+    // 中身を展開したフォルダ
+    pub dir: String,
+    pub kind: ArchiveKind,
+    // 外側のアーカイブの直下が 1
+    pub depth: u32,
+}
+
+/// extract_archive の結果
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractSummary {
+    pub kind: ArchiveKind,
+    pub nested: Vec<NestedArchive>,
+}
+
+/// 形式を判定して展開し、options.nested_depth まで内側のアーカイブも展開する
+pub fn extract_archive_nested(path: &Path, dest: &Path, options: &ExtractOptions, monitor: &mut ExtractMonitor) -> Result<ExtractSummary, ExtractError> {
+    let mut scanned = monitor.created.len();
+    let kind = extract_archive_to(path, dest, options, monitor)?;
+    let inner_options = ExtractOptions { filter: EntryFilter::default(), ..options.clone() };
+    let mut nested = Vec::new();
+    for depth in 1..=options.nested_depth {
+        let found: Vec<PathBuf> = monitor.created[scanned..].iter().filter(|p| is_nested_archive(p)).cloned().collect();
+        scanned = monitor.created.len();
+        if found.is_empty() {
+            break;
+        }
+        for archive in found {
+            let dir = nested_dir(&archive);
+            let kind = extract_archive_to(&archive, &dir, &inner_options, monitor)?;
+            monitor.placement.remove(&archive)?;
+            let rel = archive.strip_prefix(dest).unwrap_or(&archive);
+            nested.push(NestedArchive {
+                archive: rel.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/"),
+                dir: dir.to_string_lossy().into_owned(),
+                kind,
+                depth,
+            });
+        }
+    }
+    Ok(ExtractSummary { kind, nested })
+}
+
+fn is_nested_archive(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    NESTED_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) && sniff_archive(path).is_ok()
+}
+
+/// 内側のアーカイブの展開先（inner.zip → inner、inner.tar.gz → inner。同名があれば inner_1, inner_2, ...）
+fn nested_dir(archive: &Path) -> PathBuf {
+    let name = archive.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let lower = name.to_ascii_lowercase();
+    let stem = NESTED_SUFFIXES.iter().find(|suffix| lower.ends_with(*suffix) && lower.len() > suffix.len()).map(|suffix| &name[..name.len() - suffix.len()]).unwrap_or(&name);
+    let parent = archive.parent().unwrap_or(Path::new(""));
+    let mut dir = parent.join(stem);
+    let mut n = 1;
+    while dir.exists() {
+        dir = parent.join(format!("{stem}_{n}"));
+        n += 1;
+    }
+    dir
+}
+
+// -----------------------
+// 7z
+// -----------------------
+
+/// 7z SFX（自己解凍形式）から埋め込まれた 7z データを探して展開する
+pub fn extract_7z_sfx_to(sfx_path: &Path, dest: &Path, options: &ExtractOptions, monitor: &mut ExtractMonitor) -> Result<(), ExtractError> {
+    let file = File::open(sfx_path).map_err(|e| ExtractError::Open(format!("{}: {}", sfx_path.display(), e)))?;
+    // ファイルをメモリマップする（シグネチャ検索を高速に行うため）
+    let mmap = unsafe { MmapOptions::new().map(&file) }.map_err(|e| ExtractError::Open(format!("mmap error: {e}")))?;
+    let offset = Finder::new(SEVENZ_SIGNATURE).find(&mmap).ok_or_else(|| ExtractError::Open("7z signature not found in SFX binary".to_string()))?;
+    extract_7z_reader(Cursor::new(&mmap[offset..]), dest, options, monitor)
+}
+
+/// 7z データを展開する
+/// - エントリ名は UTF-16 で格納されるため文字コードの指定は使わない
+/// - 7z のシンボリックリンクは sevenz-rust2 が通常ファイルとして書き出すため、パスの検査のみ行う
+/// - ヘッダーが暗号化されていればここで、本体だけが暗号化されていれば展開中にパスワードの誤りが分かる
+///   展開中は復号・伸長のエラーとして現れるので、読み込みのエラーは sevenz-rust2 に返して MaybeBadPassword に変換させる
+fn extract_7z_reader<R: Read + Seek>(reader: R, dest: &Path, options: &ExtractOptions, monitor: &mut ExtractMonitor) -> Result<(), ExtractError> {
+    let limits = &options.limits;
+    let password = options.password.as_deref().map(Password::from).unwrap_or_else(Password::empty);
+    let mut archive = ArchiveReader::new(reader, password).map_err(|e| sevenz_error(e, "7z open error"))?;
+    let entries: Vec<EntryInfo> = archive
+        .archive()
+        .files
+        .iter()
+        .map(|e| EntryInfo {
+            name: e.name().to_string(),
+            kind: if e.is_directory() { EntryKind::Dir } else { EntryKind::File },
+            size: e.size(),
+        })
+        .collect();
+    let paths = options.filter.apply(&entries, check_entries(&entries, limits)?)?;
+    monitor.begin(limits, &entries, &paths);
+    let targets: HashMap<String, PathBuf> = entries.iter().zip(paths).filter_map(|(info, rel)| rel.map(|rel| (info.name.clone(), dest.join(rel)))).collect();
+
+    monitor.placement.create_dir_all(dest)?;
+    // コールバック内では sevenz-rust2 のエラーしか返せないので、書き込み側のエラーは外に退避して中断する
+    let mut failure: Option<ExtractError> = None;
+    let result = archive.for_each_entries(|entry, reader| {
+        let Some(path) = targets.get(entry.name()) else {
+            return Ok(true);
+        };
+        let mut reader = ReadErrorTap { inner: reader, error: None };
+        let written = if entry.is_directory() {
+            monitor.check_cancelled().and_then(|_| monitor.placement.create_dir_all(path).map_err(ExtractError::from))
+        } else {
+            write_entry(&mut reader, path, entry.name(), monitor)
+        };
+        match (written, reader.error) {
+            (Ok(()), _) => {
+                monitor.entry_done();
+                Ok(true)
+            }
+            (Err(_), Some(read_error)) => Err(read_error.into()),
+            (Err(e), None) => {
+                failure = Some(e);
+                Ok(false)
+            }
+        }
+    });
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    result.map_err(|e| sevenz_error(e, "7z decompress error"))
+}
+
+/// 読み込みで起きたエラーを残しておく Read（書き込み側のエラーと区別するため）
+struct ReadErrorTap<'a> {
+    inner: &'a mut dyn Read,
+    error: Option<io::Error>,
+}
+
+impl Read for ReadErrorTap<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).inspect_err(|e| self.error = Some(io::Error::new(e.kind(), e.to_string())))
+    }
+}
+
+fn sevenz_error(e: sevenz_rust2::Error, context: &str) -> ExtractError {
+    match e {
+        sevenz_rust2::Error::PasswordRequired => ExtractError::PasswordRequired,
+        sevenz_rust2::Error::MaybeBadPassword(_) => ExtractError::PasswordIncorrect,
+        e => ExtractError::Archive(format!("{context}: {e}")),
+    }
+}
+
+// -----------------------
+// 一覧
+// -----------------------
+
+/// アーカイブ内のエントリ
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveListEntry {
+    // アーカイブ内のパス（区切りは /。UTF-8 でない ZIP のエントリ名は展開時と同じ規則で変換する）
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    // 7z のソリッド圧縮ではエントリ単位の圧縮後サイズが無いので None
+    pub compressed_size: Option<u64>,
+    // 更新日時（UNIX 秒。ZIP の日時はタイムゾーンを持たないのでローカル時刻として解釈する）
+    pub mtime: Option<i64>,
+    pub crc32: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveListing {
+    pub kind: ArchiveKind,
+    pub entries: Vec<ArchiveListEntry>,
+}
+
+/// 形式を判定してエントリを一覧する（本体は展開しない）
+pub fn list_archive_at(path: &Path, options: &ExtractOptions) -> Result<ArchiveListing, ExtractError> {
+    let kind = sniff_archive(path)?;
+    let entries = match kind {
+        ArchiveKind::Zip => list_zip(path, options)?,
+        ArchiveKind::SevenZip => list_7z(path, options)?,
+        ArchiveKind::Tar | ArchiveKind::TarGz | ArchiveKind::TarXz => {
+            return Err(ExtractError::UnsupportedFormat(format!("listing is supported for zip and 7z only: {}", path.display())));
+        }
+    };
+    Ok(ArchiveListing { kind, entries })
+}
+
+fn list_zip(path: &Path, options: &ExtractOptions) -> Result<Vec<ArchiveListEntry>, ExtractError> {
+    let file = File::open(path).map_err(|e| ExtractError::Open(format!("{}: {}", path.display(), e)))?;
+    let mut archive = ZipArchive::new(BufReader::new(file)).map_err(|e| ExtractError::Open(format!("{}: {}", path.display(), e)))?;
+    let mut raw_names = Vec::with_capacity(archive.len());
+    let mut infos = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        raw_names.push((entry.name_raw().to_vec(), entry.name().to_string()));
+        infos.push((entry.size(), entry.compressed_size(), entry.last_modified().and_then(zip_mtime), entry.crc32()));
+    }
+    let names = decode_entry_names(&raw_names, options.encoding);
+    let entries = names
+        .into_iter()
+        .zip(infos)
+        .map(|(name, (size, compressed_size, mtime, crc32))| {
+            let path = name.replace('\\', "/");
+            ArchiveListEntry {
+                is_dir: path.ends_with('/'),
+                path: path.trim_end_matches('/').to_string(),
+                size,
+                compressed_size: Some(compressed_size),
+                mtime,
+                crc32: Some(crc32),
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+fn zip_mtime(dt: zip::DateTime) -> Option<i64> {
+    let local = chrono::NaiveDate::from_ymd_opt(dt.year().into(), dt.month().into(), dt.day().into())?.and_hms_opt(dt.hour().into(), dt.minute().into(), dt.second().into())?;
+    local.and_local_timezone(chrono::Local).earliest().map(|t| t.timestamp())
+}
+
+fn list_7z(path: &Path, options: &ExtractOptions) -> Result<Vec<ArchiveListEntry>, ExtractError> {
+    let file = File::open(path).map_err(|e| ExtractError::Open(format!("{}: {}", path.display(), e)))?;
+    let password = options.password.as_deref().map(Password::from).unwrap_or_else(Password::empty);
+    let reader = ArchiveReader::new(BufReader::new(file), password).map_err(|e| sevenz_error(e, "7z open error"))?;
+    let archive = reader.archive();
+    let entries = archive
+        .files
+        .iter()
+        .map(|e| ArchiveListEntry {
+            path: e.name().replace('\\', "/"),
+            is_dir: e.is_directory(),
+            size: e.size(),
+            compressed_size: (!archive.is_solid).then_some(e.compressed_size),
+            mtime: e.has_last_modified_date.then(|| chrono::DateTime::<chrono::Utc>::from(std::time::SystemTime::from(e.last_modified_date)).timestamp()),
+            crc32: e.has_crc.then_some(e.crc as u32),
+        })
+        .collect();
+    Ok(entries)
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// 展開処理を別スレッドで実行する
+/// - task_id があれば TaskRegistry に登録し、cancel_task で中断できるようにする
+/// - 失敗・キャンセル時は書き込んだファイルを消して既存のファイルを元に戻す（パスワードを入れ直して再実行できるように）
+/// - journal はインストール中の展開で渡す（書き込みをインストール全体の巻き戻しの対象にする）
+pub async fn run_extract<T, F>(app: &AppHandle, tasks: &TaskRegistry, task_id: Option<String>, journal: Option<Arc<Journal>>, f: F) -> Result<T, ExtractError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut ExtractMonitor) -> Result<T, ExtractError> + Send + 'static,
+{
+    let guard = task_id.as_deref().map(|id| tasks.register(id));
+    let control = guard.as_ref().map(|g| g.handle());
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut monitor = ExtractMonitor::new(Some(&app), task_id.as_deref(), control.as_deref(), journal);
+        let result = f(&mut monitor);
+        match &result {
+            Ok(_) => monitor.finish(),
+            Err(_) => monitor.rollback(),
+        }
+        result
+    })
+    .await
+    .map_err(|e| ExtractError::Io(format!("task join error: {e}")))?
+}
+
+/// アーカイブを形式を判定して展開する（zip / 7z / tar / tar.gz / tar.xz）
+/// - options.encoding: ZIP・tar の UTF-8 でないエントリ名の文字コード（省略時は自動判定）
+/// - options.password: 暗号化された ZIP / 7z のパスワード
+/// - options.include / exclude / stripComponents: 展開するエントリの絞り込みと先頭階層の除去
+/// - options.nestedDepth: 中に入っているアーカイブを続けて展開する深さ
+/// - 戻り値は判定した形式と展開した内側のアーカイブ
+#[tauri::command]
+pub async fn extract_archive(
+    app: AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    archive_path: String,
+    dest_path: String,
+    options: Option<ExtractArgs>,
+    task_id: Option<String>,
+) -> Result<ExtractSummary, ExtractError> {
+    let options = options.unwrap_or_default().into_options()?;
+    run_extract(&app, &tasks, task_id, None, move |monitor| extract_archive_nested(Path::new(&archive_path), Path::new(&dest_path), &options, monitor)).await
+}
+
+/// ZIPファイルを解凍する
+/// - encoding: UTF-8 フラグのないエントリ名の文字コード（省略時は自動判定）
+/// - password: ZipCrypto / AES で暗号化されたエントリのパスワード
+#[tauri::command]
+pub async fn extract_zip(
+    app: AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    zip_path: String,
+    dest_path: String,
+    encoding: Option<String>,
+    password: Option<String>,
+    task_id: Option<String>,
+) -> Result<(), ExtractError> {
+    let options = ExtractOptions::from_args(encoding.as_deref(), password)?;
+    run_extract(&app, &tasks, task_id, None, move |monitor| extract_zip_to(Path::new(&zip_path), Path::new(&dest_path), &options, monitor)).await
+}
+
+/// 7z SFX（自己解凍形式）ファイルを展開
+/// - password: AES で暗号化されている場合のパスワード
+#[tauri::command]
+pub async fn extract_7z_sfx(
+    app: AppHandle,
+    tasks: tauri::State<'_, TaskRegistry>,
+    sfx_path: String,
+    dest_path: String,
+    password: Option<String>,
+    task_id: Option<String>,
+) -> Result<(), ExtractError> {
+    let options = ExtractOptions::from_args(None, password)?;
+    run_extract(&app, &tasks, task_id, None, move |monitor| extract_7z_sfx_to(Path::new(&sfx_path), Path::new(&dest_path), &options, monitor)).await
+}
+
+/// アーカイブの中身を一覧する（zip / 7z）
+/// - 登録画面で extract ステップの include / stripComponents を組み立てるときに使う
+#[tauri::command]
+pub async fn list_archive(archive_path: String, encoding: Option<String>, password: Option<String>) -> Result<ArchiveListing, ExtractError> {
+    let options = ExtractOptions::from_args(encoding.as_deref(), password)?;
+    tauri::async_runtime::spawn_blocking(move || list_archive_at(Path::new(&archive_path), &options)).await.map_err(|e| ExtractError::Io(format!("task join error: {e}")))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::SimpleFileOptions;
+
+    fn file(name: &str, size: u64) -> EntryInfo {
+        EntryInfo { name: name.to_string(), kind: EntryKind::File, size }
+    }
+
+    /// (名前, 内容) から ZIP を作る（名前が / で終わるものはディレクトリ）
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])], options: SimpleFileOptions) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, body) in entries {
+            if name.ends_with('/') {
+                zip.add_directory(*name, options).unwrap();
+            } else {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(body).unwrap();
+            }
+        }
+        zip.finish().unwrap();
+    }
+
+    /// (名前, 内容) から 7z を作る（password があれば本体を AES で暗号化し、encrypt_header ならヘッダーも暗号化する）
+    fn write_7z(path: &Path, entries: &[(&str, &[u8])], password: Option<&str>, encrypt_header: bool) {
+        use sevenz_rust2::encoder_options::AesEncoderOptions;
+        use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod};
+        let mut writer = ArchiveWriter::create(path).unwrap();
+        if let Some(password) = password {
+            writer.set_content_methods(vec![
+                AesEncoderOptions::new(Password::from(password)).into(),
+                EncoderMethod::LZMA2.into(),
+            ]);
+            writer.set_encrypt_header(encrypt_header);
+        }
+        for (name, body) in entries {
+            writer.push_archive_entry(ArchiveEntry::new_file(name), Some(*body)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn write_tar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, body) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(body.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *body).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// extract_archive_to を実行し、失敗したら書いたファイルを元に戻す
+    fn extract_file(path: &Path, dest: &Path, options: &ExtractOptions) -> Result<ArchiveKind, ExtractError> {
+        let mut monitor = ExtractMonitor::new(None, None, None, None);
+        let result = extract_archive_to(path, dest, options, &mut monitor);
+        match &result {
+            Ok(_) => monitor.finish(),
+            Err(_) => monitor.rollback(),
+        }
+        result
+    }
+
+    fn extract_zip_file(zip_path: &Path, dest: &Path, options: &ExtractOptions) -> Result<(), ExtractError> {
+        let mut monitor = ExtractMonitor::new(None, None, None, None);
+        let result = extract_zip_to(zip_path, dest, options, &mut monitor);
+        match &result {
+            Ok(_) => monitor.finish(),
+            Err(_) => monitor.rollback(),
+        }
+        result
+    }
+
+    #[test]
+    fn entry_paths_outside_destination_are_rejected() {
+        assert_eq!(sanitize_entry_path("dir/./file.txt").unwrap(), Path::new("dir").join("file.txt"));
+        assert_eq!(sanitize_entry_path("dir\\sub\\file.txt").unwrap(), Path::new("dir").join("sub").join("file.txt"));
+        for (name, reason) in [
+            ("../evil.dll", "parent-dir"),
+            ("dir/../../evil.dll", "parent-dir"),
+            ("..\\evil.dll", "parent-dir"),
+            ("/etc/passwd", "absolute-path"),
+            ("\\Windows\\evil.dll", "absolute-path"),
+            ("C:/Windows/evil.dll", "drive-letter"),
+            ("dir/C:evil.dll", "invalid-name"),
+            ("file.txt:stream", "invalid-name"),
+            ("nul\0byte", "invalid-name"),
+        ] {
+            assert_eq!(sanitize_entry_path(name).unwrap_err(), reason, "{name}");
+        }
+    }
+
+    #[test]
+    fn symlinks_must_stay_inside() {
+        assert!(symlink_stays_inside(Path::new("a/b/link"), "../c/target"));
+        assert!(symlink_stays_inside(Path::new("a/link"), "./target"));
+        assert!(!symlink_stays_inside(Path::new("a/link"), "../../outside"));
+        assert!(!symlink_stays_inside(Path::new("link"), "/etc/passwd"));
+        assert!(!symlink_stays_inside(Path::new("link"), "C:\\Windows"));
+    }
+
+    #[test]
+    fn check_entries_reports_every_unsafe_entry() {
+        let entries = vec![
+            file("ok/file.txt", 1),
+            file("../slip.txt", 1),
+            EntryInfo {
+                name: "ok/link".to_string(),
+                kind: EntryKind::Symlink("../../outside".to_string()),
+                size: 0,
+            },
+            EntryInfo {
+                name: "ok/hard".to_string(),
+                kind: EntryKind::HardLink("/etc/passwd".to_string()),
+                size: 0,
+            },
+            file("./", 0),
+        ];
+        let Err(ExtractError::UnsafeEntries(unsafe_entries)) = check_entries(&entries, &ExtractLimits::default()) else {
+            panic!("unsafe entries were accepted");
+        };
+        let reasons: Vec<&str> = unsafe_entries.iter().map(|e| e.reason).collect();
+        assert_eq!(reasons, ["parent-dir", "symlink-outside", "symlink-outside"]);
+
+        let paths = check_entries(&[file("ok/file.txt", 1), file("./", 0)], &ExtractLimits::default()).unwrap();
+        assert_eq!(paths, vec![Some(Path::new("ok").join("file.txt")), None]);
+    }
+
+    #[test]
+    fn check_entries_enforces_limits() {
+        let limits = ExtractLimits { max_entries: 2, max_total_size: 10 };
+        let err = check_entries(&[file("a", 1), file("b", 1), file("c", 1)], &limits).unwrap_err();
+        assert!(matches!(err, ExtractError::TooManyEntries { count: 3, limit: 2 }));
+        let err = check_entries(&[file("a", 6), file("b", 6)], &limits).unwrap_err();
+        assert!(matches!(err, ExtractError::TooLarge { limit: 10, ref entry } if entry == "b"));
+    }
+
+    #[test]
+    fn zip_slip_writes_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let zip_path = tmp.path().join("slip.zip");
+        write_zip(&zip_path, &[("good.txt", b"good"), ("../evil.txt", b"evil")], SimpleFileOptions::default());
+        let dest = tmp.path().join("dest");
+        let err = extract_zip_file(&zip_path, &dest, &ExtractOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "EXTRACT_UNSAFE_ENTRY");
+        assert!(!dest.join("good.txt").exists());
+        assert!(!tmp.path().join("evil.txt").exists());
+    }
+
+    #[test]
+    fn oversized_zip_writes_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let zip_path = tmp.path().join("big.zip");
+        write_zip(&zip_path, &[("a.bin", &[0u8; 64]), ("b.bin", &[0u8; 64])], SimpleFileOptions::default());
+        let dest = tmp.path().join("dest");
+        let options = ExtractOptions {
+            limits: ExtractLimits { max_entries: 10, max_total_size: 100 },
+            ..Default::default()
+        };
+        assert_eq!(extract_zip_file(&zip_path, &dest, &options).unwrap_err().code(), "EXTRACT_TOO_LARGE");
+        assert!(!dest.join("a.bin").exists());
+    }
+
+    #[test]
+    fn strip_components_rejects_entries_that_collide() {
+        let tmp = tempfile::tempdir().unwrap();
+        let zip_path = tmp.path().join("strip.zip");
+        write_zip(
+            &zip_path,
+            &[
+                ("a/", b""),
+                ("a/Plugin/", b""),
+                ("a/Plugin/x.auf", b"a"),
+                ("b/", b""),
+                ("b/Plugin/", b""),
+                ("b/plugin/X.auf", b"b"),
+                ("b/readme.txt", b"r"),
+            ],
+            SimpleFileOptions::default(),
+        );
+        let dest = tmp.path().join("dest");
+        let options = ExtractOptions { filter: EntryFilter::new(&[], &[], 1).unwrap(), ..Default::default() };
+        let Err(ExtractError::UnsafeEntries(entries)) = extract_zip_file(&zip_path, &dest, &options) else {
+            panic!("expected EXTRACT_UNSAFE_ENTRY");
+        };
+        // ディレクトリ同士（a/Plugin/ と b/Plugin/）は重なってもよく、ファイルだけが拒否される
+        let names: Vec<(&str, &str)> = entries.iter().map(|e| (e.name.as_str(), e.reason)).collect();
+        assert_eq!(
+            names,
+            [
+                ("a/Plugin/x.auf -> Plugin/x.auf", "duplicate-path"),
+                ("b/plugin/X.auf -> plugin/X.auf", "duplicate-path")
+            ]
+        );
+        assert!(!dest.join("readme.txt").exists());
+
+        // 重ならないように絞り込めば展開できる
+        let options = ExtractOptions {
+            filter: EntryFilter::new(&["b/**".to_string()], &[], 1).unwrap(),
+            ..Default::default()
+        };
+        extract_zip_file(&zip_path, &dest, &options).unwrap();
+        assert_eq!(fs::read(dest.join("plugin").join("X.auf")).unwrap(), b"b");
+        assert!(dest.join("readme.txt").exists());
+    }
+
+    #[test]
+    fn nested_archive_removal_is_journaled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let inner_path = tmp.path().join("inner.zip");
+        write_zip(&inner_path, &[("a.txt", b"inner")], SimpleFileOptions::default());
+        let inner = fs::read(&inner_path).unwrap();
+        let outer_path = tmp.path().join("outer.zip");
+        write_zip(&outer_path, &[("inner.zip", &inner), ("b.txt", b"outer")], SimpleFileOptions::default());
+
+        let dest = tmp.path().join("dest");
+        let journal = Arc::new(Journal::begin(&tmp.path().join("journal"), "pkg", "1.0", Vec::new()).unwrap());
+        let mut monitor = ExtractMonitor::new(None, None, None, Some(journal.clone()));
+        let options = ExtractOptions { nested_depth: 1, ..Default::default() };
+        let summary = extract_archive_nested(&outer_path, &dest, &options, &mut monitor).unwrap();
+        monitor.finish();
+        assert_eq!(summary.nested.len(), 1);
+        assert!(!dest.join("inner.zip").exists());
+        assert_eq!(fs::read(dest.join("inner").join("a.txt")).unwrap(), b"inner");
+
+        // 削除した内側のアーカイブは置いたファイルの一覧に残らない
+        let placed: Vec<PathBuf> = journal.sections().unwrap().remove(0).placed.files.into_iter().map(|(path, _)| path).collect();
+        assert!(placed.contains(&dest.join("inner").join("a.txt")));
+        assert!(!placed.contains(&dest.join("inner.zip")));
+
+        // 巻き戻すと退避したアーカイブも含めて何も残らない
+        Arc::into_inner(journal).unwrap().rollback().unwrap();
+        assert!(!dest.join("inner.zip").exists());
+        assert!(!dest.join("b.txt").exists());
+        assert!(!dest.join("inner").join("a.txt").exists());
+        assert_eq!(fs::read_dir(&dest).map(|d| d.count()).unwrap_or(0), 0);
+    }
+
+    fn encode(enc: &'static Encoding, names: &[&str]) -> Vec<Vec<u8>> {
+        names.iter().map(|n| enc.encode(n).0.into_owned()).collect()
+    }
+
+    #[test]
+    fn guess_encoding_picks_the_plausible_legacy_encoding() {
+        let cases: [(&'static Encoding, &[&str]); 3] = [
+            (SHIFT_JIS, &["プラグイン/設定ファイル.txt", "スクリプト/表示.lua", "説明書.txt"]),
+            (GBK, &["插件/说明文档.txt", "脚本/设置.lua"]),
+            (BIG5, &["外掛/說明檔案.txt", "腳本/設定.lua"]),
+        ];
+        for (enc, names) in cases {
+            let raw = encode(enc, names);
+            let refs: Vec<&[u8]> = raw.iter().map(Vec::as_slice).collect();
+            assert_eq!(guess_encoding(&refs).name(), enc.name(), "{names:?}");
+        }
+        // どれでも読めない場合は CP932
+        assert_eq!(guess_encoding(&[b"\xFF\xFF\xFF"]).name(), SHIFT_JIS.name());
+    }
+
+    #[test]
+    fn decode_entry_names_keeps_utf8_and_decodes_the_rest_together() {
+        let sjis = SHIFT_JIS.encode("ソフト/表示.txt").0.into_owned();
+        let raw = vec![
+            (sjis.clone(), "cp437 name".to_string()),
+            ("utf8/名前.txt".as_bytes().to_vec(), String::new()),
+        ];
+        assert_eq!(decode_entry_names(&raw, NameEncoding::Auto), ["ソフト/表示.txt", "utf8/名前.txt"]);
+        assert_eq!(decode_entry_names(&raw, NameEncoding::Cp437), ["cp437 name", "utf8/名前.txt"]);
+        assert_eq!(decode_entry_names(&raw, NameEncoding::from_label(Some("gbk")).unwrap())[1], "utf8/名前.txt");
+        // ソ・表の 2 バイト目は \ だが、変換後の名前には \ が残らない
+        assert!(sjis.contains(&b'\\'));
+        assert!(!decode_entry_names(&raw, NameEncoding::from_label(Some("cp932")).unwrap())[0].contains('\\'));
+    }
+
+    #[test]
+    fn encoding_labels() {
+        assert!(matches!(NameEncoding::from_label(None).unwrap(), NameEncoding::Auto));
+        assert!(matches!(NameEncoding::from_label(Some(" AUTO ")).unwrap(), NameEncoding::Auto));
+        assert!(matches!(NameEncoding::from_label(Some("cp437")).unwrap(), NameEncoding::Cp437));
+        assert!(matches!(NameEncoding::from_label(Some("ms932")).unwrap(), NameEncoding::Fixed(enc) if enc == SHIFT_JIS));
+        assert!(matches!(NameEncoding::from_label(Some("big5")).unwrap(), NameEncoding::Fixed(enc) if enc == BIG5));
+        assert_eq!(NameEncoding::from_label(Some("klingon")).unwrap_err().code(), "EXTRACT_INVALID_OPTION");
+    }
+
+    #[test]
+    fn sniff_archive_uses_magic_bytes_not_extensions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tar = write_tar(&[("a.txt", b"a")]);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&tar).unwrap();
+        let cases: Vec<(&str, Vec<u8>, Option<ArchiveKind>)> = vec![
+            ("zip.bin", b"PK\x03\x04rest".to_vec(), Some(ArchiveKind::Zip)),
+            ("empty.dat", b"PK\x05\x06".to_vec(), Some(ArchiveKind::Zip)),
+            ("a.zip", [SEVENZ_SIGNATURE, b"rest"].concat(), Some(ArchiveKind::SevenZip)),
+            ("a.7z", gz.finish().unwrap(), Some(ArchiveKind::TarGz)),
+            ("a.gz", b"\xFD7zXZ\x00rest".to_vec(), Some(ArchiveKind::TarXz)),
+            ("a.xz", tar, Some(ArchiveKind::Tar)),
+            ("a.rar", b"Rar!\x1A\x07\x00".to_vec(), None),
+            ("setup.zip", b"MZ\x90\x00".to_vec(), None),
+            ("text.zip", b"hello".to_vec(), None),
+        ];
+        for (name, body, expected) in cases {
+            let path = tmp.path().join(name);
+            fs::write(&path, body).unwrap();
+            match (sniff_archive(&path), expected) {
+                (Ok(kind), Some(expected)) => assert_eq!(kind, expected, "{name}"),
+                (Err(e), None) => assert_eq!(e.code(), "EXTRACT_UNSUPPORTED_FORMAT", "{name}"),
+                (result, _) => panic!("{name}: unexpected {result:?}"),
+            }
+        }
+        assert_eq!(sniff_archive(&tmp.path().join("missing")).unwrap_err().code(), "EXTRACT_OPEN_FAILED");
+    }
+
+    #[test]
+    fn extract_archive_to_handles_each_format() {
+        let tmp = tempfile::tempdir().unwrap();
+        let entries: [(&str, &[u8]); 2] = [("dir/a.txt", b"alpha"), ("b.txt", b"beta")];
+        let zip_path = tmp.path().join("archive.bin");
+        write_zip(&zip_path, &entries, SimpleFileOptions::default());
+        let sevenz_path = tmp.path().join("archive.7z");
+        write_7z(&sevenz_path, &entries, None, false);
+        let tar_gz_path = tmp.path().join("archive.tgz");
+        let mut gz = flate2::write::GzEncoder::new(File::create(&tar_gz_path).unwrap(), flate2::Compression::default());
+        gz.write_all(&write_tar(&entries)).unwrap();
+        gz.finish().unwrap();
+
+        for (path, kind) in [
+            (zip_path, ArchiveKind::Zip),
+            (sevenz_path, ArchiveKind::SevenZip),
+            (tar_gz_path, ArchiveKind::TarGz),
+        ] {
+            let dest = tmp.path().join(format!("{kind:?}"));
+            assert_eq!(extract_file(&path, &dest, &ExtractOptions::default()).unwrap(), kind);
+            assert_eq!(fs::read(dest.join("dir").join("a.txt")).unwrap(), b"alpha");
+            assert_eq!(fs::read(dest.join("b.txt")).unwrap(), b"beta");
+        }
+    }
+
+    fn with_password(password: Option<&str>) -> ExtractOptions {
+        ExtractOptions::from_args(None, password.map(str::to_string)).unwrap()
+    }
+
+    #[test]
+    fn encrypted_7z_reports_missing_and_wrong_password() {
+        let tmp = tempfile::tempdir().unwrap();
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        // ヘッダーも暗号化されていれば開くときに、本体だけなら展開中に分かる
+        for encrypt_header in [true, false] {
+            let path = tmp.path().join(format!("secret-{encrypt_header}.7z"));
+            write_7z(&path, &[("plugin/a.aui2", &body), ("b.txt", b"beta")], Some("correct"), encrypt_header);
+            let dest = tmp.path().join(format!("dest-{encrypt_header}"));
+
+            assert_eq!(extract_file(&path, &dest, &with_password(None)).unwrap_err().code(), "EXTRACT_PASSWORD_REQUIRED");
+            for wrong in ["wrong", "correct ", "Correct"] {
+                assert_eq!(extract_file(&path, &dest, &with_password(Some(wrong))).unwrap_err().code(), "EXTRACT_PASSWORD_INCORRECT", "{wrong}");
+            }
+            assert!(!dest.join("plugin").join("a.aui2").exists() && !dest.join("b.txt").exists());
+
+            extract_file(&path, &dest, &with_password(Some("correct"))).unwrap();
+            assert_eq!(fs::read(dest.join("plugin").join("a.aui2")).unwrap(), body);
+        }
+    }
+
+    #[test]
+    fn encrypted_zip_reports_missing_and_wrong_password() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("secret.zip");
+        let options = SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "correct");
+        write_zip(&path, &[("plugin/a.aui2", b"alpha"), ("b.txt", b"beta")], options);
+        let dest = tmp.path().join("dest");
+
+        assert_eq!(extract_file(&path, &dest, &with_password(None)).unwrap_err().code(), "EXTRACT_PASSWORD_REQUIRED");
+        assert_eq!(extract_file(&path, &dest, &with_password(Some("wrong"))).unwrap_err().code(), "EXTRACT_PASSWORD_INCORRECT");
+        assert!(!dest.join("b.txt").exists());
+
+        extract_file(&path, &dest, &with_password(Some("correct"))).unwrap();
+        assert_eq!(fs::read(dest.join("b.txt")).unwrap(), b"beta");
+    }
+}
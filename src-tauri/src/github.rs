@@ -0,0 +1,417 @@
+// -----------------------
+// GitHub リリースのアセット解決
+// -----------------------
+//
+// - api.github.com の応答は ETag 付きで設定ディレクトリ（github-cache/）に保存し、If-None-Match で再検証する
+//   （304 はレート制限の回数を消費しない。本文が JSON として読めないキャッシュは無いものとして取り直す）
+// - settings.json の github_token があれば Authorization ヘッダに付ける
+// - レート制限の状態は結果とエラーの両方に含め、最後に観測した値は github_rate_limit で取得できる
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+const API_BASE: &str = "https://api.github.com";
+
+// 最後に観測したレート制限の状態
+static LAST_RATE_LIMIT: Mutex<Option<RateLimit>> = Mutex::new(None);
+
+// エラー型定義
+// - Display の先頭はフロント側で判定に使うエラーコード
+#[derive(thiserror::Error, Debug)]
+pub enum GithubError {
+    #[error("GITHUB_INVALID_PATTERN: {0}")]
+    InvalidPattern(String),
+    #[error("GITHUB_NOT_FOUND: repository {owner}/{repo} not found")]
+    RepoNotFound { owner: String, repo: String },
+    #[error("GITHUB_NO_RELEASE: {owner}/{repo} has no published release")]
+    NoRelease { owner: String, repo: String },
+    #[error("GITHUB_NO_ASSET_MATCHED: no asset matched pattern {pattern} in release {release}")]
+    NoAssetMatched { pattern: String, release: String },
+    #[error("GITHUB_RATE_LIMITED: API rate limit exceeded{}", .0.reset_hint())]
+    RateLimited(RateLimit),
+    #[error("GITHUB_UNAUTHORIZED: the configured token was rejected")]
+    Unauthorized,
+    #[error("GITHUB_HTTP_ERROR:{0} {1}")]
+    Http(u16, String),
+    #[error("GITHUB_INVALID_RESPONSE: {0}")]
+    InvalidResponse(String),
+    #[error("GITHUB_NETWORK_ERROR: {0}")]
+    Net(String),
+}
+
+impl GithubError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            GithubError::InvalidPattern(_) => "GITHUB_INVALID_PATTERN",
+            GithubError::RepoNotFound { .. } => "GITHUB_NOT_FOUND",
+            GithubError::NoRelease { .. } => "GITHUB_NO_RELEASE",
+            GithubError::NoAssetMatched { .. } => "GITHUB_NO_ASSET_MATCHED",
+            GithubError::RateLimited(_) => "GITHUB_RATE_LIMITED",
+            GithubError::Unauthorized => "GITHUB_UNAUTHORIZED",
+            GithubError::Http(..) => "GITHUB_HTTP_ERROR",
+            GithubError::InvalidResponse(_) => "GITHUB_INVALID_RESPONSE",
+            GithubError::Net(_) => "GITHUB_NETWORK_ERROR",
+        }
+    }
+}
+
+impl From<reqwest::Error> for GithubError {
+    fn from(e: reqwest::Error) -> Self {
+        GithubError::Net(e.to_string())
+    }
+}
+
+// フロントには { code, message, rateLimit } の形で返す
+impl Serialize for GithubError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("GithubError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("rateLimit", &if let GithubError::RateLimited(rate) = self { Some(rate) } else { None })?;
+        s.end()
+    }
+}
+
+/// x-ratelimit-* ヘッダの内容
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    // リセット時刻（UNIX 秒）
+    pub reset: Option<i64>,
+    pub authenticated: bool,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &reqwest::header::HeaderMap, authenticated: bool) -> Option<Self> {
+        let num = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).and_then(|s| s.trim().parse::<i64>().ok());
+        let rate = RateLimit {
+            limit: num("x-ratelimit-limit").map(|v| v as u64),
+            remaining: num("x-ratelimit-remaining").map(|v| v as u64),
+            reset: num("x-ratelimit-reset"),
+            authenticated,
+        };
+        (rate.limit.is_some() || rate.remaining.is_some()).then_some(rate)
+    }
+
+    fn reset_hint(&self) -> String {
+        match self.reset.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0)) {
+            Some(at) => format!(" (resets at {})", at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")),
+            None => String::new(),
+        }
+    }
+}
+
+/// 解決結果
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GithubAsset {
+    pub name: String,
+    pub download_url: String,
+    pub size: Option<u64>,
+    pub updated_at: Option<String>,
+    // アセットが属するリリースのタグ
+    pub release: String,
+    pub rate_limit: Option<RateLimit>,
+}
+
+// -----------------------
+// ETag キャッシュ
+// -----------------------
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    body: String,
+}
+
+fn cache_path(dir: &Path, url: &str) -> PathBuf {
+    dir.join(format!("{:016x}.json", xxhash_rust::xxh3::xxh3_64(url.as_bytes())))
+}
+
+/// キャッシュを読む（壊れている場合は None として、If-None-Match を付けずに取り直す）
+fn read_cache(dir: &Path, url: &str) -> Option<(CacheEntry, serde_json::Value)> {
+    let entry: CacheEntry = serde_json::from_str(&fs::read_to_string(cache_path(dir, url)).ok()?).ok()?;
+    let value = serde_json::from_str(&entry.body).ok()?;
+    (entry.url == url).then_some((entry, value))
+}
+
+fn write_cache(dir: &Path, entry: &CacheEntry) {
+    let _ = fs::create_dir_all(dir);
+    if let Ok(text) = serde_json::to_string(entry) {
+        let _ = fs::write(cache_path(dir, &entry.url), text);
+    }
+}
+
+// -----------------------
+// API 呼び出し
+// -----------------------
+
+fn github_token(app: &AppHandle) -> Option<String> {
+    let token = crate::paths::current_settings(app).github_token;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// API 呼び出しの共通設定（キャッシュの場所とトークン）
+struct Api<'a> {
+    client: &'a reqwest::Client,
+    cache_dir: PathBuf,
+    token: Option<String>,
+}
+
+impl<'a> Api<'a> {
+    fn new(app: &AppHandle, client: &'a reqwest::Client) -> Self {
+        Self {
+            client,
+            cache_dir: crate::app_config_dir(app).join("github-cache"),
+            token: github_token(app),
+        }
+    }
+
+    /// API を呼び出して JSON を返す（404 は None）
+    /// - キャッシュがあれば If-None-Match を付け、304 ならキャッシュの本文を使う
+    /// - レート制限に達した場合もキャッシュがあればそれを使う
+    async fn fetch_json(&self, url: &str, rate_out: &mut Option<RateLimit>) -> Result<Option<serde_json::Value>, GithubError> {
+        use reqwest::header::{ACCEPT, AUTHORIZATION, ETAG, IF_NONE_MATCH};
+        use reqwest::StatusCode;
+
+        let cached = read_cache(&self.cache_dir, url);
+        let mut req = self.client.get(url).header(ACCEPT, "application/vnd.github+json").header("X-GitHub-Api-Version", "2022-11-28");
+        if let Some(token) = &self.token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        if let Some(etag) = cached.as_ref().and_then(|(c, _)| c.etag.as_deref()) {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        let res = req.send().await?;
+        let rate = RateLimit::from_headers(res.headers(), self.token.is_some());
+        if let Some(rate) = &rate {
+            if let Ok(mut last) = LAST_RATE_LIMIT.lock() {
+                *last = Some(rate.clone());
+            }
+            *rate_out = Some(rate.clone());
+        }
+
+        let status = res.status();
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some((_, value)) = cached {
+                return Ok(Some(value));
+            }
+        }
+        if status.is_success() {
+            let etag = res.headers().get(ETAG).and_then(|v| v.to_str().ok()).map(str::to_string);
+            let body = res.text().await?;
+            let value = serde_json::from_str(&body).map_err(|e| GithubError::InvalidResponse(format!("invalid JSON from {}: {}", url, e)))?;
+            write_cache(&self.cache_dir, &CacheEntry { url: url.to_string(), etag, body });
+            return Ok(Some(value));
+        }
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(GithubError::Unauthorized);
+        }
+        let body = res.text().await.unwrap_or_default();
+        let limited =
+            status == StatusCode::TOO_MANY_REQUESTS || (status == StatusCode::FORBIDDEN && (rate.as_ref().and_then(|r| r.remaining) == Some(0) || body.contains("rate limit")));
+        if limited {
+            // 古くてもキャッシュがあればそれで解決する
+            if let Some((_, value)) = cached {
+                return Ok(Some(value));
+            }
+            return Err(GithubError::RateLimited(rate.unwrap_or_default()));
+        }
+        let snippet: String = body.chars().take(300).collect();
+        Err(GithubError::Http(status.as_u16(), snippet))
+    }
+}
+
+/// リリースからパターンに一致するアセットを探す
+/// - latest のアセットを優先し、無ければ直近 30 件のリリースから最も新しく更新されたものを選ぶ
+/// - パターン未指定の場合は latest の先頭のアセット
+pub async fn resolve_asset(app: &AppHandle, client: &reqwest::Client, owner: &str, repo: &str, pattern: Option<&str>) -> Result<GithubAsset, GithubError> {
+    let pattern = pattern.map(str::trim).filter(|p| !p.is_empty());
+    let re = match pattern {
+        Some(p) => Some(regex::Regex::new(p).map_err(|e| GithubError::InvalidPattern(format!("{}: {}", p, e)))?),
+        None => None,
+    };
+    let matches = |asset: &serde_json::Value| re.as_ref().map(|re| re.is_match(asset.get("name").and_then(|v| v.as_str()).unwrap_or(""))).unwrap_or(true);
+    let api = Api::new(app, client);
+    let mut rate = None;
+
+    let latest = api.fetch_json(&format!("{}/repos/{}/{}/releases/latest", API_BASE, owner, repo), &mut rate).await?;
+    if let Some(release) = &latest {
+        if let Some(asset) = release.get("assets").and_then(|v| v.as_array()).and_then(|assets| assets.iter().find(|a| matches(a))) {
+            if let Some(found) = to_asset(asset, release, rate.clone()) {
+                return Ok(found);
+            }
+        }
+    }
+
+    let list = api.fetch_json(&format!("{}/repos/{}/{}/releases?per_page=30", API_BASE, owner, repo), &mut rate).await?;
+    let Some(list) = list else {
+        return Err(GithubError::RepoNotFound { owner: owner.to_string(), repo: repo.to_string() });
+    };
+    let releases = list.as_array().cloned().unwrap_or_default();
+    if releases.is_empty() {
+        return Err(GithubError::NoRelease { owner: owner.to_string(), repo: repo.to_string() });
+    }
+    let timestamp = |v: Option<&serde_json::Value>| v.and_then(|v| v.as_str()).and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()).map(|d| d.timestamp());
+    let best = releases
+        .iter()
+        .flat_map(|rel| {
+            let rel_ts = timestamp(rel.get("published_at")).or_else(|| timestamp(rel.get("created_at")));
+            rel.get("assets").and_then(|v| v.as_array()).into_iter().flatten().map(move |a| (a, rel, rel_ts))
+        })
+        .filter(|(a, _, _)| matches(a))
+        .max_by_key(|(a, _, rel_ts)| timestamp(a.get("updated_at")).or_else(|| timestamp(a.get("created_at"))).or(*rel_ts).unwrap_or(0));
+    if let Some(found) = best.and_then(|(asset, release, _)| to_asset(asset, release, rate.clone())) {
+        return Ok(found);
+    }
+
+    let release = latest.as_ref().or(releases.first()).and_then(|r| r.get("tag_name")).and_then(|v| v.as_str()).unwrap_or("(none)").to_string();
+    Err(GithubError::NoAssetMatched { pattern: pattern.unwrap_or("*").to_string(), release })
+}
+
+fn to_asset(asset: &serde_json::Value, release: &serde_json::Value, rate_limit: Option<RateLimit>) -> Option<GithubAsset> {
+    let str_of = |v: &serde_json::Value, key: &str| v.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    Some(GithubAsset {
+        name: str_of(asset, "name").unwrap_or_default(),
+        download_url: str_of(asset, "browser_download_url")?,
+        size: asset.get("size").and_then(|v| v.as_u64()),
+        updated_at: str_of(asset, "updated_at"),
+        release: str_of(release, "tag_name").unwrap_or_default(),
+        rate_limit,
+    })
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// { owner, repo, pattern } からダウンロードするアセットを解決
+#[tauri::command]
+pub async fn resolve_github_asset(
+    app: AppHandle,
+    manager: tauri::State<'_, crate::download::DownloadManager>,
+    owner: String,
+    repo: String,
+    pattern: Option<String>,
+) -> Result<GithubAsset, GithubError> {
+    let result = resolve_asset(&app, manager.client(), owner.trim(), repo.trim(), pattern.as_deref()).await;
+    if let Err(e) = &result {
+        crate::log_error(&app, &format!("resolve_github_asset failed ({}/{}): {}", owner, repo, e));
+    }
+    result
+}
+
+/// 最後に観測したレート制限の状態（まだ API を呼んでいなければ null）
+#[tauri::command]
+pub fn github_rate_limit() -> Option<RateLimit> {
+    LAST_RATE_LIMIT.lock().ok().and_then(|last| last.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const ETAG: &str = "\"abc\"";
+
+    /// ETag 付きで body を返し、If-None-Match が一致すれば 304 を返すサーバー
+    /// - 戻り値は (URL, 200 を返した回数, 受け取った If-None-Match)
+    async fn api_server(body: &'static str) -> (String, Arc<AtomicUsize>, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/repos/o/r/releases/latest", listener.local_addr().unwrap());
+        let full = Arc::new(AtomicUsize::new(0));
+        let seen: Arc<Mutex<Vec<Option<String>>>> = Arc::default();
+        let (full_count, seen_log) = (full.clone(), seen.clone());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !raw.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let text = String::from_utf8_lossy(&raw).into_owned();
+                let if_none_match = text.lines().find_map(|l| l.split_once(':').filter(|(k, _)| k.eq_ignore_ascii_case("if-none-match")).map(|(_, v)| v.trim().to_string()));
+                let response = if if_none_match.as_deref() == Some(ETAG) {
+                    "HTTP/1.1 304 Not Modified\r\nx-ratelimit-remaining: 59\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    full_count.fetch_add(1, Ordering::SeqCst);
+                    format!("HTTP/1.1 200 OK\r\nETag: {ETAG}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+                };
+                seen_log.lock().unwrap().push(if_none_match);
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        (url, full, seen)
+    }
+
+    fn api<'a>(client: &'a reqwest::Client, dir: &Path) -> Api<'a> {
+        Api { client, cache_dir: dir.to_path_buf(), token: None }
+    }
+
+    #[tokio::test]
+    async fn not_modified_is_served_from_cache() {
+        let (url, full, seen) = api_server(r#"{"tag_name":"v1.0"}"#).await;
+        let dir = tempfile::tempdir().unwrap();
+        let client = reqwest::Client::new();
+        let api = api(&client, dir.path());
+        let mut rate = None;
+
+        let first = api.fetch_json(&url, &mut rate).await.unwrap().unwrap();
+        let second = api.fetch_json(&url, &mut rate).await.unwrap().unwrap();
+        assert_eq!(first, second);
+        assert_eq!(second["tag_name"], "v1.0");
+        assert_eq!(full.load(Ordering::SeqCst), 1);
+        assert_eq!(*seen.lock().unwrap(), vec![None, Some(ETAG.to_string())]);
+        assert_eq!(rate.and_then(|r| r.remaining), Some(59));
+    }
+
+    #[tokio::test]
+    async fn corrupt_cache_is_refetched_without_etag() {
+        let (url, full, seen) = api_server(r#"{"tag_name":"v2.0"}"#).await;
+        let dir = tempfile::tempdir().unwrap();
+        let client = reqwest::Client::new();
+        let api = api(&client, dir.path());
+
+        // 本文が JSON でないキャッシュと、キャッシュ自体が壊れている場合
+        for corrupt in [
+            serde_json::to_string(&CacheEntry { url: url.clone(), etag: Some(ETAG.to_string()), body: "{broken".to_string() }).unwrap(),
+            "not json".to_string(),
+        ] {
+            fs::write(cache_path(dir.path(), &url), corrupt).unwrap();
+            let value = api.fetch_json(&url, &mut None).await.unwrap().unwrap();
+            assert_eq!(value["tag_name"], "v2.0");
+            assert!(read_cache(dir.path(), &url).is_some());
+        }
+        assert_eq!(full.load(Ordering::SeqCst), 2);
+        assert_eq!(*seen.lock().unwrap(), vec![None, None]);
+    }
+
+    #[tokio::test]
+    async fn invalid_json_is_not_an_http_error() {
+        let (url, _, _) = api_server("<html>maintenance</html>").await;
+        let dir = tempfile::tempdir().unwrap();
+        let client = reqwest::Client::new();
+        let err = api(&client, dir.path()).fetch_json(&url, &mut None).await.unwrap_err();
+        assert_eq!(err.code(), "GITHUB_INVALID_RESPONSE");
+        assert!(read_cache(dir.path(), &url).is_none());
+    }
+}
@@ -0,0 +1,1151 @@
+// -----------------------
+// インストーラの実行（installer.install / installer.uninstall）
+// -----------------------
+//
+// - カタログのステップ一覧を action ごとの enum として受け取り、最初から最後まで Rust 側で実行する
+//   知らない action を含む install は何も実行せずに失敗する（uninstall は従来どおり読み飛ばす）
+// - 進捗は install:progress の 1 本にまとめて送る（ダウンロード・展開の進捗はステップ内の割合に換算する）
+// - ダウンロードと展開は taskId で TaskRegistry に登録するので、cancel_task で中断できる
+// - 暗号化されたアーカイブでパスワードが必要な場合は EXTRACT_PASSWORD_* のまま失敗する
+//   フロントは入力されたパスワードと downloadPath（ダウンロード済みのファイル）を付けて呼び直す
+// - 作業フォルダは設定フォルダの installer-tmp/<id>-<version>/。終了時に消すが、
+//   ダウンロードの失敗（続きから再開するため）とパスワードの入力待ちのときは残す
+// - install は 1 つのトランザクションとして journal に記録し、失敗したら作業フォルダの外で作成・上書きしたファイルを元に戻す
+//   最後のステップの後に確定を記録し、落ちて残ったジャーナルは起動時に recover_interrupted で処理する
+// - install_plan は依存関係の plan のパッケージを順に実行し、全体を 1 つのトランザクションとして記録する
+//   どれかが失敗したら先に終えたパッケージも巻き戻し、すべて成功したときだけインストール済みとして記録する
+// - 確定時に置いたファイルを manifest に記録し、uninstall はステップの後に manifest のファイルを消す
+//   （カタログの delete ステップが無い・足りない場合もインストールしたファイルが残らない）
+
+use base64::Engine;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, EventId, Listener};
+use thiserror::Error;
+
+use crate::copy::{self, CopyError, OverwritePolicy};
+use crate::download::{self, DownloadError, DownloadManager, DownloadTask, InstallerSource};
+use crate::extract::{self, ExtractArgs, ExtractError, ExtractOptions};
+use crate::journal::{self, Journal};
+use crate::manifest::{self, Manifest, RemovalReport};
+use crate::state;
+use crate::tasks::TaskRegistry;
+
+// {nested} / {nested1} / {nested2} …（数字なしは 1 番目）
+static NESTED_MACRO: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{nested(\d*)\}").unwrap());
+
+/// インストール・アンインストール時のエラー（Display の先頭はフロントで判定するためのコード）
+/// - ダウンロード・展開・コピーのエラーはそれぞれのコードをそのまま使う
+#[derive(Debug, Error)]
+pub enum InstallError {
+    #[error("INSTALL_INVALID_STEP: {0}")]
+    InvalidStep(String),
+    #[error(transparent)]
+    Download(#[from] DownloadError),
+    #[error(transparent)]
+    Extract(#[from] ExtractError),
+    #[error(transparent)]
+    Copy(#[from] CopyError),
+    #[error("INSTALL_NOTHING_COPIED: copy matched 0 files (from={from} to={to})")]
+    NothingCopied { from: String, to: String },
+    #[error("INSTALL_RUN_FAILED: {0}")]
+    Run(String),
+    #[error("INSTALL_IO_ERROR: {0}")]
+    Io(String),
+}
+
+impl InstallError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            InstallError::InvalidStep(_) => "INSTALL_INVALID_STEP",
+            InstallError::Download(e) => e.code(),
+            InstallError::Extract(e) => e.code(),
+            InstallError::Copy(e) => e.code(),
+            InstallError::NothingCopied { .. } => "INSTALL_NOTHING_COPIED",
+            InstallError::Run(_) => "INSTALL_RUN_FAILED",
+            InstallError::Io(_) => "INSTALL_IO_ERROR",
+        }
+    }
+
+    /// パスワードを入れ直せば続けられる失敗か
+    fn needs_password(&self) -> bool {
+        matches!(self, InstallError::Extract(ExtractError::PasswordRequired | ExtractError::PasswordIncorrect))
+    }
+}
+
+impl From<std::io::Error> for InstallError {
+    fn from(e: std::io::Error) -> Self {
+        InstallError::Io(e.to_string())
+    }
+}
+
+fn join_error(e: impl std::fmt::Display) -> InstallError {
+    InstallError::Io(format!("task join error: {e}"))
+}
+
+/// 失敗したステップの情報を付けたエラー（フロントへ返す形）
+#[derive(Debug, Error)]
+#[error("step {}/{} action={} failed: {}", .step + 1, .total, .action, .error)]
+pub struct StepFailure {
+    // 失敗したパッケージ（install_plan で区別する）
+    pub package_id: String,
+    pub step: usize,
+    pub total: usize,
+    pub action: &'static str,
+    pub error: InstallError,
+    // ダウンロード済みのファイル（呼び直すときに downloadPath に渡せば再ダウンロードしない）
+    pub download_path: Option<String>,
+}
+
+impl Serialize for StepFailure {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("StepFailure", 7)?;
+        s.serialize_field("code", self.error.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("packageId", &self.package_id)?;
+        s.serialize_field("step", &self.step)?;
+        s.serialize_field("action", self.action)?;
+        s.serialize_field("downloadPath", &self.download_path)?;
+        // 展開を拒否したエントリ・内容の異なる既存のファイルなど、元のエラーの詳細
+        match &self.error {
+            InstallError::Extract(e) => s.serialize_field("cause", e)?,
+            InstallError::Copy(e) => s.serialize_field("cause", e)?,
+            _ => s.serialize_field("cause", &None::<()>)?,
+        }
+        s.end()
+    }
+}
+
+// -----------------------
+// ステップの定義
+// -----------------------
+
+/// installer.install の 1 ステップ
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum InstallStep {
+    // installer.source をダウンロードする（保存先は {download}）
+    Download,
+    Extract(ExtractStep),
+    ExtractSfx(ExtractSfxStep),
+    Copy(CopyStep),
+    Run(RunStep),
+    RunAuoSetup(PathStep),
+}
+
+/// installer.uninstall の 1 ステップ
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum UninstallStep {
+    Delete(PathStep),
+    Run(RunStep),
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExtractStep {
+    // 省略時は {download}
+    pub from: Option<String>,
+    // 省略時は {tmp}
+    pub to: Option<String>,
+    pub encoding: Option<String>,
+    #[serde(default, deserialize_with = "pattern_list")]
+    pub include: Vec<String>,
+    #[serde(default, deserialize_with = "pattern_list")]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub strip: usize,
+    #[serde(default)]
+    pub nested: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExtractSfxStep {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CopyStep {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub overwrite: OverwritePolicy,
+    // true ならコピーするものが無くても失敗しない（環境によって存在しないファイルなど）
+    #[serde(default)]
+    pub optional: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RunStep {
+    pub path: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub elevate: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PathStep {
+    pub path: String,
+}
+
+/// include / exclude は 1 つだけなら文字列でも書ける
+fn pattern_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    let list = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(list) => list,
+    };
+    Ok(list.into_iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+}
+
+impl InstallStep {
+    pub fn action(&self) -> &'static str {
+        match self {
+            InstallStep::Download => "download",
+            InstallStep::Extract(_) => "extract",
+            InstallStep::ExtractSfx(_) => "extract_sfx",
+            InstallStep::Copy(_) => "copy",
+            InstallStep::Run(_) => "run",
+            InstallStep::RunAuoSetup(_) => "run_auo_setup",
+        }
+    }
+}
+
+impl UninstallStep {
+    pub fn action(&self) -> &'static str {
+        match self {
+            UninstallStep::Delete(_) => "delete",
+            UninstallStep::Run(_) => "run",
+            UninstallStep::Unsupported => "unsupported",
+        }
+    }
+}
+
+/// install_package / uninstall_package に渡すパッケージ（カタログの項目から必要な部分だけ）
+#[derive(Deserialize, Debug)]
+pub struct Package<S> {
+    pub id: String,
+    // 記録するバージョン（カタログの latest-version）
+    #[serde(default)]
+    pub version: String,
+    pub source: Option<InstallerSource>,
+    pub steps: Vec<S>,
+}
+
+/// 実行時の指定
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallOptions {
+    // 進捗イベントとキャンセルに使う ID（省略時は自動で決める）
+    pub task_id: Option<String>,
+    // 事前にダウンロード済みのファイル（指定時は download ステップでダウンロードしない）
+    pub download_path: Option<String>,
+    // 暗号化されたアーカイブのパスワード
+    pub password: Option<String>,
+}
+
+/// install_plan の実行時の指定（パッケージ ID ごとに持つもの以外は InstallOptions と同じ）
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlanOptions {
+    pub task_id: Option<String>,
+    // パッケージ ID → 事前にダウンロード済みのファイル
+    pub download_paths: HashMap<String, String>,
+    // パッケージ ID → 暗号化されたアーカイブのパスワード
+    pub passwords: HashMap<String, String>,
+}
+
+/// install_package / install_plan の結果
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallReport {
+    // copy ステップでコピーしたファイル（コピー先のパス）
+    pub copied: Vec<String>,
+}
+
+// -----------------------
+// 実行中の状態
+// -----------------------
+
+/// ステップ間で受け渡す値（マクロの展開に使う）
+struct InstallContext {
+    tmp_dir: PathBuf,
+    download_path: Option<PathBuf>,
+    password: Option<String>,
+    // 直前の extract ステップで展開した内側のアーカイブのフォルダ
+    nested_dirs: Vec<String>,
+    copied: Vec<String>,
+}
+
+impl InstallContext {
+    /// {tmp} {download} {nested} とフォルダ系のマクロ（{appDir} など）を展開する
+    fn expand(&self, raw: &str) -> String {
+        let download = self.download_path.as_deref().map(|p| p.to_string_lossy()).unwrap_or_default();
+        let s = crate::expand_macros(raw).replace("{tmp}", &self.tmp_dir.to_string_lossy()).replace("{download}", &download);
+        NESTED_MACRO
+            .replace_all(&s, |caps: &regex::Captures| {
+                let n = caps[1].parse::<usize>().unwrap_or(1);
+                n.checked_sub(1).and_then(|i| self.nested_dirs.get(i)).cloned().unwrap_or_default()
+            })
+            .into_owned()
+    }
+
+    /// from が省略されたときはダウンロードしたファイルを使う
+    fn archive_path(&self, from: Option<&str>) -> Result<PathBuf, InstallError> {
+        match from {
+            Some(from) => Ok(PathBuf::from(self.expand(from))),
+            None => self.download_path.clone().ok_or_else(|| InstallError::InvalidStep("no archive to extract (from is empty and nothing was downloaded)".to_string())),
+        }
+    }
+}
+
+/// 作業フォルダを置くフォルダ（ジャーナルに記録しない）
+fn tmp_root(app: &AppHandle) -> PathBuf {
+    crate::app_config_dir(app).join("installer-tmp")
+}
+
+/// 作業フォルダ（設定フォルダの installer-tmp/<id>-<version>）
+fn tmp_dir_of(app: &AppHandle, id: &str, version: &str) -> PathBuf {
+    let version = if version.is_empty() { "latest" } else { version };
+    let key: String = format!("{id}-{version}").chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' }).collect();
+    tmp_root(app).join(key)
+}
+
+/// 実行中のインストールのジャーナルを置くフォルダ
+fn transactions_dir(app: &AppHandle) -> PathBuf {
+    crate::app_config_dir(app).join("transactions")
+}
+
+// -----------------------
+// トランザクション
+// -----------------------
+
+/// install のトランザクション（install_plan では plan 全体で 1 つ）
+/// - 最初のステップの前にジャーナルを開始し、パッケージが変わるたびに区切りを記録する
+/// - 展開・コピーの処理にはジャーナルの参照を渡す。確定後の後始末と巻き戻しは、参照がすべて返されてから行う
+struct Transaction {
+    dir: PathBuf,
+    // 記録しないフォルダ（作業フォルダ）
+    exclude: PathBuf,
+    journal: Option<Arc<Journal>>,
+    // ジャーナルに記録中のパッケージ
+    package: Option<String>,
+}
+
+impl Transaction {
+    fn new(dir: PathBuf, exclude: PathBuf) -> Self {
+        Self { dir, exclude, journal: None, package: None }
+    }
+
+    /// まだならジャーナルを開始し、パッケージが変わったら以降の記録をそのパッケージのものとする
+    fn prepare(&mut self, id: &str, version: &str) -> std::io::Result<()> {
+        match &self.journal {
+            None => {
+                let journal = Journal::begin(&self.dir, id, version, vec![self.exclude.clone()])?;
+                self.journal = Some(Arc::new(journal));
+            }
+            Some(journal) if self.package.as_deref() != Some(id) => journal.begin_package(id, version)?,
+            Some(_) => {}
+        }
+        self.package = Some(id.to_string());
+        Ok(())
+    }
+
+    /// 展開・コピーの処理に渡す参照
+    fn handle(&self) -> Option<Arc<Journal>> {
+        self.journal.clone()
+    }
+
+    /// 置いたファイルの manifest をパッケージごとに作ってから確定を記録する（これ以降に失敗・中断しても巻き戻さない）
+    /// - previous はパッケージ ID ごとの前回の manifest
+    async fn commit(&self, previous: impl Fn(&str) -> Option<Manifest> + Send + 'static) -> Result<Vec<Manifest>, InstallError> {
+        let Some(journal) = self.handle() else {
+            return Ok(Vec::new());
+        };
+        tauri::async_runtime::spawn_blocking(move || -> Result<Vec<Manifest>, InstallError> {
+            let mut manifests = Vec::new();
+            for section in journal.sections()? {
+                manifests.push(manifest::build(&section.package, &section.version, &section.placed, previous(&section.package))?);
+            }
+            journal.commit().map_err(|e| InstallError::Io(format!("failed to commit install journal: {e}")))?;
+            Ok(manifests)
+        })
+        .await
+        .map_err(join_error)?
+    }
+
+    /// 記録した変更を新しいものから順に元に戻し、ジャーナルを消す（ジャーナルが無ければ false）
+    async fn rollback(&mut self) -> std::io::Result<bool> {
+        let Some(journal) = self.take().await else {
+            return Ok(false);
+        };
+        tauri::async_runtime::spawn_blocking(move || journal.rollback()).await.map_err(std::io::Error::other)??;
+        Ok(true)
+    }
+
+    /// 確定後の後始末（上書き前のファイルとジャーナルを消す）
+    async fn finish(&mut self) -> std::io::Result<()> {
+        let Some(journal) = self.take().await else {
+            return Ok(());
+        };
+        tauri::async_runtime::spawn_blocking(move || journal.finish()).await.map_err(std::io::Error::other)?
+    }
+
+    /// ジャーナルを取り出す（展開・コピーの処理がまだ参照を持っていれば、返されるまで待つ）
+    /// - 待たずに後回しにすると、未確定のジャーナルが次回の起動まで残り、その間に成功したインストールまで巻き戻されてしまう
+    async fn take(&mut self) -> Option<Journal> {
+        self.package = None;
+        let mut journal = self.journal.take()?;
+        loop {
+            match Arc::try_unwrap(journal) {
+                Ok(journal) => return Some(journal),
+                Err(shared) => journal = shared,
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+// -----------------------
+// 進捗（install:progress）
+// -----------------------
+
+/// install:progress の送信
+/// - ratio は完了したステップ数 + 実行中のステップ内の割合を総ステップ数で割ったもの
+#[derive(Clone)]
+struct Progress {
+    app: AppHandle,
+    task_id: String,
+    // install_plan ではすべてのパッケージのステップ数の合計
+    total_steps: usize,
+    // 実行中のパッケージ
+    package_id: String,
+}
+
+impl Progress {
+    fn emit(&self, units: f64, step: Option<(usize, &str)>, phase: &str) {
+        let ratio = if self.total_steps == 0 {
+            if phase == "done" {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            (units / self.total_steps as f64).clamp(0.0, 1.0)
+        };
+        let _ = self.app.emit(
+            "install:progress",
+            serde_json::json!({
+                "taskId": self.task_id,
+                "packageId": self.package_id,
+                "ratio": ratio,
+                "step": step.map(|(_, action)| action),
+                "stepIndex": step.map(|(index, _)| index),
+                "totalSteps": self.total_steps,
+                "phase": phase,
+            }),
+        );
+    }
+
+    /// 同じ taskId の download:progress / extract:progress をステップ内の進捗として送り直す（戻り値を破棄すると止まる）
+    fn forward(&self, event: &str, index: usize, action: &'static str) -> Forwarding {
+        let this = self.clone();
+        // サイズが分からないダウンロードは通知のたびに少しずつ進める
+        let unknown = Mutex::new(0.0_f64);
+        let id = self.app.listen(event, move |event| {
+            let Ok(payload) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+                return;
+            };
+            if payload["taskId"].as_str() != Some(this.task_id.as_str()) {
+                return;
+            }
+            let ratio = |done: &str, total: &str| {
+                let total = payload[total].as_f64().unwrap_or(0.0);
+                (total > 0.0).then(|| payload[done].as_f64().unwrap_or(0.0) / total)
+            };
+            let fraction = match ratio("read", "total").or_else(|| ratio("bytesWritten", "bytesTotal")).or_else(|| ratio("entriesDone", "entriesTotal")) {
+                Some(fraction) => fraction,
+                None => {
+                    let Ok(mut unknown) = unknown.lock() else {
+                        return;
+                    };
+                    *unknown = (*unknown + 0.05).min(0.99);
+                    *unknown
+                }
+            };
+            this.emit(index as f64 + fraction.clamp(0.0, 0.99), Some((index, action)), "running");
+        });
+        Forwarding { app: self.app.clone(), id }
+    }
+}
+
+/// 転送中のイベント（Drop で解除）
+struct Forwarding {
+    app: AppHandle,
+    id: EventId,
+}
+
+impl Drop for Forwarding {
+    fn drop(&mut self) {
+        self.app.unlisten(self.id);
+    }
+}
+
+// -----------------------
+// ステップの実行
+// -----------------------
+
+/// 共有の状態とパッケージ単位の値
+struct Runner<'a> {
+    app: &'a AppHandle,
+    manager: &'a DownloadManager,
+    tasks: &'a TaskRegistry,
+    progress: Progress,
+    label: String,
+    transaction: Transaction,
+}
+
+impl<'a> Runner<'a> {
+    /// install_package / install_plan 用（ラベルと進捗のパッケージは実行するパッケージごとに変わる）
+    fn for_install(app: &'a AppHandle, manager: &'a DownloadManager, tasks: &'a TaskRegistry, task_id: String) -> Self {
+        Self {
+            app,
+            manager,
+            tasks,
+            progress: Progress { app: app.clone(), task_id, total_steps: 0, package_id: String::new() },
+            label: String::new(),
+            transaction: Transaction::new(transactions_dir(app), tmp_root(app)),
+        }
+    }
+
+    /// 作業フォルダを作り、まだならジャーナルを開始する（作業フォルダの中は記録しない）
+    fn prepare(&mut self, id: &str, version: &str, tmp_dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(tmp_dir)?;
+        self.transaction.prepare(id, version)
+    }
+
+    /// 置いたファイルの manifest をパッケージごとに作ってから確定を記録する
+    async fn commit(&self) -> Result<Vec<Manifest>, InstallError> {
+        let app = self.app.clone();
+        self.transaction.commit(move |id| manifest::load(&app, id)).await
+    }
+
+    /// 記録した変更を元に戻す
+    async fn rollback(&mut self) {
+        match self.transaction.rollback().await {
+            Ok(true) => crate::log_info(self.app, &format!("[{}] rolled back installed files", self.label)),
+            Ok(false) => {}
+            Err(e) => crate::log_error(self.app, &format!("[{}] failed to roll back: {}", self.label, e)),
+        }
+    }
+
+    /// 確定後の後始末
+    async fn finish(&mut self) {
+        if let Err(e) = self.transaction.finish().await {
+            crate::log_error(self.app, &format!("[{}] failed to clean up journal: {}", self.label, e));
+        }
+    }
+
+    async fn install_step(
+        &self,
+        step: &InstallStep,
+        index: usize,
+        source: Option<&InstallerSource>,
+        options: &InstallOptions,
+        ctx: &mut InstallContext,
+    ) -> Result<(), InstallError> {
+        let action = step.action();
+        match step {
+            InstallStep::Download => {
+                if let Some(path) = options.download_path.as_deref().filter(|p| !p.is_empty()) {
+                    crate::log_info(self.app, &format!("[{}] using prefetched download {}", self.label, path));
+                    ctx.download_path = Some(PathBuf::from(path));
+                    return Ok(());
+                }
+                let source = source.ok_or_else(|| InstallError::InvalidStep("download source is not specified".to_string()))?;
+                let (primary, mirrors) = source.to_sources(None)?;
+                crate::log_info(self.app, &format!("[{}] downloading from source={} to {}", self.label, primary.describe(), ctx.tmp_dir.display()));
+                let task = DownloadTask {
+                    task_id: self.progress.task_id.clone(),
+                    source: primary,
+                    mirrors,
+                    dest_dir: ctx.tmp_dir.clone(),
+                    file_name: None,
+                    expected: source.expected.clone(),
+                };
+                let _forwarding = self.progress.forward("download:progress", index, action);
+                ctx.download_path = Some(download::run_task(self.app, self.manager, &task).await?);
+            }
+            InstallStep::Extract(step) => {
+                let from = ctx.archive_path(step.from.as_deref())?;
+                let to = PathBuf::from(ctx.expand(step.to.as_deref().unwrap_or("{tmp}")));
+                crate::log_info(self.app, &format!("[{}] extracting from {} to {}", self.label, from.display(), to.display()));
+                let args = ExtractArgs {
+                    encoding: step.encoding.clone(),
+                    password: ctx.password.clone(),
+                    include: step.include.clone(),
+                    exclude: step.exclude.clone(),
+                    strip_components: step.strip,
+                    nested_depth: step.nested,
+                };
+                let extract_options = args.into_options()?;
+                let _forwarding = self.progress.forward("extract:progress", index, action);
+                let summary = extract::run_extract(self.app, self.tasks, Some(self.progress.task_id.clone()), self.transaction.handle(), move |monitor| {
+                    extract::extract_archive_nested(&from, &to, &extract_options, monitor)
+                })
+                .await?;
+                crate::log_info(self.app, &format!("[{}] extracted {:?} archive", self.label, summary.kind));
+                for nested in &summary.nested {
+                    crate::log_info(self.app, &format!("[{}] extracted nested {:?} archive {} to {}", self.label, nested.kind, nested.archive, nested.dir));
+                }
+                ctx.nested_dirs = summary.nested.into_iter().map(|n| n.dir).collect();
+            }
+            InstallStep::ExtractSfx(step) => {
+                let from = ctx.archive_path(step.from.as_deref())?;
+                let to = PathBuf::from(ctx.expand(step.to.as_deref().unwrap_or("{tmp}")));
+                crate::log_info(self.app, &format!("[{}] extracting SFX from {} to {}", self.label, from.display(), to.display()));
+                let extract_options = ExtractOptions::from_args(None, ctx.password.clone())?;
+                let _forwarding = self.progress.forward("extract:progress", index, action);
+                extract::run_extract(self.app, self.tasks, Some(self.progress.task_id.clone()), self.transaction.handle(), move |monitor| {
+                    extract::extract_7z_sfx_to(&from, &to, &extract_options, monitor)
+                })
+                .await?;
+            }
+            InstallStep::Copy(step) => {
+                let from = ctx.expand(&step.from);
+                let to = ctx.expand(&step.to);
+                let backup_root = crate::app_config_dir(self.app).join("backups");
+                let (policy, optional) = (step.overwrite, step.optional);
+                let journal = self.transaction.handle();
+                let report = {
+                    let (from, to) = (from.clone(), to.clone());
+                    tauri::async_runtime::spawn_blocking(move || copy::copy_item(&from, Path::new(&to), policy, optional, &backup_root, journal)).await.map_err(join_error)??
+                };
+                let count = report.copied.len() + report.skipped.len();
+                crate::log_info(self.app, &format!("[{}] copy matched {} files (from={} to={})", self.label, count, from, to));
+                if !report.skipped.is_empty() {
+                    crate::log_info(self.app, &format!("[{}] kept existing files:\n{}", self.label, report.skipped.join("\n")));
+                }
+                for backup in &report.backups {
+                    crate::log_info(self.app, &format!("[{}] backed up {} to {}", self.label, backup.original, backup.backup));
+                }
+                if count == 0 && !optional {
+                    return Err(InstallError::NothingCopied { from, to });
+                }
+                ctx.copied.extend(report.copied);
+            }
+            InstallStep::Run(step) => self.run(step, ctx).await?,
+            // aviutl2 本体向けの auo_setup（ポータブル版かどうかで引数が変わる）
+            InstallStep::RunAuoSetup(step) => {
+                crate::run_auo_setup(self.app.clone(), ctx.expand(&step.path)).await.map_err(InstallError::Run)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn uninstall_step(&self, step: &UninstallStep, ctx: &InstallContext) -> Result<(), InstallError> {
+        match step {
+            UninstallStep::Delete(step) => {
+                let path = PathBuf::from(ctx.expand(&step.path));
+                let removed = {
+                    let path = path.clone();
+                    tauri::async_runtime::spawn_blocking(move || delete_path(&path)).await.map_err(join_error)??
+                };
+                let state = if removed { "ok" } else { "skip (not found)" };
+                crate::log_info(self.app, &format!("[{}] delete {} path=\"{}\"", self.label, state, path.display()));
+            }
+            UninstallStep::Run(step) => self.run(step, ctx).await?,
+            UninstallStep::Unsupported => crate::log_info(self.app, &format!("[{}] skip unsupported action", self.label)),
+        }
+        Ok(())
+    }
+
+    async fn run(&self, step: &RunStep, ctx: &InstallContext) -> Result<(), InstallError> {
+        let path = ctx.expand(&step.path);
+        let args: Vec<String> = step.args.iter().map(|a| ctx.expand(a)).collect();
+        crate::log_info(self.app, &format!("[{}] running {} args={:?} elevate={}", self.label, path, args, step.elevate));
+        run_hidden(&path, &args, step.elevate).await
+    }
+}
+
+/// ファイル・フォルダを削除する（存在しなければ false）
+fn delete_path(path: &Path) -> Result<bool, InstallError> {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return Ok(false);
+    };
+    if meta.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(true)
+}
+
+// PowerShell の単一引用符文字列用のエスケープ
+fn ps_escape(s: &str) -> String {
+    s.replace('\'', "''")
+}
+
+/// 実行ファイルを非表示のウィンドウで起動して終了を待つ（elevate なら UAC で管理者として実行する）
+/// - Start-Process を使うのは -Verb RunAs で昇格と終了コードの取得を同じ形で行えるため
+async fn run_hidden(exe: &str, args: &[String], elevate: bool) -> Result<(), InstallError> {
+    let arg_list = args.iter().map(|a| format!("'{}'", ps_escape(a))).collect::<Vec<_>>().join(", ");
+    let arg_clause = if args.is_empty() { String::new() } else { format!(" -ArgumentList @({})", arg_list) };
+    let verb = if elevate { " -Verb RunAs" } else { "" };
+    let script = [
+        "$ErrorActionPreference='Stop'".to_string(),
+        "[Console]::OutputEncoding=[System.Text.UTF8Encoding]::new()".to_string(),
+        format!("$p = Start-Process -FilePath '{}'{}{} -WindowStyle Hidden -Wait -PassThru", ps_escape(exe), arg_clause, verb),
+        "exit ($p.ExitCode)".to_string(),
+    ]
+    .join("\n");
+    // -EncodedCommand は UTF-16LE を Base64 にしたもの（引数の引用符の解釈に左右されない）
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    let encoded = base64::engine::general_purpose::STANDARD.encode(utf16);
+    let mut cmd = tokio::process::Command::new("powershell");
+    cmd.args([
+        "-ExecutionPolicy",
+        "Bypass",
+        "-NoLogo",
+        "-NoProfile",
+        "-NonInteractive",
+        "-EncodedCommand",
+        &encoded,
+    ]);
+    #[cfg(windows)]
+    {
+        // CREATE_NO_WINDOW（PowerShell 自身のコンソールを出さない）
+        cmd.creation_flags(0x0800_0000);
+    }
+    let output = cmd.output().await.map_err(|e| InstallError::Run(format!("failed to start powershell: {e}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(InstallError::Run(format!(
+            "{} exited with {} (args={:?}, elevate={}): {}",
+            exe,
+            output.status.code().unwrap_or(-1),
+            args,
+            elevate,
+            stderr.chars().take(500).collect::<String>()
+        )));
+    }
+    Ok(())
+}
+
+/// 作業フォルダを消す（開発ビルドでは確認用に残す）
+fn cleanup_tmp_dir(tmp_dir: &Path) {
+    if !cfg!(debug_assertions) {
+        let _ = fs::remove_dir_all(tmp_dir);
+    }
+}
+
+/// インストール済みとして manifest と一緒に記録する（記録の失敗でインストール自体は失敗にしない。バージョンは次回の検出で補われる）
+fn record_install(app: &AppHandle, label: &str, id: &str, version: &str, source: Option<String>, manifest: Option<Manifest>) {
+    let manifest = manifest.filter(|m| !m.files.is_empty());
+    let files = manifest.as_ref().map_or(0, |m| m.files.len());
+    match state::record_install(app, id, version, source, manifest) {
+        Ok(()) => crate::log_info(app, &format!("[{}] recorded version={} with {} files in manifest", label, version, files)),
+        Err(e) => crate::log_error(app, &format!("[{}] failed to record installed state: {}", label, e)),
+    }
+}
+
+/// manifest のファイルを消す（消せなかったファイルは報告するだけで、記録はアンインストールと一緒に消える）
+async fn remove_by_manifest(app: &AppHandle, label: &str, id: &str) -> RemovalReport {
+    let Some(manifest) = manifest::load(app, id) else {
+        return RemovalReport::default();
+    };
+    let others = manifest::load_all(app);
+    let result = tauri::async_runtime::spawn_blocking(move || manifest::remove_files(&manifest, &others)).await;
+    let report = match result {
+        Ok(v) => v,
+        Err(e) => {
+            crate::log_error(app, &format!("[{}] failed to remove files by manifest: {}", label, e));
+            return RemovalReport::default();
+        }
+    };
+    crate::log_info(app, &format!("[{}] removed {} files by manifest ({} already missing)", label, report.removed.len(), report.missing.len()));
+    for (files, reason) in [
+        (&report.modified, "modified after install"),
+        (&report.shared, "used by other packages"),
+        (&report.replaced, "existed before install"),
+        (&report.failed, "failed to delete"),
+    ] {
+        if !files.is_empty() {
+            crate::log_info(app, &format!("[{}] kept files ({}):\n{}", label, reason, files.join("\n")));
+        }
+    }
+    report
+}
+
+/// packages を順に実行し、最後のステップの後にまとめて確定してインストール済みとして記録する
+/// - どれかのステップが失敗したら、それまでのパッケージも含めて巻き戻す
+async fn install_packages(runner: &mut Runner<'_>, packages: &[(Package<InstallStep>, InstallOptions)]) -> Result<InstallReport, StepFailure> {
+    let app = runner.app;
+    let total: usize = packages.iter().map(|(package, _)| package.steps.len()).sum();
+    runner.progress.total_steps = total;
+    let mut contexts: Vec<InstallContext> = Vec::with_capacity(packages.len());
+    let mut manifests = Vec::new();
+    let mut done = 0;
+    runner.progress.emit(0.0, None, "init");
+    for (package, options) in packages {
+        runner.label = format!("installer {}", package.id);
+        runner.progress.package_id = package.id.clone();
+        let mut ctx = InstallContext {
+            tmp_dir: tmp_dir_of(app, &package.id, &package.version),
+            download_path: None,
+            password: options.password.clone().filter(|p| !p.is_empty()),
+            nested_dirs: Vec::new(),
+            copied: Vec::new(),
+        };
+        crate::log_info(app, &format!("[{}] start version={} steps={}", runner.label, package.version, package.steps.len()));
+        for (index, step) in package.steps.iter().enumerate() {
+            let action = step.action();
+            // plan 全体でのステップの位置
+            let overall = done + index;
+            runner.progress.emit(overall as f64, Some((overall, action)), "running");
+            let mut result = match runner.prepare(&package.id, &package.version, &ctx.tmp_dir) {
+                Ok(()) => runner.install_step(step, overall, package.source.as_ref(), options, &mut ctx).await,
+                Err(e) => Err(e.into()),
+            };
+            if result.is_ok() && overall + 1 == total {
+                result = runner.commit().await.map(|m| manifests = m);
+            }
+            if let Err(error) = result {
+                runner.progress.emit(overall as f64, Some((overall, action)), "error");
+                runner.rollback().await;
+                let failure = StepFailure {
+                    package_id: package.id.clone(),
+                    step: index,
+                    total: package.steps.len(),
+                    action,
+                    error,
+                    download_path: ctx.download_path.as_ref().map(|p| p.to_string_lossy().into_owned()),
+                };
+                crate::log_error(app, &format!("[{}] {}", runner.label, failure));
+                // 中断したダウンロードは .part から再開し、パスワードの入力後はダウンロード済みのファイルを使う
+                let resumable = matches!(&failure.error, InstallError::Download(e) if !matches!(e, DownloadError::Cancelled));
+                if !resumable && !failure.error.needs_password() {
+                    cleanup_tmp_dir(&ctx.tmp_dir);
+                }
+                // 巻き戻した先のパッケージの作業フォルダ
+                for ctx in &contexts {
+                    cleanup_tmp_dir(&ctx.tmp_dir);
+                }
+                return Err(failure);
+            }
+            runner.progress.emit((overall + 1) as f64, Some((overall, action)), "step-complete");
+        }
+        done += package.steps.len();
+        contexts.push(ctx);
+    }
+    for (package, _) in packages {
+        let label = format!("installer {}", package.id);
+        let manifest = manifests.iter().position(|m| m.id == package.id).map(|i| manifests.swap_remove(i));
+        let source = package.source.as_ref().and_then(|s| s.to_sources(None).ok()).map(|(primary, _)| primary.describe());
+        record_install(app, &label, &package.id, &package.version, source, manifest);
+    }
+    runner.finish().await;
+    let mut copied = Vec::new();
+    for ((package, _), ctx) in packages.iter().zip(contexts) {
+        let label = format!("installer {}", package.id);
+        if !ctx.copied.is_empty() {
+            crate::log_info(app, &format!("[{}] installed files:\n{}", label, ctx.copied.join("\n")));
+        }
+        crate::log_info(app, &format!("[{}] completed version={}", label, package.version));
+        cleanup_tmp_dir(&ctx.tmp_dir);
+        copied.extend(ctx.copied);
+    }
+    runner.progress.emit(total as f64, None, "done");
+    Ok(InstallReport { copied })
+}
+
+fn new_task_id(task_id: Option<String>) -> String {
+    task_id.filter(|s| !s.trim().is_empty()).unwrap_or_else(|| format!("install-{}", chrono::Utc::now().timestamp_micros()))
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// パッケージの installer.install を順に実行し、インストール済みとして記録する
+/// - 進捗は install:progress（taskId / packageId / ratio / step / stepIndex / totalSteps / phase）で送る
+/// - 失敗時は失敗したステップとコードを返す（パスワードが必要な場合は EXTRACT_PASSWORD_REQUIRED / INCORRECT）
+#[tauri::command]
+pub async fn install_package(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    tasks: tauri::State<'_, TaskRegistry>,
+    package: Package<InstallStep>,
+    options: Option<InstallOptions>,
+) -> Result<InstallReport, StepFailure> {
+    let mut options = options.unwrap_or_default();
+    let task_id = new_task_id(options.task_id.take());
+    let mut runner = Runner::for_install(&app, &manager, &tasks, task_id);
+    install_packages(&mut runner, &[(package, options)]).await
+}
+
+/// 依存関係の plan（resolve_install_plan の steps の順）のパッケージをまとめてインストールする
+/// - すべてのパッケージを 1 つのジャーナルに記録し、どれかが失敗したら先に終えたパッケージも巻き戻す
+/// - 進捗は install_package と同じ install:progress で、ratio・stepIndex・totalSteps は plan 全体のもの
+/// - 失敗時は packageId が失敗したパッケージ。パスワードやダウンロード済みのファイルはパッケージ ID ごとに options に付けて呼び直す
+#[tauri::command]
+pub async fn install_plan(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    tasks: tauri::State<'_, TaskRegistry>,
+    packages: Vec<Package<InstallStep>>,
+    options: Option<PlanOptions>,
+) -> Result<InstallReport, StepFailure> {
+    let mut options = options.unwrap_or_default();
+    let task_id = new_task_id(options.task_id.take());
+    let mut runner = Runner::for_install(&app, &manager, &tasks, task_id);
+    let packages: Vec<(Package<InstallStep>, InstallOptions)> = packages
+        .into_iter()
+        .map(|package| {
+            let package_options = InstallOptions {
+                task_id: None,
+                download_path: options.download_paths.remove(&package.id),
+                password: options.passwords.remove(&package.id),
+            };
+            (package, package_options)
+        })
+        .collect();
+    install_packages(&mut runner, &packages).await
+}
+
+/// パッケージの installer.uninstall を順に実行し、manifest のファイルを消してインストール済みの記録から外す
+/// - 内容が変わっていたため残したファイルなどを返す
+#[tauri::command]
+pub async fn uninstall_package(
+    app: AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    tasks: tauri::State<'_, TaskRegistry>,
+    package: Package<UninstallStep>,
+    options: Option<InstallOptions>,
+) -> Result<RemovalReport, StepFailure> {
+    let mut options = options.unwrap_or_default();
+    let total = package.steps.len();
+    let runner = Runner {
+        app: &app,
+        manager: &manager,
+        tasks: &tasks,
+        progress: Progress {
+            app: app.clone(),
+            task_id: new_task_id(options.task_id.take()),
+            total_steps: total,
+            package_id: package.id.clone(),
+        },
+        label: format!("uninstall {}", package.id),
+        transaction: Transaction::new(transactions_dir(&app), tmp_root(&app)),
+    };
+    let ctx = InstallContext {
+        tmp_dir: tmp_dir_of(&app, &package.id, &package.version),
+        download_path: None,
+        password: None,
+        nested_dirs: Vec::new(),
+        copied: Vec::new(),
+    };
+    runner.progress.emit(0.0, None, "init");
+    crate::log_info(&app, &format!("[{}] start steps={}", runner.label, total));
+    for (index, step) in package.steps.iter().enumerate() {
+        let action = step.action();
+        runner.progress.emit(index as f64, Some((index, action)), "running");
+        let result = match fs::create_dir_all(&ctx.tmp_dir) {
+            Ok(()) => runner.uninstall_step(step, &ctx).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(error) = result {
+            runner.progress.emit(index as f64, Some((index, action)), "error");
+            let failure = StepFailure {
+                package_id: package.id.clone(),
+                step: index,
+                total,
+                action,
+                error,
+                download_path: None,
+            };
+            crate::log_error(&app, &format!("[{}] {}", runner.label, failure));
+            cleanup_tmp_dir(&ctx.tmp_dir);
+            return Err(failure);
+        }
+        runner.progress.emit((index + 1) as f64, Some((index, action)), "step-complete");
+    }
+    let report = remove_by_manifest(&app, &runner.label, &package.id).await;
+    if let Err(e) = state::remove_package(&app, &package.id) {
+        crate::log_error(&app, &format!("[{}] failed to update installed state: {}", runner.label, e));
+    }
+    crate::log_info(&app, &format!("[{}] completed", runner.label));
+    cleanup_tmp_dir(&ctx.tmp_dir);
+    runner.progress.emit(total as f64, None, "done");
+    Ok(report)
+}
+
+/// 前回の起動中に中断されたインストールを処理する（起動時に呼ぶ）
+/// - 確定済みならインストール済みとして記録し、manifest を作り直して後始末を終わらせる。未確定なら巻き戻す
+pub fn recover_interrupted(app: &AppHandle) {
+    let found = journal::recover(&transactions_dir(app), |interrupted| {
+        for section in &interrupted.sections {
+            let label = format!("installer {}", section.package);
+            let manifest = manifest::build(&section.package, &section.version, &section.placed, manifest::load(app, &section.package));
+            let manifest = manifest.inspect_err(|e| crate::log_error(app, &format!("[{}] failed to build manifest: {}", label, e))).ok();
+            record_install(app, &label, &section.package, &section.version, None, manifest);
+        }
+    });
+    for interrupted in found {
+        let state = if interrupted.committed { "completed" } else { "rolled back" };
+        for section in &interrupted.sections {
+            crate::log_info(app, &format!("[installer {}] {} interrupted install version={}", section.package, state, section.version));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        _tmp: tempfile::TempDir,
+        journals: PathBuf,
+        work: PathBuf,
+        src: PathBuf,
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        // root/ に keep.txt（old）を、src/ にパッケージ a・b のファイルを置いておく
+        fn new() -> Self {
+            let tmp = tempfile::tempdir().unwrap();
+            let root = tmp.path().join("root");
+            let src = tmp.path().join("src");
+            fs::create_dir_all(&root).unwrap();
+            fs::create_dir_all(&src).unwrap();
+            fs::write(root.join("keep.txt"), b"old").unwrap();
+            fs::write(src.join("keep.txt"), b"new").unwrap();
+            fs::write(src.join("a.auf"), b"a").unwrap();
+            fs::write(src.join("b.auf"), b"b").unwrap();
+            Self {
+                journals: tmp.path().join("journal"),
+                work: tmp.path().join("work"),
+                src,
+                root,
+                _tmp: tmp,
+            }
+        }
+
+        fn transaction(&self) -> Transaction {
+            Transaction::new(self.journals.clone(), self.work.clone())
+        }
+
+        // src/name を root/ にコピーする（install の copy ステップと同じく journal に記録する）
+        fn copy(&self, journal: Option<Arc<Journal>>, name: &str) {
+            let from = self.src.join(name);
+            copy::copy_item(&from.to_string_lossy(), &self.root, OverwritePolicy::Overwrite, false, &self.work.join("backup"), journal).unwrap();
+        }
+
+        fn journal_files(&self) -> Vec<PathBuf> {
+            match fs::read_dir(&self.journals) {
+                Ok(entries) => entries.map(|e| e.unwrap().path()).collect(),
+                Err(_) => Vec::new(),
+            }
+        }
+    }
+
+    fn has_file(manifest: &Manifest, name: &str) -> bool {
+        manifest.files.iter().any(|f| Path::new(&f.path).file_name().is_some_and(|n| n == name))
+    }
+
+    #[tokio::test]
+    async fn plan_commits_after_the_last_package_with_a_manifest_per_package() {
+        let fx = Fixture::new();
+        let mut tx = fx.transaction();
+        tx.prepare("a", "1.0").unwrap();
+        fx.copy(tx.handle(), "a.auf");
+        fx.copy(tx.handle(), "keep.txt");
+        tx.prepare("b", "2.0").unwrap();
+        fx.copy(tx.handle(), "b.auf");
+
+        let manifests = tx.commit(|_| None).await.unwrap();
+        assert_eq!(manifests.len(), 2);
+        assert_eq!((manifests[0].id.as_str(), manifests[0].version.as_str()), ("a", "1.0"));
+        assert!(has_file(&manifests[0], "a.auf") && has_file(&manifests[0], "keep.txt"));
+        assert!(!has_file(&manifests[0], "b.auf"));
+        assert_eq!((manifests[1].id.as_str(), manifests[1].version.as_str()), ("b", "2.0"));
+        assert!(has_file(&manifests[1], "b.auf") && !has_file(&manifests[1], "a.auf"));
+
+        tx.finish().await.unwrap();
+        assert!(fx.journal_files().is_empty());
+        assert_eq!(fs::read(fx.root.join("a.auf")).unwrap(), b"a");
+        assert_eq!(fs::read(fx.root.join("b.auf")).unwrap(), b"b");
+        assert_eq!(fs::read(fx.root.join("keep.txt")).unwrap(), b"new");
+        // 確定後は巻き戻さない
+        assert!(!tx.rollback().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn failure_in_a_later_package_rolls_back_the_earlier_ones() {
+        let fx = Fixture::new();
+        let mut tx = fx.transaction();
+        tx.prepare("a", "1.0").unwrap();
+        fx.copy(tx.handle(), "a.auf");
+        fx.copy(tx.handle(), "keep.txt");
+        tx.prepare("b", "2.0").unwrap();
+        fx.copy(tx.handle(), "b.auf");
+
+        // b のステップが失敗したことにする
+        assert!(tx.rollback().await.unwrap());
+        assert!(!fx.root.join("a.auf").exists());
+        assert!(!fx.root.join("b.auf").exists());
+        assert_eq!(fs::read(fx.root.join("keep.txt")).unwrap(), b"old");
+        assert!(fx.journal_files().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rollback_waits_for_outstanding_handles() {
+        let fx = Fixture::new();
+        let mut tx = fx.transaction();
+        tx.prepare("a", "1.0").unwrap();
+        fx.copy(tx.handle(), "a.auf");
+
+        // 中断されたステップがまだ参照を持ち、遅れてファイルを置く
+        let handle = tx.handle();
+        let from = fx.src.join("b.auf");
+        let root = fx.root.clone();
+        let backup = fx.work.join("backup");
+        let worker = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            copy::copy_item(&from.to_string_lossy(), &root, OverwritePolicy::Overwrite, false, &backup, handle).unwrap();
+        });
+
+        // 参照が返されてから巻き戻すので、遅れて置いたファイルも消え、未確定のジャーナルも残らない
+        assert!(tx.rollback().await.unwrap());
+        worker.join().unwrap();
+        assert!(!fx.root.join("a.auf").exists());
+        assert!(!fx.root.join("b.auf").exists());
+        assert!(fx.journal_files().is_empty());
+    }
+
+    #[tokio::test]
+    async fn nothing_to_roll_back_without_a_journal() {
+        let fx = Fixture::new();
+        let mut tx = fx.transaction();
+        assert!(!tx.rollback().await.unwrap());
+        assert!(tx.commit(|_| None).await.unwrap().is_empty());
+        tx.finish().await.unwrap();
+        assert!(fx.journal_files().is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+// -----------------------
+// インストールのジャーナル（失敗・クラッシュ時の巻き戻し）
+// -----------------------
+//
+// - インストール中に作ったフォルダ・一時ファイル・ファイルと、上書き・削除したファイルの退避先を 1 行ずつ追記する
+//   変更する前に書き込んで fsync するので、どこで落ちてもジャーナルから元の状態に戻せる
+// - 上書き前のファイル（pre-image）は placement が置き場所の隣に退避したものをそのまま使い、確定するまで消さない
+// - 失敗したら新しい記録から順に元に戻す。確定したら commit 行を書き、退避したファイルとジャーナルを消す
+// - 起動時に残っているジャーナルは、commit 行があれば後始末を終わらせ、無ければ巻き戻す
+// - 作業フォルダ（{tmp}）の中は記録しない（終了時に消えるうえ、展開するファイル数が多いと記録の fsync が重いため）
+// - run / run_auo_setup で外部のインストーラが行った変更は記録できない
+// - 置いたファイルと作ったフォルダの一覧は、確定時に manifest を作るのにも使う
+// - 依存関係の plan は複数のパッケージを 1 つのジャーナルで記録する（begin 行ごとにパッケージを区切り、まとめて確定・巻き戻す）
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// ジャーナルの 1 行
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Record {
+    // 対象のパッケージ（以降の記録はこのパッケージのもの。起動時の後始末でインストール済みの記録に使う）
+    Begin { package: String, version: String },
+    // 新しく作ったフォルダ
+    Dir { path: PathBuf },
+    // 書き込み用の一時ファイル
+    Temp { path: PathBuf },
+    // 新しく置いたファイル
+    Create { path: PathBuf },
+    // 上書きしたファイルと、元のファイルの退避先
+    Replace { path: PathBuf, preimage: PathBuf },
+    // 削除したファイルと、その退避先（展開した後の内側のアーカイブなど）
+    Remove { path: PathBuf, preimage: PathBuf },
+    // インストールの確定
+    Commit,
+}
+
+/// 実行中のインストール 1 件分のジャーナル
+pub struct Journal {
+    path: PathBuf,
+    file: Mutex<File>,
+    // 記録しないフォルダ
+    exclude: Vec<PathBuf>,
+}
+
+impl Journal {
+    /// dir に新しいジャーナルを作る
+    pub fn begin(dir: &Path, package: &str, version: &str, exclude: Vec<PathBuf>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let name: String = package.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' }).collect();
+        let path = dir.join(format!("{}-{}.journal", name, chrono::Utc::now().timestamp_micros()));
+        let file = OpenOptions::new().append(true).create_new(true).open(&path)?;
+        let journal = Self { path, file: Mutex::new(file), exclude };
+        journal.record(&Record::Begin { package: package.to_string(), version: version.to_string() })?;
+        Ok(journal)
+    }
+
+    /// path への変更を記録する対象か
+    pub fn tracks(&self, path: &Path) -> bool {
+        !self.exclude.iter().any(|root| path.starts_with(root))
+    }
+
+    /// 1 行追記して fsync する（記録した変更を行う前に呼ぶ）
+    pub fn record(&self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+        line.push('\n');
+        let mut file = self.file.lock().map_err(|_| io::Error::other("journal lock poisoned"))?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    }
+
+    /// 以降の記録を package のものとする（plan の 2 つ目以降のパッケージ）
+    pub fn begin_package(&self, package: &str, version: &str) -> io::Result<()> {
+        self.record(&Record::Begin { package: package.to_string(), version: version.to_string() })
+    }
+
+    /// これまでに置いたファイルと作ったフォルダ（パッケージごと）
+    pub fn sections(&self) -> io::Result<Vec<Section>> {
+        Ok(sections(&read_records(&self.path)?))
+    }
+
+    /// 確定を記録する（この後に落ちても、起動時に後始末が行われる）
+    pub fn commit(&self) -> io::Result<()> {
+        self.record(&Record::Commit)
+    }
+
+    /// 確定後の後始末（退避したファイルとジャーナルを消す）
+    pub fn finish(self) -> io::Result<()> {
+        let Journal { path, file, .. } = self;
+        drop(file);
+        let records = read_records(&path)?;
+        cleanup(&records);
+        fs::remove_file(&path)
+    }
+
+    /// 記録した変更を新しいものから順に元に戻し、ジャーナルを消す
+    pub fn rollback(self) -> io::Result<()> {
+        let Journal { path, file, .. } = self;
+        drop(file);
+        let records = read_records(&path)?;
+        undo(&records);
+        fs::remove_file(&path)
+    }
+}
+
+/// ジャーナルを読む（書き込み途中で落ちた最後の行は読み飛ばす）
+fn read_records(path: &Path) -> io::Result<Vec<Record>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        match serde_json::from_str(&line?) {
+            Ok(record) => records.push(record),
+            Err(_) => break,
+        }
+    }
+    Ok(records)
+}
+
+/// 置いたファイル（パス, 既存のファイルを上書きしたか）と作ったフォルダ
+#[derive(Debug, Default)]
+pub struct Placed {
+    pub files: Vec<(PathBuf, bool)>,
+    pub dirs: Vec<PathBuf>,
+}
+
+/// 1 パッケージ分の記録
+#[derive(Debug)]
+pub struct Section {
+    pub package: String,
+    pub version: String,
+    pub placed: Placed,
+}
+
+// 同じパスに複数回置いた場合は最初の記録で上書きかどうかを決める（前のパッケージが置いたものも含む）
+fn sections(records: &[Record]) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    let mut first: HashMap<PathBuf, bool> = HashMap::new();
+    // (セクションの位置, パス)。同じパッケージの一覧には 1 回だけ入れる
+    let mut listed: HashSet<(usize, PathBuf)> = HashSet::new();
+    for record in records {
+        match record {
+            Record::Begin { package, version } => sections.push(Section { package: package.clone(), version: version.clone(), placed: Placed::default() }),
+            Record::Create { path } | Record::Replace { path, .. } => {
+                let replaced = *first.entry(path.clone()).or_insert(matches!(record, Record::Replace { .. }));
+                let index = sections.len().wrapping_sub(1);
+                if let Some(section) = sections.last_mut() {
+                    if listed.insert((index, path.clone())) {
+                        section.placed.files.push((path.clone(), replaced));
+                    }
+                }
+            }
+            Record::Dir { path } => {
+                if let Some(section) = sections.last_mut() {
+                    section.placed.dirs.push(path.clone());
+                }
+            }
+            // 置いた後に削除したファイルは一覧に残さない
+            Record::Remove { path, .. } => {
+                for (index, section) in sections.iter_mut().enumerate() {
+                    if listed.remove(&(index, path.clone())) {
+                        section.placed.files.retain(|(p, _)| p != path);
+                    }
+                }
+                first.remove(path);
+            }
+            Record::Temp { .. } | Record::Commit => {}
+        }
+    }
+    sections
+}
+
+fn undo(records: &[Record]) {
+    for record in records.iter().rev() {
+        match record {
+            Record::Temp { path } | Record::Create { path } => {
+                let _ = fs::remove_file(path);
+            }
+            // 退避先が無ければ元に戻し済み（または上書き前に落ちた）ので、今あるファイルには触らない
+            Record::Replace { path, preimage } => {
+                if preimage.exists() {
+                    let _ = fs::remove_file(path);
+                    let _ = fs::rename(preimage, path);
+                }
+            }
+            Record::Remove { path, preimage } => {
+                if preimage.exists() {
+                    let _ = fs::rename(preimage, path);
+                }
+            }
+            // 空の場合だけ消える（元からあったファイルが入っていれば残る）
+            Record::Dir { path } => {
+                let _ = fs::remove_dir(path);
+            }
+            Record::Begin { .. } | Record::Commit => {}
+        }
+    }
+}
+
+fn cleanup(records: &[Record]) {
+    for record in records {
+        match record {
+            Record::Replace { preimage: path, .. } | Record::Remove { preimage: path, .. } | Record::Temp { path } => {
+                let _ = fs::remove_file(path);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 起動時に見つかった中断されたインストール
+#[derive(Debug)]
+pub struct Interrupted {
+    // commit 行まで書かれていた（後始末だけ行う）か
+    pub committed: bool,
+    // 記録されていたパッケージ（plan の場合は複数）
+    pub sections: Vec<Section>,
+}
+
+/// dir に残っているジャーナルを処理する
+/// - on_committed は確定済みのものについて後始末の前に呼ぶ（インストール済みの記録と manifest をやり直すため）
+pub fn recover(dir: &Path, mut on_committed: impl FnMut(&Interrupted)) -> Vec<Interrupted> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("journal") {
+            continue;
+        }
+        let Ok(records) = read_records(&path) else {
+            continue;
+        };
+        let interrupted = Interrupted {
+            committed: records.iter().any(|r| matches!(r, Record::Commit)),
+            sections: sections(&records),
+        };
+        if interrupted.committed {
+            on_committed(&interrupted);
+            cleanup(&records);
+        } else {
+            undo(&records);
+        }
+        let _ = fs::remove_file(&path);
+        found.push(interrupted);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::placement::Placement;
+    use std::sync::Arc;
+
+    struct Fixture {
+        _tmp: tempfile::TempDir,
+        journals: PathBuf,
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        // root/ に keep.txt（old）と obsolete.txt を置いておく
+        fn new() -> Self {
+            let tmp = tempfile::tempdir().unwrap();
+            let root = tmp.path().join("root");
+            fs::create_dir_all(&root).unwrap();
+            fs::write(root.join("keep.txt"), b"old").unwrap();
+            fs::write(root.join("obsolete.txt"), b"obsolete").unwrap();
+            Self { journals: tmp.path().join("journal"), root, _tmp: tmp }
+        }
+
+        fn write(&self, placement: &mut Placement, rel: &str, body: &[u8]) {
+            let dest = self.root.join(rel);
+            let (tmp, mut file) = placement.create_temp(&dest).unwrap();
+            file.write_all(body).unwrap();
+            drop(file);
+            placement.place(&tmp, &dest).unwrap();
+        }
+
+        /// フォルダの作成・新規・上書き・削除をジャーナルに記録しながら行い、後始末をせずに落ちたことにする
+        fn crash(&self, commit: bool) {
+            let journal = Arc::new(Journal::begin(&self.journals, "pkg", "1.0", Vec::new()).unwrap());
+            let mut placement = Placement::with_journal(Some(journal.clone()));
+            self.write(&mut placement, "plugins/new.auf", b"new");
+            self.write(&mut placement, "keep.txt", b"new");
+            placement.remove(&self.root.join("obsolete.txt")).unwrap();
+            if commit {
+                placement.commit();
+                journal.commit().unwrap();
+            } else {
+                // プロセスが落ちた場合は placement の巻き戻しも行われない
+                std::mem::forget(placement);
+            }
+        }
+
+        fn journal_count(&self) -> usize {
+            fs::read_dir(&self.journals).unwrap().count()
+        }
+
+        // root/ に残っているファイル（一時ファイル・退避ファイルを含む）
+        fn files(&self) -> Vec<String> {
+            let mut files: Vec<String> = walkdir::WalkDir::new(&self.root)
+                .into_iter()
+                .flatten()
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.path().strip_prefix(&self.root).unwrap().iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/"))
+                .collect();
+            files.sort();
+            files
+        }
+    }
+
+    #[test]
+    fn recover_rolls_back_uncommitted_installs() {
+        let fx = Fixture::new();
+        fx.crash(false);
+        let mut called = false;
+        let found = recover(&fx.journals, |_| called = true);
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].committed);
+        assert_eq!(found[0].sections.len(), 1);
+        assert_eq!((found[0].sections[0].package.as_str(), found[0].sections[0].version.as_str()), ("pkg", "1.0"));
+        assert!(!called);
+        // 新しく置いたファイルとフォルダは消え、上書き・削除したファイルは元に戻る
+        assert_eq!(fx.files(), ["keep.txt", "obsolete.txt"]);
+        assert_eq!(fs::read(fx.root.join("keep.txt")).unwrap(), b"old");
+        assert!(!fx.root.join("plugins").exists());
+        assert_eq!(fx.journal_count(), 0);
+    }
+
+    #[test]
+    fn recover_finishes_committed_installs() {
+        let fx = Fixture::new();
+        fx.crash(true);
+        let mut placed = Vec::new();
+        let found = recover(&fx.journals, |interrupted| placed = interrupted.sections[0].placed.files.clone());
+        assert_eq!(found.len(), 1);
+        assert!(found[0].committed);
+        // 削除したファイルは置いたファイルに含まれず、退避したファイルは消える
+        assert_eq!(
+            placed,
+            [
+                (fx.root.join("plugins").join("new.auf"), false),
+                (fx.root.join("keep.txt"), true)
+            ]
+        );
+        assert_eq!(found[0].sections[0].placed.dirs, [fx.root.join("plugins")]);
+        assert_eq!(fx.files(), ["keep.txt", "plugins/new.auf"]);
+        assert_eq!(fs::read(fx.root.join("keep.txt")).unwrap(), b"new");
+        assert_eq!(fx.journal_count(), 0);
+    }
+
+    #[test]
+    fn recover_ignores_a_torn_last_line() {
+        let fx = Fixture::new();
+        fx.crash(false);
+        let path = fs::read_dir(&fx.journals).unwrap().next().unwrap().unwrap().path();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"op":"create","path":"#).unwrap();
+        drop(file);
+        let found = recover(&fx.journals, |_| {});
+        assert_eq!(found.len(), 1);
+        assert_eq!(fx.files(), ["keep.txt", "obsolete.txt"]);
+        assert_eq!(fs::read(fx.root.join("keep.txt")).unwrap(), b"old");
+    }
+
+    #[test]
+    fn excluded_directories_are_not_recorded() {
+        let fx = Fixture::new();
+        let work = fx.root.join("work");
+        let journal = Journal::begin(&fx.journals, "pkg", "1.0", vec![work.clone()]).unwrap();
+        assert!(!journal.tracks(&work.join("a.txt")));
+        assert!(journal.tracks(&fx.root.join("a.txt")));
+        let journal = Arc::new(journal);
+        let mut placement = Placement::with_journal(Some(journal.clone()));
+        fx.write(&mut placement, "work/a.txt", b"a");
+        placement.commit();
+        let placed = &journal.sections().unwrap()[0].placed;
+        assert!(placed.files.is_empty() && placed.dirs.is_empty());
+        Arc::into_inner(journal).unwrap().rollback().unwrap();
+        assert!(work.join("a.txt").exists());
+    }
+
+    #[test]
+    fn plan_records_each_package_separately() {
+        let fx = Fixture::new();
+        let journal = Arc::new(Journal::begin(&fx.journals, "pkg-a", "1.0", Vec::new()).unwrap());
+        let mut placement = Placement::with_journal(Some(journal.clone()));
+        fx.write(&mut placement, "plugins/shared.auf", b"a");
+        journal.begin_package("pkg-b", "2.0").unwrap();
+        fx.write(&mut placement, "plugins/shared.auf", b"b");
+        fx.write(&mut placement, "plugins/b.auf", b"b");
+        fx.write(&mut placement, "keep.txt", b"b");
+        placement.commit();
+
+        let sections = journal.sections().unwrap();
+        let summary: Vec<(&str, &str, usize, usize)> = sections.iter().map(|s| (s.package.as_str(), s.version.as_str(), s.placed.files.len(), s.placed.dirs.len())).collect();
+        assert_eq!(summary, [("pkg-a", "1.0", 1, 1), ("pkg-b", "2.0", 3, 0)]);
+        // 前のパッケージが置いたファイルを上書きしても、plan の前から無かったファイルとして扱う
+        assert_eq!(
+            sections[1].placed.files,
+            [
+                (fx.root.join("plugins").join("shared.auf"), false),
+                (fx.root.join("plugins").join("b.auf"), false),
+                (fx.root.join("keep.txt"), true)
+            ]
+        );
+
+        // 巻き戻しはすべてのパッケージをまとめて戻す
+        Arc::into_inner(journal).unwrap().rollback().unwrap();
+        assert_eq!(fx.files(), ["keep.txt", "obsolete.txt"]);
+        assert_eq!(fs::read(fx.root.join("keep.txt")).unwrap(), b"old");
+    }
+}
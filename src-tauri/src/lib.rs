@@ -1,35 +1,29 @@
 // use crate::paths::Dir;
 use once_cell::sync::Lazy;
-use percent_encoding::percent_decode_str;
 use std::fs;
-use std::io::{self};
 use std::path::{Path, PathBuf};
 use std::sync::RwLock;
 use tauri::{webview::PageLoadEvent, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
 use url::Url;
-use walkdir::WalkDir;
 
+mod cache;
+mod copy;
+mod download;
+mod extract;
+mod github;
+mod installer;
+mod journal;
+mod manifest;
 mod paths;
+mod placement;
+mod resolver;
+mod state;
+mod tasks;
 
 // -----------------------
-// Google Drive ダウンロード
+// パス解決
 // -----------------------
 
-// DataRootにします
-
-// エラー型定義
-// - thiserrorでエラーメッセージを自動実装
-// - Serializeは、フロント側へJSONで返したい場合などに備えて付与
-#[derive(thiserror::Error, Debug, serde::Serialize)]
-enum DriveError {
-    #[error("io error: {0}")]
-    Io(String),
-    #[error("http error: {0}")]
-    Http(String),
-    #[error("network error: {0}")]
-    Net(String),
-}
-
 /// Windowsドライブ指定（"C:\..."）やUNIXの絶対パス（"/..."）を判定
 fn is_abs(p: &str) -> bool {
     let s = p.replace('\\', "/");
@@ -47,219 +41,8 @@ fn resolve_rel_to_app_config(app: &tauri::AppHandle, p: &str) -> PathBuf {
     }
 }
 
-fn sanitize_filename(name: &str) -> String {
-    let mut out = String::new();
-    for ch in name.chars() {
-        // forbid separators and reserved Windows characters
-        if matches!(ch, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
-            out.push('_');
-        } else {
-            out.push(ch);
-        }
-    }
-    let trimmed = out.trim();
-    if trimmed.is_empty() {
-        String::from("download.bin")
-    } else {
-        trimmed.to_string()
-    }
-}
-
-fn drive_filename_from_headers(headers: &reqwest::header::HeaderMap) -> Option<String> {
-    let raw = headers.get(reqwest::header::CONTENT_DISPOSITION)?;
-    let value = String::from_utf8_lossy(raw.as_bytes());
-
-    for part in value.split(';') {
-        let part = part.trim();
-        if let Some(name) = part.strip_prefix("filename*=") {
-            let encoded = name.trim().trim_matches('"');
-            let payload = encoded.split_once("''").map(|(_, v)| v).unwrap_or(encoded);
-            let decoded = percent_decode_str(payload).decode_utf8_lossy();
-            let cleaned = sanitize_filename(&decoded);
-            return Some(cleaned);
-        }
-    }
-
-    for part in value.split(';') {
-        let part = part.trim();
-        if let Some(name) = part.strip_prefix("filename=") {
-            let cleaned = sanitize_filename(name.trim().trim_matches('"'));
-            return Some(cleaned);
-        }
-    }
-
-    None
-}
-
-async fn drive_fetch_response(file_id: &str) -> Result<reqwest::Response, DriveError> {
-    let url = format!("https://drive.google.com/uc?export=download&id={}", file_id);
-    let client = reqwest::Client::builder().user_agent("AviUtl2Catalog").build().map_err(|e| DriveError::Net(format!("client build failed: {}", e)))?;
-
-    let res = client.get(&url).send().await.map_err(|e| DriveError::Net(e.to_string()))?;
-    if res.status().is_success() {
-        return Ok(res);
-    }
-    let status = res.status();
-    let text = res.text().await.unwrap_or_default();
-    let snippet: String = text.chars().take(500).collect();
-    if snippet.trim().is_empty() {
-        Err(DriveError::Http(format!("{} {}", status, "failed to download file from Google Drive")))
-    } else {
-        Err(DriveError::Http(format!("{} {}", status, snippet)))
-    }
-}
-
-#[tauri::command]
-async fn drive_download_to_file(window: tauri::Window, file_id: String, dest_path: String) -> Result<(), DriveError> {
-    use std::fs::{create_dir_all, OpenOptions};
-    use std::io::Write;
-
-    let app = window.app_handle();
-    let mut res = match drive_fetch_response(&file_id).await {
-        Ok(v) => v,
-        Err(e) => {
-            log_error(&app, &format!("failed to fetch drive file (id={}): {}", file_id, e));
-            return Err(e);
-        }
-    };
-
-    let dest_abs = resolve_rel_to_app_config(&app, &dest_path);
-    let looks_dir = dest_path.ends_with('/') || dest_path.ends_with('\\') || dest_abs.is_dir();
-    let is_placeholder = dest_abs.file_name().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("download.bin") || s == file_id).unwrap_or(true);
-    let drive_name = drive_filename_from_headers(res.headers());
-
-    let final_dest = if looks_dir || is_placeholder {
-        let drive_name = drive_name.ok_or_else(|| DriveError::Http("missing filename in Google Drive response".to_string()))?;
-        if looks_dir {
-            dest_abs.join(drive_name)
-        } else {
-            dest_abs.parent().map(|p| p.join(drive_name)).unwrap_or(dest_abs.clone())
-        }
-    } else {
-        dest_abs.clone()
-    };
-    if let Some(parent) = final_dest.parent() {
-        let _ = create_dir_all(parent);
-    }
-
-    let mut f = OpenOptions::new().create(true).truncate(true).write(true).open(&final_dest).map_err(|e| DriveError::Io(e.to_string()))?;
-
-    let total_opt = res.headers().get(reqwest::header::CONTENT_LENGTH).and_then(|v| v.to_str().ok()).and_then(|s| s.parse::<u64>().ok());
-
-    let mut read: u64 = 0;
-    while let Some(chunk) = res.chunk().await.map_err(|e| DriveError::Net(e.to_string()))? {
-        f.write_all(&chunk).map_err(|e| DriveError::Io(e.to_string()))?;
-        read += chunk.len() as u64;
-        let _ = window.emit("drive:progress", serde_json::json!({ "fileId": file_id, "read": read, "total": total_opt }));
-    }
-    let _ = window.emit("drive:done", serde_json::json!({ "fileId": file_id, "path": final_dest.to_string_lossy() }));
-    Ok(())
-}
-
-#[tauri::command]
-async fn download_file_to_path(window: tauri::Window, url: String, dest_path: String, task_id: Option<String>) -> Result<String, String> {
-    use std::fs::{create_dir_all, OpenOptions};
-    use std::io::Write;
-
-    if !url.trim_start().to_ascii_lowercase().starts_with("https://") {
-        return Err("Only https:// is permitted".to_string());
-    }
-    if dest_path.trim().is_empty() {
-        return Err("dest_path must not be empty".to_string());
-    }
-
-    let app = window.app_handle();
-    let task_id = task_id.unwrap_or_else(|| format!("download-{}", chrono::Utc::now().timestamp_micros()));
-    let dest_dir = resolve_rel_to_app_config(&app, &dest_path);
-    if let Err(e) = create_dir_all(&dest_dir) {
-        let msg = format!("failed to prepare destination directory: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        return Err(msg);
-    }
-
-    let parsed_url = Url::parse(&url).map_err(|e| {
-        let msg = format!("invalid url: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        msg
-    })?;
-    let file_name_raw = parsed_url
-        .path_segments()
-        .and_then(|segments| segments.filter(|s| !s.is_empty()).last().map(|s| s.to_string()))
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| "download.bin".to_string());
-    let file_name = percent_decode_str(&file_name_raw).decode_utf8_lossy().to_string();
-    let final_name = sanitize_filename(&file_name);
-    let final_path = dest_dir.join(final_name);
-
-    let client = reqwest::Client::builder().user_agent("AviUtl2Catalog").build().map_err(|e| {
-        let msg = format!("failed to build http client: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        msg
-    })?;
-
-    let mut response = client.get(&url).send().await.map_err(|e| {
-        let msg = format!("network error: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("download failed (url={}): {}", url, e));
-        msg
-    })?;
-
-    let status = response.status();
-    if !status.is_success() {
-        let text = response.text().await.unwrap_or_default();
-        let body_snippet: String = if text.len() > 500 { text[..500].to_string() } else { text };
-        let msg = if body_snippet.is_empty() { format!("HTTP error: {}", status) } else { format!("HTTP error: {}: {}", status, body_snippet) };
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("download failed (url={}): {}", url, msg));
-        return Err(msg);
-    }
-
-    let total_opt = response.content_length();
-    let mut file = OpenOptions::new().create(true).truncate(true).write(true).open(&final_path).map_err(|e| {
-        let msg = format!("failed to open destination file: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("download failed (url={}): {}", url, msg));
-        msg
-    })?;
-
-    let mut written: u64 = 0;
-    while let Some(chunk) = response.chunk().await.map_err(|e| {
-        let msg = format!("read error: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("download failed (url={}): {}", url, msg));
-        msg
-    })? {
-        file.write_all(&chunk).map_err(|e| {
-            let msg = format!("write error: {}", e);
-            let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-            log_error(&app, &format!("download failed (url={}): {}", url, msg));
-            msg
-        })?;
-        written += chunk.len() as u64;
-        let _ = window.emit(
-            "download:progress",
-            serde_json::json!({
-                "taskId": task_id,
-                "read": written,
-                "total": total_opt,
-            }),
-        );
-    }
-
-    let final_path_str = final_path.to_string_lossy().to_string();
-    let _ = window.emit(
-        "download:done",
-        serde_json::json!({
-            "taskId": task_id,
-            "path": final_path_str,
-        }),
-    );
-
-    Ok(final_path_str)
-}
-
 // -------------------------
-// BOOTH認証用ウィンドウ管理とダウンロード処理
+// BOOTH認証用ウィンドウ管理
 // -------------------------
 
 // BOOTHのログイン系パス判定
@@ -381,158 +164,6 @@ fn close_booth_auth_window(app: tauri::AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-// ファイルダウンロード（BOOTH認証セッション対応版）
-#[tauri::command]
-async fn download_file_to_path_booth(
-    window: tauri::Window,
-    url: String,
-    dest_path: String,
-    task_id: Option<String>,
-    session_window_label: Option<String>,
-) -> Result<String, String> {
-    use reqwest::header::{CONTENT_DISPOSITION, CONTENT_TYPE, COOKIE};
-    use std::fs::{create_dir_all, OpenOptions};
-    use std::io::Write;
-
-    if !url.trim_start().to_ascii_lowercase().starts_with("https://") {
-        return Err("Only https:// is permitted".to_string());
-    }
-    if dest_path.trim().is_empty() {
-        return Err("dest_path must not be empty".to_string());
-    }
-
-    let app = window.app_handle();
-    let task_id = task_id.unwrap_or_else(|| format!("download-{}", chrono::Utc::now().timestamp_micros()));
-    let dest_dir = resolve_rel_to_app_config(&app, &dest_path);
-    if let Err(e) = create_dir_all(&dest_dir) {
-        let msg = format!("failed to prepare destination directory: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        return Err(msg);
-    }
-
-    let parsed_url = Url::parse(&url).map_err(|e| {
-        let msg = format!("invalid url: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        msg
-    })?;
-
-    let session_label = session_window_label.unwrap_or_else(|| "booth-auth".to_string()).trim().to_string();
-    let session_label = if session_label.is_empty() { "booth-auth".to_string() } else { session_label };
-    let session_window = match app.get_webview_window(&session_label) {
-        Some(w) => w,
-        None => {
-            let msg = "AUTH_WINDOW_MISSING".to_string();
-            let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-            return Err(msg);
-        }
-    };
-
-    // Webview の Cookie を Rust 側リクエストに引き継ぐ
-    let cookies = session_window.cookies_for_url(parsed_url.clone()).map_err(|e| {
-        let msg = format!("AUTH_COOKIE_FETCH_FAILED: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        msg
-    })?;
-    let cookie_header = cookies.iter().map(|c| format!("{}={}", c.name(), c.value())).collect::<Vec<_>>().join("; ");
-
-    // URL末尾から推定したファイル名を安全化
-    let file_name_raw = parsed_url
-        .path_segments()
-        .and_then(|segments| segments.filter(|s| !s.is_empty()).last().map(|s| s.to_string()))
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| "download.bin".to_string());
-    let file_name = percent_decode_str(&file_name_raw).decode_utf8_lossy().to_string();
-    let final_name = sanitize_filename(&file_name);
-    let final_path = dest_dir.join(final_name);
-
-    let client = reqwest::Client::builder().user_agent("AviUtl2Catalog").build().map_err(|e| {
-        let msg = format!("failed to build http client: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        msg
-    })?;
-
-    let mut req = client.get(&url);
-    if !cookie_header.is_empty() {
-        req = req.header(COOKIE, cookie_header);
-    }
-
-    let mut response = req.send().await.map_err(|e| {
-        let msg = format!("network error: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("booth download failed (url={}): {}", url, e));
-        msg
-    })?;
-
-    let status = response.status();
-    if !status.is_success() {
-        let msg = format!("HTTP_ERROR:{} {}", status.as_u16(), status);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("booth download failed (url={}): {}", url, msg));
-        return Err(msg);
-    }
-
-    let final_url = response.url().clone();
-    // 未ログインのリダイレクトは保存しない
-    if is_booth_login_url(&final_url) {
-        let msg = "AUTH_REQUIRED".to_string();
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        return Err(msg);
-    }
-
-    let content_type = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or("").to_ascii_lowercase();
-    let content_disposition = response.headers().get(CONTENT_DISPOSITION);
-    // HTML かつ Content-Disposition が無い場合はログイン画面とみなす
-    if content_type.contains("text/html") && content_disposition.is_none() {
-        let msg = "AUTH_REQUIRED".to_string();
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        return Err(msg);
-    }
-
-    // 認証チェック後にのみファイルを作成
-    let total_opt = response.content_length();
-    let mut file = OpenOptions::new().create(true).truncate(true).write(true).open(&final_path).map_err(|e| {
-        let msg = format!("failed to open destination file: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("booth download failed (url={}): {}", url, msg));
-        msg
-    })?;
-
-    let mut written: u64 = 0;
-    while let Some(chunk) = response.chunk().await.map_err(|e| {
-        let msg = format!("read error: {}", e);
-        let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-        log_error(&app, &format!("booth download failed (url={}): {}", url, msg));
-        msg
-    })? {
-        file.write_all(&chunk).map_err(|e| {
-            let msg = format!("write error: {}", e);
-            let _ = window.emit("download:error", serde_json::json!({ "taskId": task_id, "message": msg }));
-            log_error(&app, &format!("booth download failed (url={}): {}", url, msg));
-            msg
-        })?;
-        written += chunk.len() as u64;
-        let _ = window.emit(
-            "download:progress",
-            serde_json::json!({
-                "taskId": task_id,
-                "read": written,
-                "total": total_opt,
-            }),
-        );
-    }
-
-    let final_path_str = final_path.to_string_lossy().to_string();
-    let _ = window.emit(
-        "download:done",
-        serde_json::json!({
-            "taskId": task_id,
-            "path": final_path_str,
-        }),
-    );
-
-    Ok(final_path_str)
-}
-
 // -------------------------
 // ハッシュ計算 (OK)
 // -------------------------
@@ -556,7 +187,7 @@ fn calc_xxh3_hex(path: String) -> Result<String, String> {
 // -------------------------
 
 // カタログアイテムのインデックス情報を保持する構造体
-#[derive(Clone)]
+#[derive(Clone, Default)]
 struct IndexItem {
     id: String,
     name_key: String,
@@ -565,6 +196,12 @@ struct IndexItem {
     item_type: String,
     tags: Vec<String>,
     updated_at: Option<i64>,
+    // 依存するパッケージの ID（resolver で使う。"id>=version" で必要なバージョンを指定できる）
+    dependencies: Vec<String>,
+    // カタログに載っているバージョン（古いものから順に、最後が latest-version）
+    versions: Vec<String>,
+    // installer.install がある（文字列の installer も含む）
+    installable: bool,
 }
 
 // カタログアイテムのグローバルな検索インデックス
@@ -633,6 +270,19 @@ fn parse_updated_at(value: &serde_json::Value) -> Option<i64> {
     Some(dt.assume_utc().unix_timestamp() * 1000)
 }
 
+// カタログに載っているバージョンの一覧（versions/version配列の順、latest-versionが無ければ末尾に加える）
+fn parse_versions(value: &serde_json::Value) -> Vec<String> {
+    let arr = value.get("versions").or_else(|| value.get("version")).and_then(|v| v.as_array());
+    let mut versions: Vec<String> =
+        arr.map(|arr| arr.iter().filter_map(|x| x.get("version").and_then(|v| v.as_str())).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()).unwrap_or_default();
+    if let Some(latest) = value.get("latest-version").and_then(|v| v.as_str()).map(str::trim).filter(|s| !s.is_empty()) {
+        if !versions.iter().any(|v| v == latest) {
+            versions.push(latest.to_string());
+        }
+    }
+    versions
+}
+
 // カタログインデックスを設定し、検索用データ構造を構築
 #[tauri::command]
 fn set_catalog_index(items: Vec<serde_json::Value>) -> Result<usize, String> {
@@ -649,6 +299,17 @@ fn set_catalog_index(items: Vec<serde_json::Value>) -> Result<usize, String> {
         let author = it.get("author").and_then(|v| v.as_str()).unwrap_or("").to_string();
         let summary = it.get("summary").and_then(|v| v.as_str()).unwrap_or("").to_string();
         let updated_at = parse_updated_at(&it);
+        let dependencies: Vec<String> = it
+            .get("dependencies")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|x| x.as_str()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        let versions = parse_versions(&it);
+        let installable = match it.get("installer") {
+            Some(serde_json::Value::String(_)) => true,
+            Some(installer) => installer.get("install").is_some_and(|v| v.is_array()),
+            None => false,
+        };
         let item = IndexItem {
             id,
             name_key: normalize(&name),
@@ -657,6 +318,9 @@ fn set_catalog_index(items: Vec<serde_json::Value>) -> Result<usize, String> {
             item_type,
             tags,
             updated_at,
+            dependencies,
+            versions,
+            installable,
         };
         v.push(item);
     }
@@ -719,43 +383,6 @@ fn query_catalog_index(q: Option<String>, tags: Option<Vec<String>>, types: Opti
     filtered.iter().map(|it| it.id.clone()).collect()
 }
 
-// -----------------------
-// ZIPファイル解凍
-// -----------------------
-use std::fs::File;
-use zip::read::ZipArchive;
-/// ZIPファイルを解凍する
-#[tauri::command]
-fn extract_zip(_app: tauri::AppHandle, zip_path: String, dest_path: String) -> Result<(), String> {
-    let file = File::open(&zip_path).map_err(|e| format!("open zip error: {}", e))?;
-    let mut archive = ZipArchive::new(file).map_err(|e| format!("zip open error: {}", e))?;
-    archive.extract(Path::new(&dest_path)).map_err(|e| format!("extract error: {}", e))?;
-    Ok(())
-}
-// -----------------------
-// 7zファイル解凍
-// -----------------------
-use memchr::memmem::Finder;
-use memmap2::MmapOptions;
-use sevenz_rust2::{decompress_with_extract_fn_and_password, default_entry_extract_fn, Password};
-use std::io::Cursor;
-
-// 7z SFX（自己解凍形式）ファイルを展開
-#[tauri::command]
-async fn extract_7z_sfx(_: tauri::AppHandle, sfx_path: String, dest_path: String) -> Result<(), String> {
-    tauri::async_runtime::spawn_blocking(move || -> Result<(), String> {
-        const SIGNATURE: &[u8] = b"\x37\x7A\xBC\xAF\x27\x1C"; // // 7z ファイルのシグネチャ
-        let file = File::open(Path::new(&sfx_path)).map_err(|e| format!("open sfx error: {e}"))?; // SFX ファイルを開く
-        let mmap = unsafe { MmapOptions::new().map(&file) }.map_err(|e| format!("mmap error: {e}"))?; // ファイルをメモリマップする（高速読み取りのため）
-        let offset = Finder::new(SIGNATURE).find(&mmap).ok_or_else(|| "7z signature not found in SFX binary".to_string())?; // SFX バイナリ内から 7z シグネチャ位置を検索する
-        let cursor = Cursor::new(&mmap[offset..]); // シグネチャ位置からカーソルを作成（7z データ部分を読み込む）
-        decompress_with_extract_fn_and_password(cursor, Path::new(&dest_path), Password::empty(), default_entry_extract_fn).map_err(|e| format!("7z decompress error: {e}"))?; // 7z データを展開する（パスワード無しで解凍）
-        Ok(())
-    })
-    .await
-    .map_err(|e| format!("task join error: {e}"))?
-}
-
 // -----------------------
 // auo_setup 自動実行
 // -----------------------
@@ -1207,118 +834,41 @@ fn detect_versions_map(app: tauri::AppHandle, items: Vec<serde_json::Value>) ->
 }
 
 // -----------------------
-// インストール済みマップ管理（AppConfig/installed.json）
+// インストール済みマップ（installed-state.json の互換の表示）
 // -----------------------
 
-// インストール済みファイルのパスを取得
-fn installed_file_path(app: &tauri::AppHandle) -> std::path::PathBuf {
-    app_config_dir(app).join("installed.json")
-}
-
-// インストール済みマップをファイルから読み込み
+// インストール済みマップを読み込み（読めない場合は空として扱い、記録する）
 fn read_installed_map(app: &tauri::AppHandle) -> std::collections::HashMap<String, String> {
-    use std::fs::File;
-    use std::io::Read;
-    let mut map = std::collections::HashMap::new();
-    let p = installed_file_path(app);
-    if let Ok(mut f) = File::open(&p) {
-        let mut s = String::new();
-        if f.read_to_string(&mut s).is_ok() {
-            if let Ok(v) = serde_json::from_str::<serde_json::Value>(&s) {
-                if let Some(obj) = v.as_object() {
-                    for (k, vv) in obj.iter() {
-                        if let Some(val) = vv.as_str() {
-                            map.insert(k.clone(), val.to_string());
-                        }
-                    }
-                }
-            }
-        }
-    }
-    map
-}
-
-// インストール済みマップをファイルに書き込み
-fn write_installed_map(app: &tauri::AppHandle, map: &std::collections::HashMap<String, String>) -> Result<(), String> {
-    use std::fs::{create_dir_all, File};
-    use std::io::Write;
-    let base = app_config_dir(app);
-    let _ = create_dir_all(&base);
-    let p = installed_file_path(app);
-    let mut f = File::create(&p).map_err(|e| e.to_string())?;
-    f.write_all(serde_json::to_string_pretty(map).unwrap().as_bytes()).map_err(|e| e.to_string())?;
-    Ok(())
+    state::load(app).map(|s| s.installed_map()).unwrap_or_else(|e| {
+        log_error(app, &format!("Failed to read installed state: {}", e));
+        std::collections::HashMap::new()
+    })
 }
 
 // インストール済みマップ取得コマンド
 #[tauri::command]
 fn get_installed_map_cmd(app: tauri::AppHandle) -> Result<std::collections::HashMap<String, String>, String> {
-    Ok(read_installed_map(&app))
+    state::load(&app).map(|s| s.installed_map()).map_err(|e| e.to_string())
 }
 
 // インストール済みIDを追加するコマンド
 #[tauri::command]
 fn add_installed_id_cmd(app: tauri::AppHandle, id: String, version: Option<String>) -> Result<std::collections::HashMap<String, String>, String> {
-    let mut map = read_installed_map(&app);
-    map.insert(id, version.unwrap_or_default());
-    write_installed_map(&app, &map)?;
-    Ok(map)
+    state::update(&app, |s| {
+        s.packages.entry(id).or_default().version = version.unwrap_or_default();
+        s.installed_map()
+    })
+    .map_err(|e| e.to_string())
 }
 
 // インストール済みIDを削除するコマンド
 #[tauri::command]
 fn remove_installed_id_cmd(app: tauri::AppHandle, id: String) -> Result<std::collections::HashMap<String, String>, String> {
-    let mut map = read_installed_map(&app);
-    map.remove(&id);
-    write_installed_map(&app, &map)?;
-    Ok(map)
-}
-
-// ---------------------------
-// ダウンロード関連
-// ---------------------------
-
-/// ファイルをコピーする関数
-fn copy_item(src: &Path, dst: &Path) -> io::Result<usize> {
-    let mut count = 0;
-    // ファイル → ディレクトリ
-    if src.is_file() {
-        fs::create_dir_all(dst)?;
-        let file_name = src.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Failed to get file name"))?;
-        let dest_path = dst.join(file_name);
-        fs::copy(src, dest_path)?;
-        count += 1;
-        return Ok(count);
-    }
-    if src.is_dir() {
-        // ディレクトリ→ディレクトリ
-        fs::create_dir_all(dst)?;
-        for entry in WalkDir::new(src) {
-            let entry = entry?;
-            let path = entry.path();
-            let rel = path.strip_prefix(src).map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to calculate relative path"))?;
-            let dest_path: PathBuf = dst.join(rel);
-            if entry.file_type().is_dir() {
-                fs::create_dir_all(&dest_path)?;
-            } else {
-                if let Some(parent) = dest_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                fs::copy(path, &dest_path)?;
-                count += 1;
-            }
-        }
-        return Ok(count);
-    }
-
-    Err(io::Error::new(io::ErrorKind::Other, "Source is neither a file nor a directory"))
-}
-
-#[tauri::command]
-fn copy_item_js(src_str: String, dst_str: String) -> Result<usize, String> {
-    let src = PathBuf::from(src_str);
-    let dst = PathBuf::from(dst_str);
-    copy_item(&src, &dst).map_err(|e| e.to_string())
+    state::update(&app, |s| {
+        s.packages.remove(&id);
+        s.installed_map()
+    })
+    .map_err(|e| e.to_string())
 }
 
 // -----------------------
@@ -1389,9 +939,17 @@ pub fn run() {
                 app.deep_link().register_all().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
             }
 
+            // ダウンロード用の共有 HTTP クライアント
+            app.manage(download::DownloadManager::new(download::DownloadLimits::from_settings(&paths::current_settings(app.handle())))?);
+            // キャンセル・一時停止用のタスク登録表
+            app.manage(tasks::TaskRegistry::default());
+            // ダウンロード済みアーカイブのキャッシュ
+            app.manage(cache::PackageCache::new(app.handle(), paths::current_settings(app.handle()).package_cache_limit_mb));
             // 起動時に app.log を最新 1000 行に削減
             paths::init_settings(&app.handle())?;
             let _ = init_app(&app.handle());
+            // 前回中断されたインストールを確定・巻き戻す
+            installer::recover_interrupted(&app.handle());
             // paths::init_settings(&app.handle())?;
             // init_app()
             // // 重い処理は起動後にバックグラウンドへ
@@ -1406,21 +964,41 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             set_catalog_index,
             query_catalog_index,
-            extract_zip,
-            extract_7z_sfx,
+            extract::extract_zip,
+            extract::extract_7z_sfx,
+            extract::extract_archive,
+            extract::list_archive,
             detect_versions_map,
             log_cmd,
             calc_xxh3_hex,
             get_installed_map_cmd,
             add_installed_id_cmd,
             remove_installed_id_cmd,
-            drive_download_to_file,
-            download_file_to_path,
-            download_file_to_path_booth,
+            download::drive_download_to_file,
+            download::download_file_to_path,
+            download::download_file_to_path_booth,
+            download::download_from_source,
+            tasks::cancel_task,
+            download::cancel_download,
+            download::pause_download,
+            download::resume_download,
+            github::resolve_github_asset,
+            github::github_rate_limit,
+            cache::cache_list,
+            cache::cache_prune,
+            cache::cache_export,
             ensure_booth_auth_window,
             close_booth_auth_window,
             expand_macros,
-            copy_item_js,
+            copy::copy_item_js,
+            installer::install_package,
+            installer::install_plan,
+            installer::uninstall_package,
+            manifest::has_package_manifest,
+            resolver::resolve_install_plan,
+            state::get_installed_state,
+            state::update_package_state,
+            state::save_installed_snapshot,
             is_aviutl_running,
             launch_aviutl2,
             run_auo_setup,
@@ -1429,6 +1007,8 @@ pub fn run() {
             paths::default_aviutl2_root,
             paths::resolve_aviutl2_root,
             paths::get_app_dirs,
+            paths::update_download_settings,
+            paths::update_github_token,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
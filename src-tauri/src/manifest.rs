@@ -0,0 +1,265 @@
+// -----------------------
+// パッケージごとのファイル一覧（manifest）
+// -----------------------
+//
+// - インストールで作業フォルダの外に置いたファイル（journal の create / replace）を、サイズ・xxh3_128 と一緒に記録する
+//   インストール済みの状態（state）のパッケージごとの manifest として保存する
+// - 同じパッケージを入れ直した場合は、前回の一覧にあって今回は置かなかったファイルも引き継ぐ（古いバージョンのファイルを取り残さない）
+// - run / run_auo_setup で外部のインストーラが置いたファイルは記録されない
+// - アンインストールでは、記録したときと内容が同じファイルだけを消す
+//   内容が変わったもの（ユーザーが編集した設定ファイルなど）と、他のパッケージの一覧にもあるものは残して報告する
+//   インストール前からあったファイル（replaced）は、上書き前の内容がもう残っていないので消さずに報告する
+// - インストールで作ったフォルダは、空になったものだけ消す
+//   中身が残っているフォルダは意図して残すものとして報告せず、それ以外の理由で消せなかったものは failed に入れる
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use tauri::AppHandle;
+
+use crate::journal::Placed;
+use crate::state;
+
+/// 記録したファイル 1 件分
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    pub xxh3_128: String,
+    // インストール前から同じパスにファイルがあった（上書きした）か
+    #[serde(default)]
+    pub replaced: bool,
+}
+
+/// パッケージ 1 件分の一覧
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub id: String,
+    pub version: String,
+    pub installed_at: i64,
+    pub files: Vec<ManifestFile>,
+    // インストールで作ったフォルダ
+    #[serde(default)]
+    pub dirs: Vec<String>,
+}
+
+/// 一覧に従って削除した結果
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovalReport {
+    pub removed: Vec<String>,
+    // インストール後に内容が変わったため残したファイル
+    pub modified: Vec<String>,
+    // すでに無かったファイル
+    pub missing: Vec<String>,
+    // 他のパッケージの一覧にもあるため残したファイル
+    pub shared: Vec<String>,
+    // インストール前からあったため残したファイル
+    pub replaced: Vec<String>,
+    // 削除に失敗したファイル・フォルダ
+    pub failed: Vec<String>,
+}
+
+// Windows のパスは大文字小文字を区別しない
+fn path_key(path: &str) -> String {
+    path.to_lowercase()
+}
+
+/// id の一覧（無い・状態が読めない場合は None）
+pub fn load(app: &AppHandle, id: &str) -> Option<Manifest> {
+    state::load(app).ok()?.packages.remove(id)?.manifest
+}
+
+/// すべてのパッケージの一覧
+pub fn load_all(app: &AppHandle) -> Vec<Manifest> {
+    state::load(app).map(|s| s.packages.into_values().filter_map(|p| p.manifest).collect()).unwrap_or_default()
+}
+
+/// (xxh3_128, サイズ)
+fn hash_file(path: &Path) -> io::Result<(String, u64)> {
+    let mut f = File::open(path)?;
+    let mut xxh3 = xxhash_rust::xxh3::Xxh3::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut size = 0u64;
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        xxh3.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((format!("{:032x}", xxh3.digest128()), size))
+}
+
+/// インストールで置いたファイルをハッシュして一覧を作る（previous は同じパッケージの前回の一覧）
+pub fn build(id: &str, version: &str, placed: &Placed, previous: Option<Manifest>) -> io::Result<Manifest> {
+    let previous = previous.unwrap_or_default();
+    let replaced_before: HashMap<String, bool> = previous.files.iter().map(|f| (path_key(&f.path), f.replaced)).collect();
+    let mut manifest = Manifest {
+        id: id.to_string(),
+        version: version.to_string(),
+        installed_at: chrono::Utc::now().timestamp(),
+        files: Vec::with_capacity(placed.files.len()),
+        dirs: placed.dirs.iter().map(|d| d.to_string_lossy().into_owned()).collect(),
+    };
+    for (path, replaced) in &placed.files {
+        // 後のステップで消されたファイルは記録しない
+        let (xxh3_128, size) = match hash_file(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let path = path.to_string_lossy().into_owned();
+        // 前回のバージョンを上書きした場合は、前回の記録でインストール前からあったかを判断する
+        let replaced = replaced_before.get(&path_key(&path)).copied().unwrap_or(*replaced);
+        manifest.files.push(ManifestFile { path, size, xxh3_128, replaced });
+    }
+    let current: HashSet<String> = manifest.files.iter().map(|f| path_key(&f.path)).collect();
+    manifest.files.extend(previous.files.into_iter().filter(|f| !current.contains(&path_key(&f.path)) && Path::new(&f.path).exists()));
+    let dirs: HashSet<String> = manifest.dirs.iter().map(|d| path_key(d)).collect();
+    manifest.dirs.extend(previous.dirs.into_iter().filter(|d| !dirs.contains(&path_key(d)) && Path::new(d).is_dir()));
+    Ok(manifest)
+}
+
+/// 一覧のファイルのうち、記録したときと内容が同じで他のパッケージと共有していないものを消す
+/// - others は他のパッケージの一覧
+pub fn remove_files(manifest: &Manifest, others: &[Manifest]) -> RemovalReport {
+    let shared: HashSet<String> = others.iter().filter(|m| m.id != manifest.id).flat_map(|m| m.files.iter().map(|f| path_key(&f.path))).collect();
+    let mut report = RemovalReport::default();
+    for file in &manifest.files {
+        let path = Path::new(&file.path);
+        if shared.contains(&path_key(&file.path)) {
+            report.shared.push(file.path.clone());
+            continue;
+        }
+        if file.replaced {
+            report.replaced.push(file.path.clone());
+            continue;
+        }
+        let list = match hash_file(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => &mut report.missing,
+            Err(_) => &mut report.failed,
+            Ok((xxh3_128, size)) if size != file.size || xxh3_128 != file.xxh3_128 => &mut report.modified,
+            Ok(_) => match fs::remove_file(path) {
+                Ok(()) => &mut report.removed,
+                Err(_) => &mut report.failed,
+            },
+        };
+        list.push(file.path.clone());
+    }
+    // 子のフォルダから順に、空のものだけ消える
+    let mut dirs: Vec<&String> = manifest.dirs.iter().collect();
+    dirs.sort_by_key(|d| std::cmp::Reverse(Path::new(d).components().count()));
+    for dir in dirs {
+        match fs::remove_dir(dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            // 残したファイルや後から置かれたファイルが入っている
+            Err(_) if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) => {}
+            Err(_) => report.failed.push(dir.clone()),
+        }
+    }
+    report
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// id の manifest があるか（カタログに uninstall が無くても manifest でアンインストールできる）
+#[tauri::command]
+pub fn has_package_manifest(app: AppHandle, id: String) -> bool {
+    load(&app, &id).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(path: &Path, replaced: bool) -> ManifestFile {
+        let (xxh3_128, size) = hash_file(path).unwrap();
+        ManifestFile { path: path.to_string_lossy().into_owned(), size, xxh3_128, replaced }
+    }
+
+    fn manifest(id: &str, files: Vec<ManifestFile>, dirs: &[&Path]) -> Manifest {
+        Manifest {
+            id: id.to_string(),
+            version: "1.0".to_string(),
+            files,
+            dirs: dirs.iter().map(|d| d.to_string_lossy().into_owned()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(paths: &[String]) -> Vec<String> {
+        paths.iter().map(|p| Path::new(p).file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn remove_files_keeps_what_the_package_does_not_own() {
+        let tmp = tempfile::tempdir().unwrap();
+        let plugins = tmp.path().join("plugins");
+        let empty = plugins.join("empty");
+        fs::create_dir_all(&empty).unwrap();
+        let path = |name: &str| plugins.join(name);
+        for name in ["own.auf", "edited.ini", "shared.dll", "preexisting.auf", "gone.auf"] {
+            fs::write(path(name), name).unwrap();
+        }
+        let files = ["own.auf", "edited.ini", "shared.dll", "gone.auf"].iter().map(|n| entry(&path(n), false)).chain([entry(&path("preexisting.auf"), true)]).collect();
+        let target = manifest("pkg", files, &[&plugins, &empty]);
+        let other = manifest("other", vec![entry(&path("shared.dll"), false)], &[]);
+        fs::write(path("edited.ini"), "edited by user").unwrap();
+        fs::remove_file(path("gone.auf")).unwrap();
+
+        let report = remove_files(&target, &[target.clone(), other]);
+        assert_eq!(names(&report.removed), ["own.auf"]);
+        assert_eq!(names(&report.modified), ["edited.ini"]);
+        assert_eq!(names(&report.shared), ["shared.dll"]);
+        assert_eq!(names(&report.missing), ["gone.auf"]);
+        // インストール前からあったファイルは消さない
+        assert_eq!(names(&report.replaced), ["preexisting.auf"]);
+        assert!(path("preexisting.auf").exists());
+        // 空のフォルダだけ消え、ファイルが残っているフォルダは失敗として扱わない
+        assert!(!empty.exists());
+        assert!(plugins.exists());
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn remove_files_reports_directories_that_could_not_be_removed() {
+        let tmp = tempfile::tempdir().unwrap();
+        // フォルダとして記録したパスがファイルに置き換わっている
+        let not_a_dir: PathBuf = tmp.path().join("plugins");
+        fs::write(&not_a_dir, b"file").unwrap();
+        let report = remove_files(&manifest("pkg", Vec::new(), &[&not_a_dir]), &[]);
+        assert_eq!(report.failed, [not_a_dir.to_string_lossy().into_owned()]);
+        assert!(not_a_dir.exists());
+    }
+
+    #[test]
+    fn build_keeps_replaced_from_the_first_install() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (tmp.path().join("a.auf"), tmp.path().join("b.auf"));
+        fs::write(&a, b"a").unwrap();
+        fs::write(&b, b"b").unwrap();
+        let placed = Placed { files: vec![(a.clone(), true)], dirs: Vec::new() };
+        let first = build("pkg", "1.0", &placed, None).unwrap();
+        assert!(first.files[0].replaced);
+
+        // 入れ直し（前回のファイルを上書き）でも、インストール前からあったかは前回の記録に従う
+        let placed = Placed { files: vec![(a.clone(), true), (b.clone(), false)], dirs: Vec::new() };
+        let second = build("pkg", "1.1", &placed, Some(first)).unwrap();
+        let replaced: Vec<(String, bool)> = second.files.iter().map(|f| (f.path.clone(), f.replaced)).collect();
+        assert_eq!(
+            replaced,
+            [
+                (a.to_string_lossy().into_owned(), true),
+                (b.to_string_lossy().into_owned(), false)
+            ]
+        );
+    }
+}
@@ -25,6 +25,11 @@ pub struct Settings {
     pub package_state_opt_out: bool, // 禁用匿名统计发送
     pub app_version: String,       // 本应用程序的版本（用于UpdateChecker更新）
     pub catalog_exe_path: PathBuf, // 本软件的执行文件路径（用于UpdateChecker）
+    pub download_max_parallel: u32,   // 同时下载数（0 为默认值 3）
+    pub download_per_host_limit: u32, // 同一主机的同时下载数（0 为默认值 2）
+    pub download_bandwidth_limit: u64, // 下载总带宽上限（字节/秒，0 为不限制）
+    pub github_token: String,          // GitHub API 的个人访问令牌（可选，用于放宽速率限制）
+    pub package_cache_limit_mb: u64,   // 下载包缓存的容量上限（MB，0 为默认值 2048）
 }
 
 // 应用程序使用的目录列表
@@ -81,6 +86,11 @@ pub fn init_settings(app: &AppHandle) -> std::io::Result<()> {
     Ok(())
 }
 
+// 读取当前的settings.json（无法获取配置目录时返回默认值）
+pub fn current_settings(app: &AppHandle) -> Settings {
+    app.path().app_config_dir().map(|dir| Settings::load_from_file(dir.join("settings.json"))).unwrap_or_default()
+}
+
 // 获取路径的函数（用法：paths::dirs().catalog_config_dir等）
 pub fn dirs() -> Arc<AppDirs> {
     APP_DIR.get().expect("init_settings() must be called first").load_full()
@@ -257,6 +267,36 @@ pub async fn update_settings(app: AppHandle, aviutl2_root: String, is_portable_m
     finalize_settings(&app, &mut settings, &settings_path, &catalog_config_dir).map_err(|e| e.to_string())
 }
 
+// 保存下载相关的设置，并立即应用到下载队列和下载包缓存
+#[tauri::command]
+pub fn update_download_settings(app: AppHandle, max_parallel: u32, per_host_limit: u32, bandwidth_limit: u64, cache_limit_mb: u64) -> Result<(), String> {
+    let catalog_config_dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let settings_path = catalog_config_dir.join("settings.json");
+    let mut settings = Settings::load_from_file(&settings_path);
+    settings.download_max_parallel = max_parallel;
+    settings.download_per_host_limit = per_host_limit;
+    settings.download_bandwidth_limit = bandwidth_limit;
+    settings.package_cache_limit_mb = cache_limit_mb;
+    settings.save_to_file(&settings_path).map_err(|e| e.to_string())?;
+    if let Some(manager) = app.try_state::<crate::download::DownloadManager>() {
+        manager.set_limits(crate::download::DownloadLimits::from_settings(&settings));
+    }
+    if let Some(cache) = app.try_state::<crate::cache::PackageCache>() {
+        cache.set_limit_mb(cache_limit_mb);
+    }
+    Ok(())
+}
+
+// 保存 GitHub API 令牌（空字符串表示不使用）
+#[tauri::command]
+pub fn update_github_token(app: AppHandle, token: String) -> Result<(), String> {
+    let catalog_config_dir = app.path().app_config_dir().map_err(|e| e.to_string())?;
+    let settings_path = catalog_config_dir.join("settings.json");
+    let mut settings = Settings::load_from_file(&settings_path);
+    settings.github_token = token.trim().to_string();
+    settings.save_to_file(&settings_path).map_err(|e| e.to_string())
+}
+
 // 返回aviutl2_root的默认值
 #[tauri::command]
 pub fn default_aviutl2_root() -> Result<String, String> {
@@ -0,0 +1,168 @@
+// -----------------------
+// ファイルの安全な配置
+// -----------------------
+//
+// - 書き込みは置き場所と同じフォルダの一時ファイルに行い、fsync してから rename で置き換える
+//   （途中でプロセスが落ちたりディスクが一杯になっても、書きかけのファイルが最終的なパスに残らない）
+// - 既存のファイルは置き換える・削除する前に同じフォルダへ退避しておき、失敗したら rollback で元に戻す
+//   成功したら commit で退避したファイルを消す。commit せずに破棄した場合も rollback する
+// - 一時ファイル・退避ファイルは同じフォルダに置くので、rename は常に同じボリューム内で完結する
+// - journal を渡した場合は、フォルダ・一時ファイルの作成と置き換え・削除を行う前に記録する
+//   commit しても退避したファイルは消さず、インストール全体の確定までジャーナルに任せる
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::journal::{Journal, Record};
+
+// 一時ファイル名の重複を避けるための連番
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 置き場所と同じフォルダの一時ファイル名（.<ファイル名>.<pid>-<連番>.<suffix>）
+pub fn sibling_path(dest: &Path, suffix: &str) -> PathBuf {
+    let name = dest.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    dest.with_file_name(format!(".{}.{}-{}.{}", name, std::process::id(), n, suffix))
+}
+
+/// bytes を同じフォルダの一時ファイルに書いて fsync してから path に置き換える（状態ファイルなど 1 つだけ書くもの向け）
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = sibling_path(path, "tmp");
+    let result = File::create(&tmp).and_then(|mut f| f.write_all(bytes).and_then(|_| f.sync_all())).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// 一時ファイルから置き換えたファイルの記録
+#[derive(Default)]
+pub struct Placement {
+    // (置き場所, 退避した元のファイル, ジャーナルに記録したか)。削除したファイルは置き場所が空のまま退避先だけを持つ
+    placed: Vec<(PathBuf, Option<PathBuf>, bool)>,
+    journal: Option<Arc<Journal>>,
+}
+
+impl Placement {
+    /// 変更をジャーナルに記録しながら置く
+    pub fn with_journal(journal: Option<Arc<Journal>>) -> Self {
+        Self { placed: Vec::new(), journal }
+    }
+
+    // path への変更を記録するジャーナル
+    fn journal_for(&self, path: &Path) -> Option<&Journal> {
+        self.journal.as_deref().filter(|j| j.tracks(path))
+    }
+
+    /// フォルダを作る（ジャーナルがあれば、無かった階層を上から順に記録する）
+    pub fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        if let Some(journal) = self.journal_for(dir) {
+            let missing: Vec<&Path> = dir.ancestors().take_while(|p| !p.as_os_str().is_empty() && !p.exists()).collect();
+            for path in missing.into_iter().rev() {
+                journal.record(&Record::Dir { path: path.to_path_buf() })?;
+            }
+        }
+        fs::create_dir_all(dir)
+    }
+
+    /// dest と同じフォルダに書き込み用の一時ファイルを作る（親フォルダが無ければ作る）
+    pub fn create_temp(&self, dest: &Path) -> io::Result<(PathBuf, File)> {
+        if let Some(parent) = dest.parent() {
+            self.create_dir_all(parent)?;
+        }
+        let tmp = sibling_path(dest, "tmp");
+        if let Some(journal) = self.journal_for(&tmp) {
+            journal.record(&Record::Temp { path: tmp.clone() })?;
+        }
+        let file = OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+        Ok((tmp, file))
+    }
+
+    /// src の内容を dest 用の一時ファイルにコピーして fsync する（失敗時は一時ファイルを消す）
+    pub fn stage_copy(&self, src: &Path, dest: &Path) -> io::Result<PathBuf> {
+        let (tmp, mut file) = self.create_temp(dest)?;
+        let result = File::open(src).and_then(|mut reader| io::copy(&mut reader, &mut file)).and_then(|_| file.sync_all());
+        match result {
+            Ok(()) => Ok(tmp),
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+
+    /// fsync 済みの一時ファイルを dest に置く（既存のファイルは同じフォルダに退避する）
+    pub fn place(&mut self, tmp: &Path, dest: &Path) -> io::Result<()> {
+        let exists = fs::symlink_metadata(dest).map(|m| m.is_file()).unwrap_or(false);
+        let old = exists.then(|| sibling_path(dest, "old"));
+        let journal = self.journal_for(dest);
+        if let Some(journal) = journal {
+            let record = match &old {
+                Some(old) => Record::Replace { path: dest.to_path_buf(), preimage: old.clone() },
+                None => Record::Create { path: dest.to_path_buf() },
+            };
+            journal.record(&record)?;
+        }
+        let journaled = journal.is_some();
+        if let Some(old) = &old {
+            fs::rename(dest, old)?;
+        }
+        if let Err(e) = fs::rename(tmp, dest) {
+            if let Some(old) = &old {
+                let _ = fs::rename(old, dest);
+            }
+            return Err(e);
+        }
+        self.placed.push((dest.to_path_buf(), old, journaled));
+        Ok(())
+    }
+
+    /// path のファイルを削除する（同じフォルダに退避し、rollback で戻せるようにする）
+    pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let old = sibling_path(path, "old");
+        let journal = self.journal_for(path);
+        if let Some(journal) = journal {
+            journal.record(&Record::Remove { path: path.to_path_buf(), preimage: old.clone() })?;
+        }
+        let journaled = journal.is_some();
+        fs::rename(path, &old)?;
+        self.placed.push((path.to_path_buf(), Some(old), journaled));
+        Ok(())
+    }
+
+    /// 置いたファイルを確定し、退避した元のファイルを消す（ジャーナルに記録したものはインストールの確定時に消える）
+    pub fn commit(mut self) {
+        for (_, old, journaled) in std::mem::take(&mut self.placed) {
+            if let (Some(old), false) = (old, journaled) {
+                let _ = fs::remove_file(old);
+            }
+        }
+    }
+
+    /// 置いたファイルを消して元のファイルを戻す（同じパスに複数回置いた場合もあるので新しいものから順に）
+    pub fn rollback(mut self) {
+        self.undo();
+    }
+
+    fn undo(&mut self) {
+        for (dest, old, _) in std::mem::take(&mut self.placed).into_iter().rev() {
+            let _ = fs::remove_file(&dest);
+            if let Some(old) = old {
+                let _ = fs::rename(&old, &dest);
+            }
+        }
+    }
+}
+
+impl Drop for Placement {
+    fn drop(&mut self) {
+        self.undo();
+    }
+}
@@ -0,0 +1,344 @@
+// -----------------------
+// 依存関係の解決（dependencies からインストールの順番を決める）
+// -----------------------
+//
+// - カタログのインデックス（set_catalog_index）の dependencies をたどり、依存されるものが先に来る順番を返す
+// - 依存は "id" か "id>=version"。バージョンの新旧はカタログの versions の並び（最後が latest-version）で判断する
+// - インストール済み（installed-state）で必要なバージョンを満たす依存は plan に含めない。指定されたパッケージは入れ直し・更新のため常に含める
+// - カタログに無い依存、カタログに無いバージョン（latest-version より新しいなど）を求める依存、
+//   インストーラが無く必要なバージョンがインストールされていない依存は、まとめて DEPENDENCY_UNRESOLVED で返す
+// - 循環している場合は DEPENDENCY_CYCLE でその経路を返す
+// - plan の実行は installer::install_plan が 1 つのトランザクションとしてまとめて行う
+
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::AppHandle;
+use thiserror::Error;
+
+use crate::state;
+
+/// 解決できなかった依存
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Unresolved {
+    pub id: String,
+    // 依存しているパッケージ（指定されたパッケージ自体の場合は None）
+    pub required_by: Option<String>,
+    // 必要なバージョン（"id>=version" の version）
+    pub version: Option<String>,
+    // unknown（カタログに無い）/ unsatisfiable-version（必要なバージョンがカタログに無い）/ not-installable（インストーラが無い）
+    pub reason: &'static str,
+}
+
+/// 依存関係の解決のエラー（Display の先頭はフロントで判定するためのコード）
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("DEPENDENCY_CYCLE: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+    #[error("DEPENDENCY_UNRESOLVED: {}", describe_unresolved(.0))]
+    Unresolved(Vec<Unresolved>),
+    #[error("DEPENDENCY_CATALOG_UNAVAILABLE: catalog index lock poisoned")]
+    CatalogUnavailable,
+}
+
+impl ResolveError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolveError::Cycle(_) => "DEPENDENCY_CYCLE",
+            ResolveError::Unresolved(_) => "DEPENDENCY_UNRESOLVED",
+            ResolveError::CatalogUnavailable => "DEPENDENCY_CATALOG_UNAVAILABLE",
+        }
+    }
+}
+
+impl Serialize for ResolveError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("ResolveError", 4)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("cycle", if let ResolveError::Cycle(ids) = self { ids.as_slice() } else { &[] })?;
+        s.serialize_field("unresolved", if let ResolveError::Unresolved(list) = self { list.as_slice() } else { &[] })?;
+        s.end()
+    }
+}
+
+fn describe_unresolved(list: &[Unresolved]) -> String {
+    list.iter()
+        .map(|u| {
+            let id = match &u.version {
+                Some(version) => format!("{}>={}", u.id, version),
+                None => u.id.clone(),
+            };
+            match &u.required_by {
+                Some(by) => format!("{} ({}, required by {})", id, u.reason, by),
+                None => format!("{} ({})", id, u.reason),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// plan の 1 件
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanStep {
+    pub id: String,
+    // 指定されたパッケージか（false は足りない依存として追加したもの）
+    pub requested: bool,
+    // このパッケージに依存している plan 内のパッケージ
+    pub required_by: Vec<String>,
+}
+
+/// インストールの順番
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallPlan {
+    // 依存されるものが先
+    pub steps: Vec<PlanStep>,
+    // インストール済みのため plan に含めなかった依存
+    pub satisfied: Vec<String>,
+}
+
+/// 深さ優先でたどり、依存を先に plan に追加する
+struct Resolver<'a> {
+    catalog: HashMap<&'a str, &'a crate::IndexItem>,
+    // id → インストール済みのバージョン
+    installed: &'a HashMap<String, String>,
+    // たどっている途中の経路（循環の検出用）
+    path: Vec<String>,
+    // plan に追加済みの ID と位置
+    added: HashMap<String, usize>,
+    plan: InstallPlan,
+    unresolved: Vec<Unresolved>,
+}
+
+/// "id>=version" を (id, version) に分ける
+fn parse_dependency(dependency: &str) -> (&str, Option<&str>) {
+    match dependency.split_once(">=") {
+        Some((id, version)) => (id.trim(), Some(version.trim()).filter(|v| !v.is_empty())),
+        None => (dependency.trim(), None),
+    }
+}
+
+/// カタログの versions での位置（新しいほど大きい。載っていなければ None）
+fn version_rank(item: &crate::IndexItem, version: &str) -> Option<usize> {
+    item.versions.iter().position(|v| v == version)
+}
+
+impl Resolver<'_> {
+    fn visit(&mut self, dependency: &str, required_by: Option<&str>) -> Result<(), ResolveError> {
+        let (id, version) = parse_dependency(dependency);
+        let unresolved = |reason| Unresolved {
+            id: id.to_string(),
+            required_by: required_by.map(str::to_string),
+            version: version.map(str::to_string),
+            reason,
+        };
+        let Some(item) = self.catalog.get(id).copied() else {
+            self.unresolved.push(unresolved("unknown"));
+            return Ok(());
+        };
+        // 必要なバージョンがカタログに無ければ、インストールしても満たせない
+        let required = match version {
+            Some(version) => match version_rank(item, version) {
+                Some(rank) => Some(rank),
+                None => {
+                    self.unresolved.push(unresolved("unsatisfiable-version"));
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        if let Some(&index) = self.added.get(id) {
+            let step = &mut self.plan.steps[index];
+            match required_by {
+                Some(by) if !step.required_by.iter().any(|r| r == by) => step.required_by.push(by.to_string()),
+                Some(_) => {}
+                None => step.requested = true,
+            }
+            return Ok(());
+        }
+        if let Some(start) = self.path.iter().position(|p| p == id) {
+            let mut cycle = self.path[start..].to_vec();
+            cycle.push(id.to_string());
+            return Err(ResolveError::Cycle(cycle));
+        }
+        // インストール済みのバージョンが必要なバージョン以上なら入れ直さない（カタログに無いバージョンは満たさないものとする）
+        let satisfied = self.installed.get(id).is_some_and(|installed| match required {
+            Some(required) => version_rank(item, installed).is_some_and(|rank| rank >= required),
+            None => true,
+        });
+        if required_by.is_some() && satisfied {
+            if !self.plan.satisfied.iter().any(|s| s == id) {
+                self.plan.satisfied.push(id.to_string());
+            }
+            return Ok(());
+        }
+        if !item.installable {
+            self.unresolved.push(unresolved("not-installable"));
+            return Ok(());
+        }
+        self.path.push(id.to_string());
+        for dependency in &item.dependencies {
+            self.visit(dependency, Some(id))?;
+        }
+        self.path.pop();
+        self.added.insert(id.to_string(), self.plan.steps.len());
+        self.plan.steps.push(PlanStep {
+            id: id.to_string(),
+            requested: required_by.is_none(),
+            required_by: required_by.map(str::to_string).into_iter().collect(),
+        });
+        Ok(())
+    }
+}
+
+/// ids とその足りない依存をインストールする順番を決める
+fn resolve(ids: &[String], items: &[crate::IndexItem], installed: &HashMap<String, String>) -> Result<InstallPlan, ResolveError> {
+    let mut resolver = Resolver {
+        catalog: items.iter().map(|item| (item.id.as_str(), item)).collect(),
+        installed,
+        path: Vec::new(),
+        added: HashMap::new(),
+        plan: InstallPlan::default(),
+        unresolved: Vec::new(),
+    };
+    for id in ids.iter().map(|id| id.trim()).filter(|id| !id.is_empty()) {
+        resolver.visit(id, None)?;
+    }
+    if !resolver.unresolved.is_empty() {
+        return Err(ResolveError::Unresolved(resolver.unresolved));
+    }
+    // 依存としては済んでいたが、後から指定されて plan に入ったもの
+    let Resolver { mut plan, added, .. } = resolver;
+    plan.satisfied.retain(|id| !added.contains_key(id));
+    Ok(plan)
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// ids（とその足りない依存）をインストールする順番を返す
+#[tauri::command]
+pub fn resolve_install_plan(app: AppHandle, ids: Vec<String>) -> Result<InstallPlan, ResolveError> {
+    let installed = match state::load(&app) {
+        Ok(state) => state.installed_map(),
+        Err(e) => {
+            // インストール済みが分からない場合は、依存もすべて plan に入れる
+            crate::log_error(&app, &format!("[resolver] failed to read installed state: {}", e));
+            HashMap::new()
+        }
+    };
+    let catalog = crate::CATALOG.read().map_err(|_| ResolveError::CatalogUnavailable)?;
+    resolve(&ids, &catalog, &installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IndexItem;
+
+    fn item(id: &str, dependencies: &[&str], versions: &[&str]) -> IndexItem {
+        IndexItem {
+            id: id.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            installable: true,
+            ..Default::default()
+        }
+    }
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn installed(list: &[(&str, &str)]) -> HashMap<String, String> {
+        list.iter().map(|(id, v)| (id.to_string(), v.to_string())).collect()
+    }
+
+    fn order(plan: &InstallPlan) -> Vec<&str> {
+        plan.steps.iter().map(|s| s.id.as_str()).collect()
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let items = [
+            item("app", &["ui", "core"], &["1.0"]),
+            item("ui", &["core"], &["1.0"]),
+            item("core", &[], &["1.0"]),
+            item("extra", &["core"], &["1.0"]),
+        ];
+        let plan = resolve(&ids(&["app", "extra"]), &items, &HashMap::new()).unwrap();
+        assert_eq!(order(&plan), ["core", "ui", "app", "extra"]);
+        assert!(plan.steps[2].requested && plan.steps[3].requested && !plan.steps[0].requested);
+        assert_eq!(plan.steps[0].required_by, ["ui", "app", "extra"]);
+
+        // インストール済みの依存は含めないが、指定されたものは入れ直す
+        let plan = resolve(&ids(&["app", "core"]), &items, &installed(&[("core", "1.0"), ("ui", "1.0")])).unwrap();
+        assert_eq!(order(&plan), ["app", "core"]);
+        assert_eq!(plan.satisfied, ["ui"]);
+    }
+
+    #[test]
+    fn cycles_are_reported_with_their_path() {
+        let items = [
+            item("a", &["b"], &["1.0"]),
+            item("b", &["c"], &["1.0"]),
+            item("c", &["a"], &["1.0"]),
+        ];
+        let Err(ResolveError::Cycle(cycle)) = resolve(&ids(&["a"]), &items, &HashMap::new()) else {
+            panic!("expected DEPENDENCY_CYCLE");
+        };
+        assert_eq!(cycle, ["a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn unknown_and_not_installable_are_collected() {
+        let mut manual = item("manual", &[], &["1.0"]);
+        manual.installable = false;
+        let items = [item("app", &["missing", "manual"], &["1.0"]), manual];
+        let Err(ResolveError::Unresolved(list)) = resolve(&ids(&["app", "nothing"]), &items, &HashMap::new()) else {
+            panic!("expected DEPENDENCY_UNRESOLVED");
+        };
+        let reasons: Vec<(&str, Option<&str>, &str)> = list.iter().map(|u| (u.id.as_str(), u.required_by.as_deref(), u.reason)).collect();
+        assert_eq!(
+            reasons,
+            [
+                ("missing", Some("app"), "unknown"),
+                ("manual", Some("app"), "not-installable"),
+                ("nothing", None, "unknown")
+            ]
+        );
+
+        // インストーラが無くても、インストール済みなら依存として満たす
+        let plan = resolve(&ids(&["app"]), &items[..], &installed(&[("manual", "1.0"), ("missing", "1.0")]));
+        assert!(matches!(plan, Err(ResolveError::Unresolved(list)) if list.len() == 1 && list[0].id == "missing"));
+    }
+
+    #[test]
+    fn required_versions_follow_the_catalog_order() {
+        let items = [
+            item("app", &["core>=2.0beta1"], &["1.0"]),
+            item("core", &[], &["1.9", "2.0beta1", "2.0"]),
+            item("future", &["core>=3.0"], &["1.0"]),
+        ];
+        // 古いバージョンがインストールされていれば入れ直す
+        let plan = resolve(&ids(&["app"]), &items, &installed(&[("core", "1.9")])).unwrap();
+        assert_eq!(order(&plan), ["core", "app"]);
+        // 必要なバージョン以降なら満たす
+        for version in ["2.0beta1", "2.0"] {
+            let plan = resolve(&ids(&["app"]), &items, &installed(&[("core", version)])).unwrap();
+            assert_eq!(order(&plan), ["app"]);
+            assert_eq!(plan.satisfied, ["core"]);
+        }
+
+        // latest-version より新しいバージョンは満たせない
+        let Err(ResolveError::Unresolved(list)) = resolve(&ids(&["future"]), &items, &HashMap::new()) else {
+            panic!("expected DEPENDENCY_UNRESOLVED");
+        };
+        assert_eq!((list[0].id.as_str(), list[0].version.as_deref(), list[0].reason), ("core", Some("3.0"), "unsatisfiable-version"));
+        assert_eq!(ResolveError::Unresolved(list).to_string(), "DEPENDENCY_UNRESOLVED: core>=3.0 (unsatisfiable-version, required by future)");
+    }
+}
@@ -0,0 +1,447 @@
+// -----------------------
+// インストール済みの状態（AppConfig/installed-state.json）
+// -----------------------
+//
+// - パッケージごとのバージョン・インストール日時・取得元・manifest・固定・メモを 1 つの JSON に持つ
+//   先頭の schema_version で形式を判定し、古い形式は MIGRATIONS で順に変換してから読む
+// - 以前の installed.json（id → バージョン）は、最初に書き込むときに取り込む
+//   取り込んだ installed.json は installed.json.migrated に名前を変えて残す
+// - 書き込みは一時ファイルから置き換え、置き換える前の内容を state-backups/ に残す（新しいものから BACKUP_KEEP 件）
+//   内容が変わらない更新は書き込まない（バックアップが同じ内容で入れ替わらないように）
+// - 読めない（壊れている）場合は空として扱わずにバックアップから読む。バックアップも読めなければエラーにして上書きしない
+// - get_installed_map_cmd などの id → バージョンのマップは installed_map で作る互換の表示
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::AppHandle;
+use thiserror::Error;
+
+use crate::manifest::Manifest;
+use crate::placement;
+
+/// 現在の形式（1 は installed.json の id → バージョンのマップ）
+pub const SCHEMA_VERSION: u64 = 2;
+
+// 残すバックアップの数
+const BACKUP_KEEP: usize = 5;
+
+// schema_version n → n + 1 の変換（n = 1 から順に並べる）
+const MIGRATIONS: [fn(serde_json::Value) -> Result<serde_json::Value, StateError>; (SCHEMA_VERSION - 1) as usize] = [migrate_v1_to_v2];
+
+// 読み込みから書き込みまでを 1 つずつ行う
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// 状態の読み書きのエラー（Display の先頭はフロントで判定するためのコード）
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("STATE_CORRUPT: {0}")]
+    Corrupt(String),
+    #[error("STATE_UNSUPPORTED_VERSION: schema_version {0} is newer than supported ({SCHEMA_VERSION})")]
+    UnsupportedVersion(u64),
+    #[error("STATE_UNKNOWN_PACKAGE: {0} is not installed")]
+    UnknownPackage(String),
+    #[error("STATE_IO_ERROR: {0}")]
+    Io(String),
+}
+
+impl StateError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            StateError::Corrupt(_) => "STATE_CORRUPT",
+            StateError::UnsupportedVersion(_) => "STATE_UNSUPPORTED_VERSION",
+            StateError::UnknownPackage(_) => "STATE_UNKNOWN_PACKAGE",
+            StateError::Io(_) => "STATE_IO_ERROR",
+        }
+    }
+}
+
+impl Serialize for StateError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("StateError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> Self {
+        StateError::Io(e.to_string())
+    }
+}
+
+/// パッケージ 1 件分の状態
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct PackageState {
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed_at: Option<i64>,
+    // インストール元（installer.source の URL / drive:<id> など）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    // インストールで置いたファイル
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+    // 更新の対象から外す
+    pub pinned: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl PackageState {
+    // 検出されなくても残す（manifest のファイルを消せるように、ユーザーの設定を失わないように）
+    fn keep_when_undetected(&self) -> bool {
+        self.manifest.is_some() || self.pinned || self.notes.is_some()
+    }
+}
+
+/// installed-state.json の内容
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InstalledState {
+    pub schema_version: u64,
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageState>,
+}
+
+impl Default for InstalledState {
+    fn default() -> Self {
+        Self { schema_version: SCHEMA_VERSION, packages: BTreeMap::new() }
+    }
+}
+
+impl InstalledState {
+    /// id → バージョン（以前の installed.json と同じ形）
+    pub fn installed_map(&self) -> HashMap<String, String> {
+        self.packages.iter().map(|(id, p)| (id.clone(), p.version.clone())).collect()
+    }
+}
+
+/// 状態を置くフォルダ（設定フォルダ）とログの出力先
+struct Store<'a> {
+    dir: PathBuf,
+    app: Option<&'a AppHandle>,
+}
+
+impl<'a> Store<'a> {
+    fn new(app: &'a AppHandle) -> Self {
+        Self { dir: crate::app_config_dir(app), app: Some(app) }
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir.join("installed-state.json")
+    }
+
+    fn backups_dir(&self) -> PathBuf {
+        self.dir.join("state-backups")
+    }
+
+    fn legacy_map_path(&self) -> PathBuf {
+        self.dir.join("installed.json")
+    }
+
+    fn log_error(&self, message: &str) {
+        if let Some(app) = self.app {
+            crate::log_error(app, message);
+        }
+    }
+}
+
+// -----------------------
+// 形式の変換
+// -----------------------
+
+/// JSON を読み、現在の形式まで変換する
+fn parse(bytes: &[u8]) -> Result<InstalledState, StateError> {
+    let mut value: serde_json::Value = serde_json::from_slice(bytes).map_err(|e| StateError::Corrupt(e.to_string()))?;
+    if !value.is_object() {
+        return Err(StateError::Corrupt("top level is not an object".to_string()));
+    }
+    let version = match value.get("schema_version") {
+        Some(v) => v.as_u64().ok_or_else(|| StateError::Corrupt(format!("invalid schema_version: {v}")))?,
+        None => 1,
+    };
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    for migrate in &MIGRATIONS[(version - 1) as usize..] {
+        value = migrate(value)?;
+    }
+    serde_json::from_value(value).map_err(|e| StateError::Corrupt(e.to_string()))
+}
+
+/// 1 → 2: { id: version } を packages に移す（文字列でない値は以前と同じく読み飛ばす）
+fn migrate_v1_to_v2(value: serde_json::Value) -> Result<serde_json::Value, StateError> {
+    let serde_json::Value::Object(map) = value else {
+        return Err(StateError::Corrupt("installed map is not an object".to_string()));
+    };
+    let packages: serde_json::Map<String, serde_json::Value> =
+        map.into_iter().filter_map(|(id, v)| v.as_str().map(|version| (id, serde_json::json!({ "version": version })))).collect();
+    Ok(serde_json::json!({ "schema_version": 2, "packages": packages }))
+}
+
+// -----------------------
+// 読み書き
+// -----------------------
+
+impl Store<'_> {
+    /// 新しいものから順に並べたバックアップ
+    fn backups(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(self.backups_dir()) else {
+            return Vec::new();
+        };
+        let mut list: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.extension().and_then(|x| x.to_str()) == Some("json")).collect();
+        // ファイル名の日時で並ぶ
+        list.sort();
+        list.reverse();
+        list
+    }
+
+    /// 状態を読む（まだ無ければ以前の形式から作る）
+    fn read(&self) -> Result<InstalledState, StateError> {
+        let path = self.state_path();
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self.read_legacy()),
+            Err(e) => return Err(e.into()),
+        };
+        let error = match parse(&bytes) {
+            Ok(state) => return Ok(state),
+            // 新しいバージョンのアプリで書かれたものはバックアップに戻さない
+            Err(e @ StateError::UnsupportedVersion(_)) => return Err(e),
+            Err(e) => e,
+        };
+        for backup in self.backups() {
+            if let Ok(state) = fs::read(&backup).map_err(StateError::from).and_then(|b| parse(&b)) {
+                self.log_error(&format!("[state] {} is unreadable ({}); using backup {}", path.display(), error, backup.display()));
+                return Ok(state);
+            }
+        }
+        Err(error)
+    }
+
+    /// installed.json から作る（壊れている場合は記録して空から始める）
+    fn read_legacy(&self) -> InstalledState {
+        match fs::read(self.legacy_map_path()) {
+            Ok(bytes) => parse(&bytes).unwrap_or_else(|e| {
+                self.log_error(&format!("[state] failed to migrate installed.json: {e}"));
+                InstalledState::default()
+            }),
+            Err(_) => InstalledState::default(),
+        }
+    }
+
+    /// 今の内容をバックアップしてから書き込む
+    fn write(&self, state: &InstalledState) -> Result<(), StateError> {
+        let path = self.state_path();
+        let migrating = !path.exists();
+        if !migrating {
+            let dir = self.backups_dir();
+            fs::create_dir_all(&dir)?;
+            let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.6f").to_string();
+            fs::copy(&path, dir.join(format!("installed-state-{stamp}.json")))?;
+            for old in self.backups().into_iter().skip(BACKUP_KEEP) {
+                let _ = fs::remove_file(old);
+            }
+        }
+        let json = serde_json::to_vec_pretty(state).map_err(|e| StateError::Io(e.to_string()))?;
+        placement::write_atomic(&path, &json)?;
+        if migrating {
+            self.finish_migration();
+        }
+        Ok(())
+    }
+
+    /// 取り込んだ installed.json を片付ける
+    fn finish_migration(&self) {
+        let legacy = self.legacy_map_path();
+        if legacy.exists() {
+            if let Err(e) = fs::rename(&legacy, legacy.with_extension("json.migrated")) {
+                self.log_error(&format!("[state] failed to rename installed.json: {e}"));
+            }
+        }
+    }
+
+    /// 状態を読んで f で変更し、変わっていれば書き込む（f の戻り値を返す）
+    fn update<T>(&self, f: impl FnOnce(&mut InstalledState) -> T) -> Result<T, StateError> {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = self.read()?;
+        let before = state.clone();
+        let out = f(&mut state);
+        state.schema_version = SCHEMA_VERSION;
+        if state != before {
+            self.write(&state)?;
+        }
+        Ok(out)
+    }
+}
+
+/// 状態を読む
+pub fn load(app: &AppHandle) -> Result<InstalledState, StateError> {
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Store::new(app).read()
+}
+
+/// 状態を読んで f で変更し、書き込む（f の戻り値を返す）
+/// - 内容が変わらなければ（f が変更せずにエラーを返した場合も）書き込まず、バックアップも作らない
+pub fn update<T>(app: &AppHandle, f: impl FnOnce(&mut InstalledState) -> T) -> Result<T, StateError> {
+    Store::new(app).update(f)
+}
+
+/// インストールの完了を記録する（manifest が None の場合は前回のものを残す）
+pub fn record_install(app: &AppHandle, id: &str, version: &str, source: Option<String>, manifest: Option<Manifest>) -> Result<(), StateError> {
+    update(app, |state| {
+        let package = state.packages.entry(id.to_string()).or_default();
+        package.version = version.to_string();
+        package.installed_at = Some(chrono::Utc::now().timestamp());
+        if source.is_some() {
+            package.source = source;
+        }
+        if manifest.is_some() {
+            package.manifest = manifest;
+        }
+    })
+}
+
+/// パッケージの記録を消す
+pub fn remove_package(app: &AppHandle, id: &str) -> Result<(), StateError> {
+    update(app, |state| {
+        state.packages.remove(id);
+    })
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// インストール済みの状態をすべて返す
+#[tauri::command]
+pub fn get_installed_state(app: AppHandle) -> Result<InstalledState, StateError> {
+    load(&app)
+}
+
+/// 固定・メモを変更する（notes に空文字を渡すとメモを消す）
+/// - 記録に無い id や、今と同じ値を渡した場合は書き込まない
+#[tauri::command]
+pub fn update_package_state(app: AppHandle, id: String, pinned: Option<bool>, notes: Option<String>) -> Result<PackageState, StateError> {
+    update(&app, |state| {
+        let package = state.packages.get_mut(&id).ok_or_else(|| StateError::UnknownPackage(id.clone()))?;
+        if let Some(pinned) = pinned {
+            package.pinned = pinned;
+        }
+        if let Some(notes) = notes {
+            package.notes = Some(notes).filter(|n| !n.trim().is_empty());
+        }
+        Ok(package.clone())
+    })?
+}
+
+/// 検出したバージョンで記録を更新し、id → バージョンのマップを返す
+/// - 検出されなかったパッケージは、manifest・固定・メモが無ければ記録から外す
+#[tauri::command]
+pub fn save_installed_snapshot(app: AppHandle, detected: HashMap<String, String>) -> Result<HashMap<String, String>, StateError> {
+    update(&app, |state| {
+        state.packages.retain(|id, p| detected.get(id).is_some_and(|v| !v.is_empty()) || p.keep_when_undetected());
+        for (id, version) in detected.into_iter().filter(|(_, v)| !v.is_empty()) {
+            state.packages.entry(id).or_default().version = version;
+        }
+        state.installed_map()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn store(dir: &Path) -> Store<'static> {
+        Store { dir: dir.to_path_buf(), app: None }
+    }
+
+    fn pin(state: &mut InstalledState, id: &str, pinned: bool) -> Result<(), StateError> {
+        state.packages.get_mut(id).ok_or_else(|| StateError::UnknownPackage(id.to_string()))?.pinned = pinned;
+        Ok(())
+    }
+
+    #[test]
+    fn parse_migrates_v1_maps() {
+        let state = parse(br#"{ "pkg-a": "1.0", "pkg-b": 2, "pkg-c": "r3" }"#).unwrap();
+        assert_eq!(state.schema_version, SCHEMA_VERSION);
+        // 文字列でない値は以前と同じく読み飛ばす
+        assert_eq!(
+            state.installed_map(),
+            HashMap::from([
+                ("pkg-a".to_string(), "1.0".to_string()),
+                ("pkg-c".to_string(), "r3".to_string())
+            ])
+        );
+
+        let state = parse(br#"{ "schema_version": 2, "packages": { "pkg-a": { "version": "1.0", "pinned": true } } }"#).unwrap();
+        assert!(state.packages["pkg-a"].pinned);
+
+        assert!(matches!(parse(br#"{ "schema_version": 3 }"#), Err(StateError::UnsupportedVersion(3))));
+        assert!(matches!(parse(br#"{ "schema_version": 0 }"#), Err(StateError::UnsupportedVersion(0))));
+        assert!(matches!(parse(b"[]"), Err(StateError::Corrupt(_))));
+        assert!(matches!(parse(b"{ broken"), Err(StateError::Corrupt(_))));
+    }
+
+    #[test]
+    fn first_write_migrates_installed_json() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = store(tmp.path());
+        fs::write(store.legacy_map_path(), br#"{ "pkg-a": "1.0" }"#).unwrap();
+        assert_eq!(store.read().unwrap().installed_map()["pkg-a"], "1.0");
+
+        store.update(|state| pin(state, "pkg-a", true)).unwrap().unwrap();
+        assert!(!store.legacy_map_path().exists());
+        assert!(tmp.path().join("installed.json.migrated").exists());
+        // 取り込みの書き込みでは元の状態ファイルが無いのでバックアップも無い
+        assert!(store.backups().is_empty());
+        let state = parse(&fs::read(store.state_path()).unwrap()).unwrap();
+        assert_eq!(state.packages["pkg-a"].version, "1.0");
+        assert!(state.packages["pkg-a"].pinned);
+    }
+
+    #[test]
+    fn unchanged_updates_do_not_rotate_backups() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = store(tmp.path());
+        store.update(|state| state.packages.insert("pkg-a".to_string(), PackageState { version: "1.0".to_string(), ..Default::default() })).unwrap();
+        store.update(|state| pin(state, "pkg-a", true)).unwrap().unwrap();
+        let backups = store.backups();
+        assert_eq!(backups.len(), 1);
+
+        // 同じ値・記録に無い id では書き込まない
+        store.update(|state| pin(state, "pkg-a", true)).unwrap().unwrap();
+        assert!(matches!(store.update(|state| pin(state, "missing", true)).unwrap(), Err(StateError::UnknownPackage(_))));
+        assert_eq!(store.backups(), backups);
+    }
+
+    #[test]
+    fn backups_keep_the_newest_and_recover_a_corrupt_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = store(tmp.path());
+        store.update(|state| state.packages.insert("pkg-a".to_string(), PackageState::default())).unwrap();
+        for i in 0..BACKUP_KEEP + 3 {
+            store.update(|state| state.packages.get_mut("pkg-a").unwrap().version = format!("1.{i}")).unwrap();
+        }
+        let backups = store.backups();
+        assert_eq!(backups.len(), BACKUP_KEEP);
+        // 最新のバックアップは 1 つ前の内容
+        let latest = parse(&fs::read(&backups[0]).unwrap()).unwrap();
+        assert_eq!(latest.packages["pkg-a"].version, format!("1.{}", BACKUP_KEEP + 1));
+
+        fs::write(store.state_path(), b"{ broken").unwrap();
+        assert_eq!(store.read().unwrap(), latest);
+
+        // 新しいバージョンのアプリで書かれたものはバックアップに戻さない
+        fs::write(store.state_path(), br#"{ "schema_version": 99 }"#).unwrap();
+        assert!(matches!(store.read(), Err(StateError::UnsupportedVersion(99))));
+        assert!(matches!(store.update(|_| ()), Err(StateError::UnsupportedVersion(99))));
+    }
+}
@@ -0,0 +1,284 @@
+// -----------------------
+// 実行中タスクの管理（キャンセル・一時停止）
+// -----------------------
+//
+// - フロントから渡される taskId をキーに、実行中の処理へ中断要求を伝える
+// - 処理側は interrupted() を select! で待つか、is_cancelled() を定期的に確認する
+// - キャンセルはダウンロード・展開の区別なく cancel_task で行う（ダウンロードは cancel_download でも同じ）
+// - 一時停止できるのは register_pausable で登録したタスク（ダウンロード）だけ
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// 中断の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    Paused,
+    Cancelled,
+}
+
+/// 1 タスク分の状態
+#[derive(Default)]
+pub struct TaskControl {
+    state: AtomicU8,
+    notify: Notify,
+    // 処理側が一時停止を確認するか（しないタスクは pause できない）
+    pausable: bool,
+}
+
+impl TaskControl {
+    pub fn cancel(&self) {
+        self.state.store(CANCELLED, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// 実行中のタスクを一時停止（一時停止できないタスク、すでに停止・キャンセル済みなら false）
+    pub fn pause(&self) -> bool {
+        if !self.pausable {
+            return false;
+        }
+        let ok = self.state.compare_exchange(RUNNING, PAUSED, Ordering::SeqCst, Ordering::SeqCst).is_ok();
+        if ok {
+            self.notify.notify_waiters();
+        }
+        ok
+    }
+
+    /// 一時停止中のタスクを再開（一時停止中でなければ false）
+    pub fn resume(&self) -> bool {
+        let ok = self.state.compare_exchange(PAUSED, RUNNING, Ordering::SeqCst, Ordering::SeqCst).is_ok();
+        if ok {
+            self.notify.notify_waiters();
+        }
+        ok
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    fn interrupt(&self) -> Option<Interrupt> {
+        match self.state.load(Ordering::SeqCst) {
+            PAUSED => Some(Interrupt::Paused),
+            CANCELLED => Some(Interrupt::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// 一時停止またはキャンセルされるまで待つ
+    pub async fn interrupted(&self) -> Interrupt {
+        loop {
+            // 状態確認より先に待機を登録して通知の取りこぼしを防ぐ
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if let Some(reason) = self.interrupt() {
+                return reason;
+            }
+            notified.await;
+        }
+    }
+
+    /// 一時停止が解除されるまで待つ（キャンセルされた場合は false）
+    pub async fn wait_resumed(&self) -> bool {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            match self.interrupt() {
+                None => return true,
+                Some(Interrupt::Cancelled) => return false,
+                Some(Interrupt::Paused) => {}
+            }
+            notified.await;
+        }
+    }
+}
+
+/// taskId → TaskControl の対応表（Tauri の State として保持）
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: Mutex<HashMap<String, Arc<TaskControl>>>,
+}
+
+impl TaskRegistry {
+    /// タスクを登録する（戻り値が破棄されると登録も解除される）
+    /// - キャンセルだけを受け付ける
+    pub fn register(&self, task_id: &str) -> TaskGuard<'_> {
+        self.insert(task_id, TaskControl::default())
+    }
+
+    /// 一時停止も受け付けるタスクを登録する（処理側は interrupted() / wait_resumed() で一時停止を扱う）
+    pub fn register_pausable(&self, task_id: &str) -> TaskGuard<'_> {
+        self.insert(task_id, TaskControl { pausable: true, ..Default::default() })
+    }
+
+    fn insert(&self, task_id: &str, control: TaskControl) -> TaskGuard<'_> {
+        let control = Arc::new(control);
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.insert(task_id.to_string(), control.clone());
+        }
+        TaskGuard { registry: self, task_id: task_id.to_string(), control }
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<Arc<TaskControl>> {
+        self.tasks.lock().ok()?.get(task_id).cloned()
+    }
+
+    /// 該当タスクをキャンセル（無ければ false）
+    pub fn cancel(&self, task_id: &str) -> bool {
+        self.get(task_id).map(|control| control.cancel()).is_some()
+    }
+
+    /// 該当タスクを一時停止（無い・一時停止できない・実行中でなければ false）
+    pub fn pause(&self, task_id: &str) -> bool {
+        self.get(task_id).map(|control| control.pause()).unwrap_or(false)
+    }
+
+    /// 該当タスクを再開（無い・一時停止中でなければ false）
+    pub fn resume(&self, task_id: &str) -> bool {
+        self.get(task_id).map(|control| control.resume()).unwrap_or(false)
+    }
+}
+
+/// 登録中のタスク（Drop で登録解除）
+pub struct TaskGuard<'a> {
+    registry: &'a TaskRegistry,
+    task_id: String,
+    control: Arc<TaskControl>,
+}
+
+impl TaskGuard<'_> {
+    /// spawn_blocking など別スレッドへ渡すための参照
+    pub fn handle(&self) -> Arc<TaskControl> {
+        self.control.clone()
+    }
+}
+
+impl Deref for TaskGuard<'_> {
+    type Target = TaskControl;
+
+    fn deref(&self) -> &TaskControl {
+        &self.control
+    }
+}
+
+impl Drop for TaskGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut tasks) = self.registry.tasks.lock() {
+            // 同じ taskId で再登録されている場合は消さない
+            if tasks.get(&self.task_id).map(|c| Arc::ptr_eq(c, &self.control)).unwrap_or(false) {
+                tasks.remove(&self.task_id);
+            }
+        }
+    }
+}
+
+// -----------------------
+// Tauri コマンド
+// -----------------------
+
+/// 実行中のタスク（ダウンロード・展開）をキャンセル（該当タスクが無ければ false）
+#[tauri::command]
+pub fn cancel_task(tasks: tauri::State<'_, TaskRegistry>, task_id: String) -> bool {
+    tasks.cancel(&task_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // register で登録したタスク（展開など）は一時停止できず、キャンセルだけ受け付ける
+    #[test]
+    fn only_pausable_tasks_can_be_paused() {
+        let registry = TaskRegistry::default();
+        let extract = registry.register("extract");
+        assert!(!registry.pause("extract"));
+        assert_eq!(extract.interrupt(), None);
+        assert!(registry.cancel("extract"));
+        assert!(extract.is_cancelled());
+
+        let download = registry.register_pausable("download");
+        assert!(registry.pause("download"));
+        assert_eq!(download.interrupt(), Some(Interrupt::Paused));
+        assert!(!registry.pause("missing"));
+        assert!(!registry.cancel("missing"));
+    }
+
+    #[tokio::test]
+    async fn cancel_after_pause_stops_waiting_for_resume() {
+        let registry = TaskRegistry::default();
+        let task = registry.register_pausable("t");
+        assert!(registry.pause("t"));
+        assert!(!registry.pause("t"));
+        assert_eq!(task.interrupted().await, Interrupt::Paused);
+
+        let waiting = task.wait_resumed();
+        tokio::pin!(waiting);
+        assert!(tokio::time::timeout(Duration::from_millis(50), waiting.as_mut()).await.is_err());
+        assert!(registry.cancel("t"));
+        assert!(!tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap());
+        assert_eq!(task.interrupted().await, Interrupt::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn resume_after_pause_wakes_the_task() {
+        let registry = TaskRegistry::default();
+        let task = registry.register_pausable("t");
+        assert!(!registry.resume("t"));
+        assert!(registry.pause("t"));
+        let waiting = task.wait_resumed();
+        tokio::pin!(waiting);
+        assert!(tokio::time::timeout(Duration::from_millis(50), waiting.as_mut()).await.is_err());
+        assert!(registry.resume("t"));
+        assert!(tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap());
+        assert_eq!(task.interrupt(), None);
+    }
+
+    // キャンセルは最終状態で、再開や一時停止では戻らない
+    #[test]
+    fn resume_after_cancel_is_refused() {
+        let registry = TaskRegistry::default();
+        let task = registry.register_pausable("t");
+        assert!(registry.cancel("t"));
+        assert!(!registry.resume("t"));
+        assert!(!registry.pause("t"));
+        assert!(task.is_cancelled());
+        assert_eq!(task.interrupt(), Some(Interrupt::Cancelled));
+    }
+
+    #[test]
+    fn guard_unregisters_on_drop() {
+        let registry = TaskRegistry::default();
+        let task = registry.register("t");
+        let handle = task.handle();
+        assert!(registry.get("t").is_some());
+        drop(task);
+        assert!(registry.get("t").is_none());
+        assert!(!registry.cancel("t"));
+        // 別スレッドに渡した参照は登録解除後も使える
+        handle.cancel();
+        assert!(handle.is_cancelled());
+    }
+
+    // 同じ taskId で登録し直した後に古い guard が破棄されても、新しい登録は残る
+    #[test]
+    fn stale_guard_keeps_the_newer_registration() {
+        let registry = TaskRegistry::default();
+        let old = registry.register("t");
+        let new = registry.register_pausable("t");
+        drop(old);
+        assert!(registry.pause("t"));
+        assert_eq!(new.interrupt(), Some(Interrupt::Paused));
+        drop(new);
+        assert!(registry.get("t").is_none());
+    }
+}
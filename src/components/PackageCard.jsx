@@ -4,12 +4,13 @@ import { User, RefreshCw, Trash2, Download, Package, CheckCircle2, Calendar } fr
 import {
   formatDate,
   hasInstaller,
+  canUninstallItem,
   removeInstalledId,
-  runInstallerForItem,
+  runInstallPlan,
   runUninstallerForItem,
   loadInstalledMap,
 } from '../utils/index.js';
-import { useCatalogDispatch } from '../utils/catalogStore.jsx';
+import { useCatalog, useCatalogDispatch } from '../utils/catalogStore.jsx';
 import ErrorDialog from './ErrorDialog.jsx';
 import ProgressCircle from './ProgressCircle.jsx';
 
@@ -33,6 +34,7 @@ function pickThumbnail(item) {
 
 export default function PackageCard({ item, listSearch = '' }) {
   const navigate = useNavigate();
+  const { items } = useCatalog();
   const dispatch = useCatalogDispatch();
   const [error, setError] = useState('');
   const [downloading, setDownloading] = useState(false);
@@ -55,7 +57,7 @@ export default function PackageCard({ item, listSearch = '' }) {
       setDownloading(true);
       setDownloadProgress({ ratio: 0, percent: 0, label: '准备中…', phase: 'init' });
       if (hasInstaller(item)) {
-        await runInstallerForItem(item, dispatch, setDownloadProgress);
+        await runInstallPlan([item.id], items, dispatch, setDownloadProgress);
       } else {
         throw new Error('没有安装程序');
       }
@@ -73,7 +75,7 @@ export default function PackageCard({ item, listSearch = '' }) {
       setUpdating(true);
       setUpdateProgress({ ratio: 0, percent: 0, label: '准备中…', phase: 'init' });
       if (hasInstaller(item)) {
-        await runInstallerForItem(item, dispatch, setUpdateProgress);
+        await runInstallPlan([item.id], items, dispatch, setUpdateProgress);
       } else {
         throw new Error('没有安装程序');
       }
@@ -89,8 +91,7 @@ export default function PackageCard({ item, listSearch = '' }) {
     e.stopPropagation();
     try {
       setRemoving(true);
-      const hasUninstall = Array.isArray(item?.installer?.uninstall) && item.installer.uninstall.length > 0;
-      if (hasInstaller(item) && hasUninstall) {
+      if (await canUninstallItem(item)) {
         await runUninstallerForItem(item, dispatch);
       } else {
         await removeInstalledId(item.id);
@@ -1,7 +1,7 @@
 /**
  * 入力状態を API 送信用ペイロードへ構築するモジュール
  */
-import { commaListToArray, isHttpsUrl, normalizeArrayText } from './helpers';
+import { commaListToArray, isHttpsUrl, normalizeArrayText, patternListToArray } from './helpers';
 import { getFileExtension } from './parse';
 import type {
   RegisterCatalogEntry,
@@ -57,6 +57,21 @@ function serializeInstallStep(step: RegisterInstallStep): RegisterInstallStepPay
   if (step.to && step.to.trim()) {
     payload.to = step.to.trim();
   }
+  if (step.action === 'extract') {
+    if (step.encoding && step.encoding !== 'auto') payload.encoding = step.encoding;
+    const include = patternListToArray(step.includeText);
+    const exclude = patternListToArray(step.excludeText);
+    const strip = parseInt(step.strip, 10);
+    const nested = parseInt(step.nested, 10);
+    if (include.length) payload.include = include;
+    if (exclude.length) payload.exclude = exclude;
+    if (Number.isInteger(strip) && strip > 0) payload.strip = strip;
+    if (Number.isInteger(nested) && nested > 0) payload.nested = nested;
+  }
+  if (step.action === 'copy' && step.overwrite && step.overwrite !== 'overwrite') {
+    payload.overwrite = step.overwrite;
+  }
+  if (step.action === 'copy' && step.optional) payload.optional = true;
   return payload;
 }
 
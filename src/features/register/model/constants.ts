@@ -11,7 +11,7 @@ export const ID_PATTERN = /^[A-Za-z0-9._-]+$/;
 
 export const ACTION_LABELS: Record<string, string> = {
   download: 'ダウンロード',
-  extract: 'ZIP展開',
+  extract: 'アーカイブ展開',
   copy: 'コピー',
   run: 'EXE実行',
   delete: '削除',
@@ -19,6 +19,23 @@ export const ACTION_LABELS: Record<string, string> = {
   run_auo_setup: 'auo_setup2.exeを実行',
 };
 
+// ZIP 展開時のファイル名の文字コード（auto は自動判定で、出力には含めない）
+export const ZIP_ENCODING_OPTIONS: RegisterInstallerOption[] = [
+  { value: 'auto', label: '自动判定' },
+  { value: 'cp932', label: '日文（Shift_JIS / CP932）' },
+  { value: 'gbk', label: '简体中文（GBK）' },
+  { value: 'big5', label: '繁体中文（Big5）' },
+  { value: 'utf-8', label: 'UTF-8' },
+];
+
+// copy 步骤中已有文件的处理（overwrite 为默认值，不输出）
+export const OVERWRITE_POLICY_OPTIONS: RegisterInstallerOption[] = [
+  { value: 'overwrite', label: '直接覆盖' },
+  { value: 'skip-if-exists', label: '保留已有文件' },
+  { value: 'backup-then-overwrite', label: '备份后覆盖' },
+  { value: 'fail-if-different', label: '内容不同时中止' },
+];
+
 export const INSTALLER_SOURCES: RegisterInstallerOption[] = [
   { value: 'direct', label: '直接URL' },
   { value: 'github', label: 'GitHub Release' },
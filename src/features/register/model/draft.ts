@@ -167,6 +167,13 @@ export function computeRegisterDraftContentHash(args: {
         from: String(step.from || ''),
         to: String(step.to || ''),
         elevate: !!step.elevate,
+        encoding: String(step.encoding || ''),
+        includeText: String(step.includeText || ''),
+        excludeText: String(step.excludeText || ''),
+        strip: String(step.strip || ''),
+        nested: String(step.nested || ''),
+        overwrite: String(step.overwrite || ''),
+        optional: !!step.optional,
       })),
       uninstallSteps: (Array.isArray(form.installer.uninstallSteps) ? form.installer.uninstallSteps : []).map(
         (step) => ({
@@ -8,11 +8,13 @@ export {
   INSTALL_ACTIONS,
   INSTALLER_SOURCES,
   LICENSE_TEMPLATE_TYPES,
+  OVERWRITE_POLICY_OPTIONS,
   PACKAGE_GUIDE_FALLBACK_URL,
   SPECIAL_INSTALL_ACTIONS,
   SUBMIT_ACTIONS,
   UNINSTALL_ACTION_OPTIONS,
   UNINSTALL_ACTIONS,
+  ZIP_ENCODING_OPTIONS,
 } from './constants';
 
 export {
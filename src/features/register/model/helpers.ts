@@ -22,6 +22,19 @@ export function arrayToCommaList(arr: unknown[]): string {
   return normalizeArrayText(Array.isArray(arr) ? arr : []).join(', ');
 }
 
+// glob パターンは {a,b} でカンマを使うため、extract ステップの include / exclude はセミコロン区切りで入力する
+export function patternListToArray(text: string): string[] {
+  return String(text || '')
+    .split(';')
+    .map((v) => v.trim())
+    .filter(Boolean);
+}
+
+export function arrayToPatternList(value: unknown): string {
+  if (typeof value === 'string') return value.trim();
+  return normalizeArrayText(Array.isArray(value) ? value : []).join('; ');
+}
+
 export function isMarkdownPath(value: unknown): boolean {
   return typeof value === 'string' && /\.md$/i.test(value.trim());
 }
@@ -1,7 +1,7 @@
 /**
  * カタログデータをフォーム状態へ変換するパーサーモジュール
  */
-import { arrayToCommaList, buildPreviewUrl, isHttpsUrl, isMarkdownPath } from './helpers';
+import { arrayToCommaList, arrayToPatternList, buildPreviewUrl, isHttpsUrl, isMarkdownPath } from './helpers';
 import { INSTALL_ACTIONS, LICENSE_TEMPLATE_TYPES, SPECIAL_INSTALL_ACTIONS, UNINSTALL_ACTIONS } from './constants';
 import {
   createEmptyCopyright,
@@ -57,6 +57,13 @@ export function parseInstallerSource(installer: any = {}) {
       from: String(step?.from || ''),
       to: String(step?.to || ''),
       elevate: !!step?.elevate,
+      encoding: String(step?.encoding || ''),
+      includeText: arrayToPatternList(step?.include),
+      excludeText: arrayToPatternList(step?.exclude),
+      strip: Number.isInteger(step?.strip) && step.strip > 0 ? String(step.strip) : '',
+      nested: Number.isInteger(step?.nested) && step.nested > 0 ? String(step.nested) : '',
+      overwrite: String(step?.overwrite || ''),
+      optional: !!step?.optional,
     };
   });
   const uninstallSteps = Array.isArray(installer.uninstall) ? installer.uninstall : [];
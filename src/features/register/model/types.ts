@@ -12,6 +12,13 @@ export interface RegisterInstallStep {
   from: string;
   to: string;
   elevate: boolean;
+  encoding: string;
+  includeText: string;
+  excludeText: string;
+  strip: string;
+  nested: string;
+  overwrite: string;
+  optional: boolean;
 }
 
 export interface RegisterUninstallStep {
@@ -126,6 +133,13 @@ export interface RegisterInstallStepPayload {
   from?: string;
   to?: string;
   elevate?: boolean;
+  encoding?: string;
+  include?: string[];
+  exclude?: string[];
+  strip?: number;
+  nested?: number;
+  overwrite?: string;
+  optional?: boolean;
 }
 
 export interface RegisterUninstallStepPayload {
@@ -146,6 +160,12 @@ export interface RegisterInstallerSourcePayload {
   GoogleDrive?: {
     id: string;
   };
+  mirrors?: string[];
+  expected?: {
+    XXH3_128?: string;
+    sha256?: string;
+    size?: number;
+  };
 }
 
 export interface RegisterInstallerPayload {
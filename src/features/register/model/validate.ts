@@ -119,6 +119,9 @@ export function validatePackageForm(form: RegisterPackageForm): string {
     }
     if (step.action === 'copy') {
       if (!step.from.trim() || !step.to.trim()) return 'copy の from / to は必須です';
+      if (step.optional && typeof step.optional !== 'boolean') return 'copy の optional は true/false で指定してください';
+    } else if (step.optional) {
+      return 'optional は action: copy のときのみ指定できます';
     }
     if (step.action === 'delete') {
       if (!step.path.trim()) return 'delete の path は必須です';
@@ -142,7 +142,22 @@ export default function useRegisterInstallerLicenseHandlers({
         ...prev.installer,
         installSteps: [
           ...prev.installer.installSteps,
-          { key: generateKey(), action: 'download', path: '', argsText: '', from: '', to: '', elevate: false },
+          {
+            key: generateKey(),
+            action: 'download',
+            path: '',
+            argsText: '',
+            from: '',
+            to: '',
+            elevate: false,
+            encoding: '',
+            includeText: '',
+            excludeText: '',
+            strip: '',
+            nested: '',
+            overwrite: '',
+            optional: false,
+          },
         ],
       },
     }));
@@ -161,6 +176,9 @@ export default function useRegisterInstallerLicenseHandlers({
             if (field === 'action' && value !== 'run') {
               next.elevate = false;
             }
+            if (field === 'action' && value !== 'copy') {
+              next.optional = false;
+            }
             return next;
           }),
         },
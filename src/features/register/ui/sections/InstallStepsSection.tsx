@@ -3,7 +3,13 @@
  */
 import React from 'react';
 import { GripVertical, Plus } from 'lucide-react';
-import { ACTION_LABELS, INSTALL_ACTION_OPTIONS, SPECIAL_INSTALL_ACTIONS } from '../../model/form';
+import {
+  ACTION_LABELS,
+  INSTALL_ACTION_OPTIONS,
+  OVERWRITE_POLICY_OPTIONS,
+  SPECIAL_INSTALL_ACTIONS,
+  ZIP_ENCODING_OPTIONS,
+} from '../../model/form';
 import type { PackageInstallerSectionProps } from '../types';
 import ActionDropdown from '../components/ActionDropdown';
 import DeleteButton from '../components/DeleteButton';
@@ -125,6 +131,105 @@ export default function InstallStepsSection({
                   </div>
                 </div>
               )}
+              {!isSpecialAction && step.action === 'extract' && (
+                <div className="grid gap-3 rounded-lg bg-slate-50 p-3 dark:bg-slate-800/50 md:grid-cols-2">
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-encoding`}
+                    >
+                      文件名编码
+                    </label>
+                    <ActionDropdown
+                      buttonId={`install-${step.key}-encoding`}
+                      value={step.encoding || 'auto'}
+                      onChange={(val) => updateInstallStep(step.key, 'encoding', val)}
+                      options={ZIP_ENCODING_OPTIONS}
+                      ariaLabel="选择文件名编码"
+                    />
+                  </div>
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-to`}
+                    >
+                      解压目标
+                    </label>
+                    <input
+                      id={`install-${step.key}-to`}
+                      value={step.to}
+                      onChange={(e) => updateInstallStep(step.key, 'to', e.target.value)}
+                      placeholder="（省略时为 {tmp}）"
+                      className="!bg-white dark:!bg-slate-800"
+                    />
+                  </div>
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-include`}
+                    >
+                      仅解压（glob，分号分隔）
+                    </label>
+                    <input
+                      id={`install-${step.key}-include`}
+                      value={step.includeText}
+                      onChange={(e) => updateInstallStep(step.key, 'includeText', e.target.value)}
+                      placeholder="（例：*/Plugin/*.aui2）"
+                      className="!bg-white dark:!bg-slate-800"
+                    />
+                  </div>
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-exclude`}
+                    >
+                      排除（glob，分号分隔）
+                    </label>
+                    <input
+                      id={`install-${step.key}-exclude`}
+                      value={step.excludeText}
+                      onChange={(e) => updateInstallStep(step.key, 'excludeText', e.target.value)}
+                      placeholder="（例：**/*.txt）"
+                      className="!bg-white dark:!bg-slate-800"
+                    />
+                  </div>
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-strip`}
+                    >
+                      去掉开头的目录层数
+                    </label>
+                    <input
+                      id={`install-${step.key}-strip`}
+                      type="number"
+                      min={0}
+                      value={step.strip}
+                      onChange={(e) => updateInstallStep(step.key, 'strip', e.target.value)}
+                      placeholder="0"
+                      className="!bg-white dark:!bg-slate-800"
+                    />
+                  </div>
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-nested`}
+                    >
+                      继续解压内层压缩包（层数）
+                    </label>
+                    <input
+                      id={`install-${step.key}-nested`}
+                      type="number"
+                      min={0}
+                      max={4}
+                      value={step.nested}
+                      onChange={(e) => updateInstallStep(step.key, 'nested', e.target.value)}
+                      placeholder="0（展开后可用 {nested} 引用）"
+                      className="!bg-white dark:!bg-slate-800"
+                    />
+                  </div>
+                </div>
+              )}
               {!isSpecialAction && step.action === 'copy' && (
                 <div className="grid gap-3 rounded-lg bg-slate-50 p-3 dark:bg-slate-800/50 md:grid-cols-2">
                   <div className="space-y-1">
@@ -138,7 +243,7 @@ export default function InstallStepsSection({
                       id={`install-${step.key}-from`}
                       value={step.from}
                       onChange={(e) => updateInstallStep(step.key, 'from', e.target.value)}
-                      placeholder="（例：{tmp}/example.auo）"
+                      placeholder="（例：{tmp}/example.auo、{tmp}/*/Plugin/*.auo）"
                       className="!bg-white dark:!bg-slate-800"
                     />
                   </div>
@@ -157,6 +262,32 @@ export default function InstallStepsSection({
                       className="!bg-white dark:!bg-slate-800"
                     />
                   </div>
+                  <div className="space-y-1">
+                    <label
+                      className="text-xs font-medium text-slate-600 dark:text-slate-400"
+                      htmlFor={`install-${step.key}-overwrite`}
+                    >
+                      已有文件
+                    </label>
+                    <ActionDropdown
+                      buttonId={`install-${step.key}-overwrite`}
+                      value={step.overwrite || 'overwrite'}
+                      onChange={(val) => updateInstallStep(step.key, 'overwrite', val)}
+                      options={OVERWRITE_POLICY_OPTIONS}
+                      ariaLabel="选择已有文件的处理方式"
+                    />
+                  </div>
+                  <div className="md:col-span-2">
+                    <label className="inline-flex cursor-pointer items-center gap-2 rounded-lg border border-slate-200 bg-white px-3 py-2 text-xs font-medium text-slate-700 shadow-sm transition hover:bg-slate-50 dark:border-slate-800 dark:bg-slate-900 dark:text-slate-300 dark:hover:bg-slate-800">
+                      <input
+                        type="checkbox"
+                        className="accent-blue-600"
+                        checked={!!step.optional}
+                        onChange={(e) => updateInstallStep(step.key, 'optional', e.target.checked)}
+                      />
+                      <span>没有匹配的文件时跳过（不视为错误）</span>
+                    </label>
+                  </div>
                 </div>
               )}
             </div>
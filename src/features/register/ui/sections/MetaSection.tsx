@@ -127,14 +127,14 @@ export default function RegisterMetaSection({
 
         <div className="space-y-2">
           <label className="text-sm font-medium text-slate-700 dark:text-slate-300" htmlFor="package-dependencies">
-            依赖包 (当前不支持)
+            依赖包
           </label>
           <input
             id="package-dependencies"
             name="dependencies"
             value={packageForm.dependenciesText}
             onChange={(e) => onUpdatePackageField('dependenciesText', e.target.value)}
-            placeholder="包ID (逗号分隔)"
+            placeholder="包ID (逗号分隔，可用 包ID>=版本 指定所需版本)"
           />
         </div>
 
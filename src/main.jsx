@@ -203,7 +203,7 @@ function Bootstrapper() {
             const detected = await detectInstalledVersionsMap(items);
             if (!cancelled) {
               dispatch({ type: 'SET_DETECTED_MAP', payload: detected });
-              // 検出結果をinstalled.jsonにスナップショット保存
+              // 検出結果をインストール済みの状態（installed-state.json）に保存
               try {
                 const snap = await saveInstalledSnapshot(detected);
                 dispatch({ type: 'SET_INSTALLED_MAP', payload: snap });
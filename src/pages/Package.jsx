@@ -18,7 +18,8 @@ import { useCatalog, useCatalogDispatch } from '../utils/catalogStore.jsx';
 import {
   formatDate,
   hasInstaller,
-  runInstallerForItem,
+  canUninstallItem,
+  runInstallPlan,
   runUninstallerForItem,
   removeInstalledId,
   latestVersionOf,
@@ -227,7 +228,7 @@ export default function Package() {
       setDownloading(true);
       setDownloadProgress({ ratio: 0, percent: 0, label: '准备中…', phase: 'init' });
       if (hasInstaller(item)) {
-        await runInstallerForItem(item, dispatch, setDownloadProgress);
+        await runInstallPlan([item.id], items, dispatch, setDownloadProgress);
       } else {
         throw new Error('安装功能未实现');
       }
@@ -275,7 +276,7 @@ export default function Package() {
       setUpdating(true);
       setUpdateProgress({ ratio: 0, percent: 0, label: '准备中…', phase: 'init' });
       if (hasInstaller(item)) {
-        await runInstallerForItem(item, dispatch, setUpdateProgress);
+        await runInstallPlan([item.id], items, dispatch, setUpdateProgress);
       } else {
         throw new Error('安装功能未实现');
       }
@@ -291,8 +292,7 @@ export default function Package() {
   async function onRemove() {
     try {
       setRemoving(true);
-      const hasUninstall = Array.isArray(item?.installer?.uninstall) && item.installer.uninstall.length > 0;
-      if (hasInstaller(item) && hasUninstall) {
+      if (await canUninstallItem(item)) {
         await runUninstallerForItem(item, dispatch);
       } else {
         await removeInstalledId(item.id);
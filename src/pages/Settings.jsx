@@ -8,10 +8,15 @@ import {
   loadInstalledMap,
   logError,
   runInstallerForItem,
+  resolveInstallPlan,
   runUninstallerForItem,
+  canUninstallItem,
   saveInstalledSnapshot,
   hasInstaller,
   resetPackageStateLocalState,
+  listPackageCache,
+  prunePackageCache,
+  exportPackageCache,
 } from '../utils/index.js';
 
 const iconBlockStyle = { display: 'block' };
@@ -33,6 +38,11 @@ export default function Settings() {
     isPortableMode: false,
     theme: 'darkmode',
     packageStateOptOut: false,
+    downloadMaxParallel: '',
+    downloadPerHostLimit: '',
+    downloadBandwidthKb: '',
+    packageCacheLimitMb: '',
+    githubToken: '',
   });
 
   const [saving, setSaving] = useState(false);
@@ -42,6 +52,8 @@ export default function Settings() {
   const [syncBusy, setSyncBusy] = useState(false);
   const [syncStatus, setSyncStatus] = useState('');
   const [initialPackageStateOptOut, setInitialPackageStateOptOut] = useState(false);
+  const [cacheInfo, setCacheInfo] = useState(null);
+  const [cacheBusy, setCacheBusy] = useState(false);
 
   useEffect(() => {
     let mounted = true;
@@ -54,7 +66,24 @@ export default function Settings() {
           const aviutl2Root = String(cur?.aviutl2_root || '');
           const isPortableMode = !!cur?.is_portable_mode;
           const packageStateOptOut = !!cur?.package_state_opt_out;
-          setForm({ theme, aviutl2Root, isPortableMode, packageStateOptOut });
+          // 0 表示使用默认值 / 不限制，输入框留空
+          const toField = (v) => (Number(v) > 0 ? String(v) : '');
+          const downloadMaxParallel = toField(cur?.download_max_parallel);
+          const downloadPerHostLimit = toField(cur?.download_per_host_limit);
+          const downloadBandwidthKb = toField(Math.floor(Number(cur?.download_bandwidth_limit || 0) / 1024));
+          const packageCacheLimitMb = toField(cur?.package_cache_limit_mb);
+          const githubToken = String(cur?.github_token || '');
+          setForm({
+            theme,
+            aviutl2Root,
+            isPortableMode,
+            packageStateOptOut,
+            downloadMaxParallel,
+            downloadPerHostLimit,
+            downloadBandwidthKb,
+            packageCacheLimitMb,
+            githubToken,
+          });
           setInitialPackageStateOptOut(packageStateOptOut);
           applyTheme(theme);
         }
@@ -64,6 +93,15 @@ export default function Settings() {
         } catch {}
       }
 
+      try {
+        const info = await listPackageCache();
+        if (mounted) setCacheInfo(info || null);
+      } catch (e) {
+        try {
+          await logError(`[settings] listPackageCache failed: ${e?.message || e}`);
+        } catch {}
+      }
+
       try {
         const app = await import('@tauri-apps/api/app');
         const v = app?.getVersion ? await app.getVersion() : '';
@@ -120,6 +158,15 @@ export default function Settings() {
         packageStateOptOut: !!form.packageStateOptOut,
       });
 
+      const toCount = (v) => Math.max(0, Math.floor(Number(v) || 0));
+      await invoke('update_download_settings', {
+        maxParallel: toCount(form.downloadMaxParallel),
+        perHostLimit: toCount(form.downloadPerHostLimit),
+        bandwidthLimit: toCount(form.downloadBandwidthKb) * 1024,
+        cacheLimitMb: toCount(form.packageCacheLimitMb),
+      });
+      await invoke('update_github_token', { token: String(form.githubToken || '') });
+
       applyTheme(form.theme);
       const nextOptOut = !!form.packageStateOptOut;
       if (!initialPackageStateOptOut && nextOptOut) {
@@ -184,6 +231,49 @@ export default function Settings() {
     }
   }
 
+  async function handleCacheClear() {
+    if (cacheBusy) return;
+    setError('');
+    setCacheBusy(true);
+    try {
+      const dialog = await import('@tauri-apps/plugin-dialog');
+      const ok = await dialog.confirm('将删除所有已缓存的下载包。\n是否继续？', { title: '下载缓存', kind: 'warning' });
+      if (!ok) return;
+      await prunePackageCache(0);
+      setCacheInfo(await listPackageCache());
+    } catch (e) {
+      setError('清空缓存失败。');
+      try {
+        await logError(`[settings] cache prune failed: ${e?.message || e}`);
+      } catch {}
+    } finally {
+      setCacheBusy(false);
+    }
+  }
+
+  async function handleCacheExport() {
+    if (cacheBusy) return;
+    setError('');
+    setCacheBusy(true);
+    try {
+      const dialog = await import('@tauri-apps/plugin-dialog');
+      const dir = await dialog.open({ directory: true, multiple: false, title: '选择缓存导出位置' });
+      const destPath = Array.isArray(dir) ? dir[0] : dir;
+      if (!destPath) return;
+      const count = await exportPackageCache(String(destPath));
+      try {
+        await dialog.message(`已导出 ${count} 个下载包。`, { title: '导出', kind: 'info' });
+      } catch {}
+    } catch (e) {
+      setError('导出缓存失败。\n请检查权限和保存位置。');
+      try {
+        await logError(`[settings] cache export failed: ${e?.message || e}`);
+      } catch {}
+    } finally {
+      setCacheBusy(false);
+    }
+  }
+
   async function handleImport() {
     if (syncBusy) return;
     setError('');
@@ -243,15 +333,28 @@ export default function Settings() {
       let installedCount = 0;
       let removedCount = 0;
 
-      for (let i = 0; i < toInstall.length; i++) {
-        const id = toInstall[i];
+      // 按依赖顺序安装（缺失的依赖也一并安装），解析失败时按原顺序安装
+      let installOrder = toInstall;
+      if (toInstall.length) {
+        try {
+          const plan = await resolveInstallPlan(toInstall);
+          installOrder = plan.steps.map((step) => step.id);
+        } catch (e) {
+          try {
+            await logError(`[sync] resolveInstallPlan failed: ${e?.message || e}`);
+          } catch {}
+        }
+      }
+
+      for (let i = 0; i < installOrder.length; i++) {
+        const id = installOrder[i];
         const item = idToItem.get(id);
         if (!item || !hasInstaller(item)) {
           skippedInstall.push(id);
           continue;
         }
         const label = item?.name ? `${item.name} (${id})` : id;
-        setSyncStatus(`正在安装… (${i + 1}/${toInstall.length}) ${label}`);
+        setSyncStatus(`正在安装… (${i + 1}/${installOrder.length}) ${label}`);
         try {
           await runInstallerForItem(item, dispatch);
           installedCount += 1;
@@ -263,7 +366,7 @@ export default function Settings() {
       for (let i = 0; i < toRemove.length; i++) {
         const id = toRemove[i];
         const item = idToItem.get(id);
-        if (!item || !Array.isArray(item?.installer?.uninstall) || item.installer.uninstall.length === 0) {
+        if (!item || !(await canUninstallItem(item))) {
           skippedRemove.push(id);
           continue;
         }
@@ -433,6 +536,87 @@ export default function Settings() {
             </div>
           </div>
 
+          <div className="space-y-2">
+            <div className="text-sm font-medium">下载</div>
+            <div className="text-xs text-slate-500 dark:text-slate-400">
+              批量更新时的同时下载数与带宽上限。留空则使用默认值（同时 3 个、同一站点 2 个、不限速）。
+            </div>
+            <div className="grid grid-cols-1 gap-2 sm:grid-cols-3">
+              <label className="space-y-1 text-xs text-slate-500 dark:text-slate-400" htmlFor="settings-download-parallel">
+                <span>同时下载数</span>
+                <input
+                  id="settings-download-parallel"
+                  name="downloadMaxParallel"
+                  type="number"
+                  min="0"
+                  value={form.downloadMaxParallel}
+                  onChange={onChange}
+                  className="w-full rounded-lg border border-slate-200 dark:border-slate-700 bg-white dark:bg-slate-800 px-3 py-2 text-sm text-slate-800 dark:text-slate-100 cursor-text select-text"
+                  placeholder="3"
+                />
+              </label>
+              <label className="space-y-1 text-xs text-slate-500 dark:text-slate-400" htmlFor="settings-download-per-host">
+                <span>同一站点的同时下载数</span>
+                <input
+                  id="settings-download-per-host"
+                  name="downloadPerHostLimit"
+                  type="number"
+                  min="0"
+                  value={form.downloadPerHostLimit}
+                  onChange={onChange}
+                  className="w-full rounded-lg border border-slate-200 dark:border-slate-700 bg-white dark:bg-slate-800 px-3 py-2 text-sm text-slate-800 dark:text-slate-100 cursor-text select-text"
+                  placeholder="2"
+                />
+              </label>
+              <label className="space-y-1 text-xs text-slate-500 dark:text-slate-400" htmlFor="settings-download-bandwidth">
+                <span>带宽上限（KB/秒）</span>
+                <input
+                  id="settings-download-bandwidth"
+                  name="downloadBandwidthKb"
+                  type="number"
+                  min="0"
+                  value={form.downloadBandwidthKb}
+                  onChange={onChange}
+                  className="w-full rounded-lg border border-slate-200 dark:border-slate-700 bg-white dark:bg-slate-800 px-3 py-2 text-sm text-slate-800 dark:text-slate-100 cursor-text select-text"
+                  placeholder="不限制"
+                />
+              </label>
+              <label className="space-y-1 text-xs text-slate-500 dark:text-slate-400" htmlFor="settings-package-cache-limit">
+                <span>下载缓存上限（MB）</span>
+                <input
+                  id="settings-package-cache-limit"
+                  name="packageCacheLimitMb"
+                  type="number"
+                  min="0"
+                  value={form.packageCacheLimitMb}
+                  onChange={onChange}
+                  className="w-full rounded-lg border border-slate-200 dark:border-slate-700 bg-white dark:bg-slate-800 px-3 py-2 text-sm text-slate-800 dark:text-slate-100 cursor-text select-text"
+                  placeholder="2048"
+                />
+              </label>
+            </div>
+          </div>
+
+          <div className="space-y-2">
+            <label className="text-sm font-medium" htmlFor="settings-github-token">
+              GitHub 令牌{' '}
+              <span className="text-xs text-slate-500 dark:text-slate-400 font-normal">（可选）</span>
+            </label>
+            <div className="text-xs text-slate-500 dark:text-slate-400">
+              未登录时 GitHub API 每小时仅可调用 60 次。批量更新较多包时，可填写个人访问令牌（无需任何权限）以放宽限制。
+            </div>
+            <input
+              id="settings-github-token"
+              name="githubToken"
+              type="password"
+              autoComplete="off"
+              value={form.githubToken}
+              onChange={onChange}
+              className="w-full rounded-lg border border-slate-200 dark:border-slate-700 bg-white dark:bg-slate-800 px-3 py-2 text-sm cursor-text select-text"
+              placeholder="github_pat_..."
+            />
+          </div>
+
           <div className="flex flex-wrap items-center justify-end gap-2 border-slate-100 dark:border-slate-800">
             <button
               className={`flex items-center gap-2 px-4 py-2 rounded-lg text-white text-sm font-medium transition-all duration-200 disabled:opacity-60 cursor-pointer ${
@@ -479,6 +663,35 @@ export default function Settings() {
             </div>
             {syncStatus && <div className="text-xs text-slate-500 dark:text-slate-400">{syncStatus}</div>}
           </div>
+
+          <div className="space-y-3">
+            <div className="text-sm font-medium">下载缓存</div>
+            <div className="text-xs text-slate-500 dark:text-slate-400">
+              已下载的安装包会保存在缓存中，重新安装时无需再次下载。
+              {cacheInfo
+                ? ` 当前 ${cacheInfo.entries?.length || 0} 个，共 ${(Number(cacheInfo.totalSize || 0) / 1024 / 1024).toFixed(1)} MB。`
+                : ''}
+            </div>
+            <div className="flex flex-wrap gap-2">
+              <button
+                className="flex items-center gap-2 px-3 py-2 rounded-lg border border-slate-200 dark:border-slate-700 text-sm hover:bg-slate-50 dark:hover:bg-slate-800 cursor-pointer"
+                onClick={handleCacheExport}
+                disabled={cacheBusy}
+                type="button"
+              >
+                <Download size={16} />
+                导出缓存
+              </button>
+              <button
+                className="flex items-center gap-2 px-3 py-2 rounded-lg border border-slate-200 dark:border-slate-700 text-sm hover:bg-slate-50 dark:hover:bg-slate-800 cursor-pointer"
+                onClick={handleCacheClear}
+                disabled={cacheBusy}
+                type="button"
+              >
+                清空缓存
+              </button>
+            </div>
+          </div>
         </div>
       </section>
 
@@ -1,6 +1,12 @@
 import React, { useMemo, useState } from 'react';
 import { useCatalog, useCatalogDispatch } from '../utils/catalogStore.jsx';
-import { hasInstaller, latestVersionOf, logError, runInstallerForItem } from '../utils/index.js';
+import {
+  hasInstaller,
+  latestVersionOf,
+  logError,
+  prefetchInstallerDownloads,
+  runInstallerForItem,
+} from '../utils/index.js';
 import ErrorDialog from '../components/ErrorDialog.jsx';
 import ProgressCircle from '../components/ProgressCircle.jsx';
 
@@ -32,6 +38,26 @@ export default function Updates() {
     const total = targets.length || 1;
     const failed = [];
 
+    // 先并行下载（同时下载数和带宽上限由设置决定），再逐个安装
+    let prefetched = new Map();
+    try {
+      prefetched = await prefetchInstallerDownloads(targets, ({ item, done, total: downloadTotal }) => {
+        const ratio = downloadTotal > 0 ? done / downloadTotal : 0;
+        setBulkProgress({
+          ratio,
+          percent: Math.round(ratio * 100),
+          itemName: item.name,
+          status: `下载中… (${done}/${downloadTotal})`,
+          current: 0,
+          total,
+        });
+      });
+    } catch (err) {
+      try {
+        await logError(`[BulkUpdate] prefetch failed: ${err?.message || err}`);
+      } catch {}
+    }
+
     for (let i = 0; i < targets.length; i++) {
       const item = targets[i];
       setBulkProgress({
@@ -44,19 +70,24 @@ export default function Updates() {
       });
 
       try {
-        await runInstallerForItem(item, dispatch, (progress) => {
-          const stepRatio = progress && Number.isFinite(progress.ratio) ? Math.min(1, Math.max(0, progress.ratio)) : 0;
-          const percent = Math.round(stepRatio * 100);
-          const label = progress?.label || '处理中…';
-          setBulkProgress({
-            ratio: stepRatio,
-            percent,
-            itemName: item.name,
-            status: label,
-            current: i + 1,
-            total,
-          });
-        });
+        await runInstallerForItem(
+          item,
+          dispatch,
+          (progress) => {
+            const stepRatio = progress && Number.isFinite(progress.ratio) ? Math.min(1, Math.max(0, progress.ratio)) : 0;
+            const percent = Math.round(stepRatio * 100);
+            const label = progress?.label || '处理中…';
+            setBulkProgress({
+              ratio: stepRatio,
+              percent,
+              itemName: item.name,
+              status: label,
+              current: i + 1,
+              total,
+            });
+          },
+          { downloadPath: prefetched.get(item.id) },
+        );
         setBulkProgress({
           ratio: 1,
           percent: 100,
@@ -157,9 +157,8 @@ export function getSorter(key = 'newest', dir = 'desc') {
 // 安装状态记录
 // -------------------------
 
-// 将已安装插件的ID和版本保存到json
-// 模式: { [id: string]: string /* version */ }
-const INSTALLED_FILE = 'installed.json';
+// 已安装插件的状态由 Rust 侧保存在 installed-state.json（带 schema_version）
+// JS 侧使用兼容视图: { [id: string]: string /* version */ }
 const CATALOG_CACHE_DIR = 'catalog';
 const CATALOG_CACHE_FILE = `${CATALOG_CACHE_DIR}/index.json`;
 
@@ -317,7 +316,7 @@ export async function loadCatalogData(options = {}) {
   return { items, source };
 }
 
-// 从 installed.json 加载已安装包列表（整合 Rust 和 JS）
+// 加载已安装包列表（installed-state.json 的兼容视图）
 export async function loadInstalledMap() {
   try {
     const { invoke } = await import('@tauri-apps/api/core');
@@ -327,32 +326,7 @@ export async function loadInstalledMap() {
   }
 }
 
-// 将已安装包列表写入 installed.json（计划废弃）
-async function writeInstalledMap(map) {
-  const fs = await import('@tauri-apps/plugin-fs');
-  try {
-    await fs.writeTextFile(INSTALLED_FILE, JSON.stringify(map, null, 2), { baseDir: fs.BaseDirectory.AppConfig });
-  } catch (e) {
-    try {
-      await logError(`[writeInstalledMap] failed: ${e?.message || e}`);
-    } catch {}
-  }
-  return map;
-}
-
-// 向 installed.json 添加 ID 和版本
-async function addInstalledId(id, version = '') {
-  try {
-    const { invoke } = await import('@tauri-apps/api/core');
-    await invoke('add_installed_id_cmd', { id, version: String(version || '') });
-  } catch (e) {
-    try {
-      await logError(`[addInstalledId] invoke failed, fallback to file: ${e?.message || e}`);
-    } catch {}
-  }
-}
-
-// 从 installed.json 删除指定 ID
+// 从已安装状态中删除指定 ID
 export async function removeInstalledId(id) {
   try {
     const { invoke } = await import('@tauri-apps/api/core');
@@ -364,7 +338,8 @@ export async function removeInstalledId(id) {
   }
 }
 
-// 将检测到的已安装包保存到 installed.json
+// 将检测到的版本保存到已安装状态，返回更新后的兼容视图
+// 未检测到的包中，有 manifest、固定或备注的会保留
 export async function saveInstalledSnapshot(detectedMap) {
   const snapshot = {};
   if (detectedMap && typeof detectedMap === 'object') {
@@ -372,8 +347,15 @@ export async function saveInstalledSnapshot(detectedMap) {
       if (ver) snapshot[id] = String(ver);
     }
   }
-  await writeInstalledMap(snapshot);
-  return snapshot;
+  try {
+    const { invoke } = await import('@tauri-apps/api/core');
+    return await invoke('save_installed_snapshot', { detected: snapshot });
+  } catch (e) {
+    try {
+      await logError(`[saveInstalledSnapshot] failed: ${e?.message || e}`);
+    } catch {}
+    return snapshot;
+  }
 }
 
 // 估计最新版本的函数
@@ -860,21 +842,6 @@ async function ensureAviutlClosed() {
   }
 }
 
-// 在字符串宏中填入实际值
-async function expandMacros(s, ctx) {
-  const { invoke } = await import('@tauri-apps/api/core');
-  const dirs = await invoke('get_app_dirs');
-  //   logInfo(`get_app_dirs: ${JSON.stringify(dirs)}`);
-  if (typeof s !== 'string') return s;
-  return s
-    .replaceAll('{tmp}', ctx.tmpDir)
-    .replaceAll('{appDir}', dirs.aviutl2_root || '')
-    .replaceAll('{pluginsDir}', dirs.plugin_dir || '')
-    .replaceAll('{scriptsDir}', dirs.script_dir || '')
-    .replaceAll('{dataDir}', dirs.aviutl2_data || '')
-    .replaceAll('{download}', ctx.downloadPath || '');
-}
-
 // 创建安装程序处理的临时工作目录
 // 创建安装程序处理的临时工作目录
 async function ensureTmpDir(idVersion) {
@@ -896,132 +863,6 @@ async function ensureTmpDir(idVersion) {
   return absPath;
 }
 
-// -------------------------
-// exe 执行处理
-// -------------------------
-
-function psEscape(s) {
-  return String(s).replace(/'/g, "''");
-}
-
-function toPowerShellEncodedCommand(script) {
-  const s = String(script || '');
-  let binary = '';
-  for (let i = 0; i < s.length; i++) {
-    const codeUnit = s.charCodeAt(i);
-    binary += String.fromCharCode(codeUnit & 0xff, codeUnit >> 8);
-  }
-  if (typeof btoa === 'function') return btoa(binary);
-  if (typeof Buffer !== 'undefined') return Buffer.from(binary, 'binary').toString('base64');
-  throw new Error('Failed to encode PowerShell command to Base64');
-}
-
-// 以隐藏窗口执行可执行文件的函数
-async function runInstaller(exeAbsPath, args = [], elevate = false, _tmpPath) {
-  const shell = await import('@tauri-apps/plugin-shell');
-  const argList = (args || []).map((a) => `'${psEscape(a)}'`).join(', ');
-  const argClause = args && args.length > 0 ? ` -ArgumentList @(${argList})` : '';
-  const body = [
-    "$ErrorActionPreference='Stop'",
-    '[Console]::OutputEncoding=[System.Text.UTF8Encoding]::new()',
-    `$p = Start-Process -FilePath '${psEscape(exeAbsPath)}'${argClause}${elevate ? ' -Verb RunAs' : ''} -WindowStyle Hidden -Wait -PassThru`,
-    'exit ($p.ExitCode)',
-  ].join('\n');
-  const encodedCommand = toPowerShellEncodedCommand(body);
-  const argsPs = [
-    '-ExecutionPolicy',
-    'Bypass',
-    '-NoLogo',
-    '-NoProfile',
-    '-NonInteractive',
-    '-EncodedCommand',
-    encodedCommand,
-  ];
-  const cmd = shell.Command.create('powershell', argsPs, { encoding: 'utf-8' });
-  const res = await cmd.execute();
-  if (res.code !== 0) {
-    throw new Error(
-      `runExecutableQuietWindows failed (exe=${exeAbsPath}, args=${JSON.stringify(args)}, elevate=${!!elevate}) exit=${res.code}, stderr=${(res.stderr || '').slice(0, 500)}`,
-    );
-  }
-}
-
-async function runAuoSetup(exeAbsPath) {
-  try {
-    const { invoke } = await import('@tauri-apps/api/core');
-    const _result = await invoke('run_auo_setup', { exePath: exeAbsPath });
-  } catch (e) {
-    logError(`[runAuoSetup] failed exe=${exeAbsPath}: ${e}`);
-    throw e; // ← 将 Rust 的错误抛给调用方
-  }
-}
-
-// 从安装程序生成下载URL
-// 获取 GitHub 最新发布的下载URL
-async function fetchGitHubURL(github) {
-  const http = await import('@tauri-apps/plugin-http');
-  const { owner, repo, pattern } = github;
-  const regex = pattern ? new RegExp(pattern) : null;
-
-  // 从发布中选择目标资产
-  const pickAssetFromRelease = (release) => {
-    if (!release || !Array.isArray(release.assets)) return null;
-    if (regex) {
-      const matched = release.assets.find((a) => regex.test(a.name || ''));
-      if (matched) return matched;
-    }
-    return release.assets[0] || null;
-  };
-
-  // 遍历发布列表，返回"最后更新时间最新的资产"
-  const findLatestUpdatedAsset = (releases) => {
-    if (!Array.isArray(releases) || !releases.length) return null;
-    let best = null;
-    let bestTs = -Infinity;
-    for (const rel of releases) {
-      const assets = Array.isArray(rel?.assets) ? rel.assets : [];
-      for (const asset of assets) {
-        if (regex && !regex.test(asset?.name || '')) continue;
-        // 比较 asset.updated_at/created_at，如果没有则使用 release 的日期
-        const ts =
-          Date.parse(asset?.updated_at || asset?.created_at || rel?.published_at || rel?.created_at || '') || 0;
-        if (ts > bestTs) {
-          best = asset;
-          bestTs = ts;
-        }
-      }
-    }
-    return best;
-  };
-
-  try {
-    // 尝试从 latest API 获取
-    const res = await http.fetch(`https://api.github.com/repos/${owner}/${repo}/releases/latest`);
-    const data = await res.json().catch(() => ({}));
-    const asset = pickAssetFromRelease(data);
-    if (asset?.browser_download_url) {
-      return asset.browser_download_url;
-    }
-  } catch (e) {
-    try {
-      await logError(`[fetchGitHubAsset] fetch latest failed: ${e?.message || e}`);
-    } catch {}
-  }
-
-  try {
-    // 如果没有 latest，则从发布列表中选择最后更新的资产
-    const res = await http.fetch(`https://api.github.com/repos/${owner}/${repo}/releases?per_page=30`);
-    const list = await res.json().catch(() => []);
-    const asset = findLatestUpdatedAsset(list);
-    return asset?.browser_download_url || '';
-  } catch (e) {
-    try {
-      await logError(`[fetchGitHubAsset] fetch failed: ${e?.message || e}`);
-    } catch {}
-    return '';
-  }
-}
-
 // 判断是否为绝对路径
 function isAbsPath(p) {
   return /^(?:[a-zA-Z]:[\\/]|\\\\|\/)/.test(String(p || ''));
@@ -1035,82 +876,45 @@ function ensureAbsolutePath(p, label) {
   return s;
 }
 
-async function deletePath(absPath) {
-  const fs = await import('@tauri-apps/plugin-fs');
-  let ok = false;
-  let lastErr = null;
-  try {
-    // 确认存在
-    const exists = await fs.exists(absPath);
-    if (!exists) {
-      return false; // 不存在的情况
-    }
-    // 尝试删除（包括目录，递归）
-    try {
-      await fs.remove(absPath, { recursive: true });
-      ok = true;
-    } catch {
-      // 一旦失败，通过 stat 确认类型并重试删除
-      try {
-        const st = await fs.stat(absPath);
-        if (st.isDirectory) {
-          await fs.remove(absPath, { recursive: true });
-        } else {
-          await fs.remove(absPath);
-        }
-        ok = true;
-      } catch (e) {
-        lastErr = e;
-      }
-    }
-    if (ok) return true;
-  } catch (e) {
-    lastErr = e;
-  }
-  // 如果无法删除则抛出错误
-  if (!ok) throw lastErr || new Error('remove failed');
-  return ok;
+// 生成下载任务 ID
+function newDownloadTaskId() {
+  return typeof crypto !== 'undefined' && typeof crypto.randomUUID === 'function'
+    ? crypto.randomUUID()
+    : `dl-${Date.now()}-${Math.random().toString(16).slice(2)}`;
 }
 
-// 文件下载（通过 Rust）
-export async function downloadFileFromUrl(url, destPath, options = {}) {
-  if (!/^https:\/\//i.test(url)) throw new Error(`Only https:// is allowed (got: ${url})`);
-  if (typeof destPath !== 'string' || !destPath.trim()) throw new Error('destPath must be an existing directory');
-
+// 调用 Rust 侧下载命令，并按 taskId 监听 download:progress
+async function invokeDownloadWithProgress(command, args, options = {}) {
   const { invoke } = await import('@tauri-apps/api/core');
   const { listen } = await import('@tauri-apps/api/event');
 
   const onProgress = typeof options.onProgress === 'function' ? options.onProgress : null;
-  const taskId =
-    options.taskId ||
-    (typeof crypto !== 'undefined' && typeof crypto.randomUUID === 'function'
-      ? crypto.randomUUID()
-      : `dl-${Date.now()}-${Math.random().toString(16).slice(2)}`);
+  const taskId = options.taskId || newDownloadTaskId();
 
   const unlisteners = [];
-  const registerListener = async (eventName, handler) => {
-    const unlisten = await listen(eventName, (evt) => {
+  if (onProgress) {
+    const unlisten = await listen('download:progress', (evt) => {
       const payload = evt?.payload;
       if (!payload || payload.taskId !== taskId) return;
-      handler(payload);
-    });
-    unlisteners.push(unlisten);
-  };
-
-  if (onProgress) {
-    await registerListener('download:progress', (payload) => {
       const read = typeof payload.read === 'number' ? payload.read : 0;
       const total = typeof payload.total === 'number' ? payload.total : null;
-      onProgress({ read, total });
+      onProgress({ read, total, taskId });
     });
+    unlisteners.push(unlisten);
+  }
+  if (typeof options.onRetry === 'function') {
+    // 暂时性错误的重试通知（attempt: 已失败的次数，delayMs: 下次重试前的等待时间）
+    const onRetry = options.onRetry;
+    const unlisten = await listen('download:retry', (evt) => {
+      const payload = evt?.payload;
+      if (!payload || payload.taskId !== taskId) return;
+      onRetry({ attempt: payload.attempt, delayMs: payload.delayMs, message: payload.message, taskId });
+    });
+    unlisteners.push(unlisten);
   }
 
   try {
-    const finalPath = await invoke('download_file_to_path', { url, destPath, taskId });
-    return finalPath;
-  } catch (e) {
-    const detail = e?.message || (typeof e === 'object' ? JSON.stringify(e) : String(e)) || 'unknown error';
-    throw new Error(`downloadFileFromUrl failed (url=${url}): ${detail}`, { cause: e });
+    return await invoke(command, { ...args, taskId });
   } finally {
     for (const unlisten of unlisteners) {
       try {
@@ -1120,6 +924,73 @@ export async function downloadFileFromUrl(url, destPath, options = {}) {
   }
 }
 
+// 解析 GitHub 发布中的资产（Rust 侧带 ETag 缓存和令牌支持）
+// 失败时抛出的错误为 { code, message, rateLimit }
+export async function resolveGitHubAsset(github) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  const { owner, repo, pattern } = github || {};
+  return await invoke('resolve_github_asset', { owner, repo, pattern: pattern || null });
+}
+
+// 取消下载或解压（下载会删除 .part 并发送 download:cancelled，解压会删除已写出的文件）
+export async function cancelTask(taskId) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('cancel_task', { taskId });
+}
+
+// 取消下载（Rust 侧会删除 .part 并发送 download:cancelled）
+export async function cancelDownload(taskId) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('cancel_download', { taskId });
+}
+
+// 暂停下载（保留 .part，恢复时续传；解压等无法暂停的任务返回 false）
+export async function pauseDownload(taskId) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('pause_download', { taskId });
+}
+
+// 恢复已暂停的下载
+export async function resumeDownload(taskId) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('resume_download', { taskId });
+}
+
+// 下载包缓存：列表（{ dir, totalSize, limit, entries }）
+export async function listPackageCache() {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('cache_list');
+}
+
+// 下载包缓存：从最久未使用的开始删除，直到不超过 maxBytes（省略时为设置的上限，0 为全部删除）
+export async function prunePackageCache(maxBytes) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('cache_prune', { maxBytes: maxBytes == null ? null : Number(maxBytes) });
+}
+
+// 下载包缓存：导出到指定文件夹（附带 cache-index.json），返回导出的数量
+export async function exportPackageCache(destPath) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return await invoke('cache_export', { destPath: String(destPath || '') });
+}
+
+// 文件下载（通过 Rust）
+export async function downloadFileFromUrl(url, destPath, options = {}) {
+  if (!/^https:\/\//i.test(url)) throw new Error(`Only https:// is allowed (got: ${url})`);
+  if (typeof destPath !== 'string' || !destPath.trim()) throw new Error('destPath must be an existing directory');
+
+  try {
+    return await invokeDownloadWithProgress(
+      'download_file_to_path',
+      { url, destPath, expected: options.expected ?? null },
+      options,
+    );
+  } catch (e) {
+    const detail = e?.message || (typeof e === 'object' ? JSON.stringify(e) : String(e)) || 'unknown error';
+    throw new Error(`downloadFileFromUrl failed (url=${url}): ${detail}`, { cause: e });
+  }
+}
+
 // -------------------------
 // BOOTH 认证窗口管理・下载
 // -------------------------
@@ -1155,106 +1026,85 @@ async function prepareBoothLoginWait() {
   return done;
 }
 
-// BOOTH 直链下载（通过 Rust + Cookie）
-// 文件下载（从 BOOTH）
-export async function downloadFileFromBoothUrl(url, destPath, options = {}) {
-  const { invoke } = await import('@tauri-apps/api/core');
-  const { listen } = await import('@tauri-apps/api/event');
-
-  const onProgress = typeof options.onProgress === 'function' ? options.onProgress : null;
-  // 确定任务ID
-  const taskId =
-    options.taskId ||
-    (typeof crypto !== 'undefined' && typeof crypto.randomUUID === 'function'
-      ? crypto.randomUUID()
-      : `dl-${Date.now()}-${Math.random().toString(16).slice(2)}`);
-
-  const unlisteners = [];
-  const registerListener = async (eventName, handler) => {
-    const unlisten = await listen(eventName, (evt) => {
-      const payload = evt?.payload;
-      if (!payload || payload.taskId !== taskId) return;
-      handler(payload);
-    });
-    unlisteners.push(unlisten);
-  };
-
-  if (onProgress) {
-    await registerListener('download:progress', (payload) => {
-      const read = typeof payload.read === 'number' ? payload.read : 0;
-      const total = typeof payload.total === 'number' ? payload.total : null;
-      onProgress({ read, total });
-    });
-  }
-
-  // 调用 Rust 侧的 BOOTH 下载
-  const invokeDownload = () =>
-    invoke('download_file_to_path_booth', {
-      url,
-      destPath,
-      taskId,
-      sessionWindowLabel: BOOTH_AUTH_WINDOW_LABEL,
-    });
-
-  try {
-    for (let attempt = 0; attempt < 2; attempt++) {
-      try {
-        const finalPath = await invokeDownload();
-        return finalPath;
-      } catch (e) {
-        const detail = e?.message || (typeof e === 'object' ? JSON.stringify(e) : String(e)) || 'unknown error';
-        const needsAuth = detail.includes('AUTH_REQUIRED') || detail.includes('AUTH_WINDOW_MISSING');
-        if (needsAuth && attempt === 0) {
-          // 仅未登录时，等待登录完成事件并重试
-          const waitLogin = prepareBoothLoginWait();
-          await ensureBoothAuthWindow();
-          await waitLogin;
-          continue;
-        }
-        throw new Error(`downloadFileFromBoothUrl failed (url=${url}): ${detail}`, { cause: e });
+// 需要 BOOTH 登录时，等待登录完成后重试一次
+async function withBoothLoginRetry(label, run) {
+  for (let attempt = 0; attempt < 2; attempt++) {
+    try {
+      return await run();
+    } catch (e) {
+      const detail = e?.message || (typeof e === 'object' ? JSON.stringify(e) : String(e)) || 'unknown error';
+      const needsAuth = detail.includes('AUTH_REQUIRED') || detail.includes('AUTH_WINDOW_MISSING');
+      if (needsAuth && attempt === 0) {
+        // 仅未登录时，等待登录完成事件并重试
+        const waitLogin = prepareBoothLoginWait();
+        await ensureBoothAuthWindow();
+        await waitLogin;
+        continue;
       }
-    }
-    throw new Error(`downloadFileFromBoothUrl failed (url=${url}): AUTH_REQUIRED`);
-  } finally {
-    for (const unlisten of unlisteners) {
-      try {
-        unlisten();
-      } catch {}
+      throw new Error(`${label}: ${detail}`, { cause: e });
     }
   }
+  throw new Error(`${label}: AUTH_REQUIRED`);
 }
 
-// 解压 ZIP 文件（Rust）
-async function extractZip(zipPath, destPath) {
+// BOOTH 直链下载（通过 Rust + Cookie）
+// 文件下载（从 BOOTH）
+export async function downloadFileFromBoothUrl(url, destPath, options = {}) {
+  // 调用 Rust 侧的 BOOTH 下载
+  return await withBoothLoginRetry(`downloadFileFromBoothUrl failed (url=${url})`, () =>
+    invokeDownloadWithProgress(
+      'download_file_to_path_booth',
+      { url, destPath, sessionWindowLabel: BOOTH_AUTH_WINDOW_LABEL, expected: options.expected ?? null },
+      options,
+    ),
+  );
+}
+
+// 根据 installer.source（direct / github / GoogleDrive / booth）下载（通过 Rust）
+export async function downloadFromSource(source, destPath, options = {}) {
+  if (!source) throw new Error('Download source is not specified');
+  const run = () =>
+    invokeDownloadWithProgress(
+      'download_from_source',
+      { source, destPath, sessionWindowLabel: BOOTH_AUTH_WINDOW_LABEL },
+      options,
+    );
+  if (typeof source.booth === 'string' && source.booth) {
+    return await withBoothLoginRetry(`downloadFromSource failed (booth=${source.booth})`, run);
+  }
   try {
-    const { invoke } = await import('@tauri-apps/api/core');
-    await invoke('extract_zip', { zipPath, destPath });
-    return;
+    return await run();
   } catch (e) {
-    try {
-      await logError(`[extractZip] failed: ${e?.message || e}`);
-    } catch {}
+    const detail = e?.message || (typeof e === 'object' ? JSON.stringify(e) : String(e)) || 'unknown error';
+    throw new Error(`downloadFromSource failed: ${detail}`, { cause: e });
   }
 }
 
-// 解压 7-Zip SFX（自解压）数据 (Rust)
-async function extractSevenZipSfx(sfxPath, destPath) {
-  try {
-    const { invoke } = await import('@tauri-apps/api/core');
-    // const base = (!isAbsPath(sfxPath) && !isAbsPath(destPath)) ? 'AppConfig' : null;
-    await invoke('extract_7z_sfx', { sfxPath, destPath });
-    return;
-  } catch (e) {
-    try {
-      await logError(`[extractSevenZipSfx] failed: ${e?.message || e}`);
-    } catch {}
+// 将 Rust 侧的解压错误（{ code, message, entries }）转换为 Error
+// code 和 entries（被拒绝的条目及原因）保留在 Error 上，供调用方显示
+function toExtractError(e) {
+  const message = e?.message || (typeof e === 'string' ? e : JSON.stringify(e)) || 'unknown error';
+  const err = new Error(message, { cause: e });
+  if (e && typeof e === 'object') {
+    err.code = e.code;
+    err.entries = Array.isArray(e.entries) ? e.entries : [];
   }
+  return err;
 }
 
-// 文件复制处理函数(Rust)
-async function copyPattern(fromPattern, toDirRel) {
+// 列出压缩包内容（zip / 7z）：[{ path, isDir, size, compressedSize, mtime, crc32 }]
+// 供登记页面确认 extract 步骤的 include / strip 是否正确
+export async function listArchive(archivePath, options = {}) {
   const { invoke } = await import('@tauri-apps/api/core');
-  return await invoke('copy_item_js', { srcStr: fromPattern, dstStr: toDirRel });
+  try {
+    return await invoke('list_archive', {
+      archivePath,
+      encoding: options.encoding || null,
+      password: options.password || null,
+    });
+  } catch (e) {
+    throw toExtractError(e);
+  }
 }
 
 // 判断安装程序是否存在
@@ -1263,6 +1113,18 @@ export function hasInstaller(item) {
   return !!(item && item.installer && (typeof item.installer === 'string' || Array.isArray(item.installer.install)));
 }
 
+// 是否可以卸载（有 installer.uninstall，或安装时记录了 manifest）
+export async function canUninstallItem(item) {
+  if (!hasInstaller(item)) return false;
+  if (Array.isArray(item?.installer?.uninstall) && item.installer.uninstall.length > 0) return true;
+  try {
+    const { invoke } = await import('@tauri-apps/api/core');
+    return !!(await invoke('has_package_manifest', { id: item.id }));
+  } catch {
+    return false;
+  }
+}
+
 // -------------------------
 // 安装程序&卸载程序的执行
 // -------------------------
@@ -1277,8 +1139,6 @@ const STEP_PROGRESS_LABELS = {
   run_auo_setup: '执行中',
 };
 
-const STEP_PROGRESS_OFFSET = 0;
-
 const TEST_OPERATION_LABELS = {
   download: '下载',
   extract: '解压',
@@ -1300,217 +1160,196 @@ function toTestOperationKind(action) {
   return 'error';
 }
 
-// 安装执行
-export async function runInstallerForItem(item, dispatch, onProgress) {
-  await ensureAviutlClosed();
-  // 构建执行上下文
+// 安装用临时目录名（id-版本，与 Rust 侧 install_package 的作业文件夹相同，预下载的文件放在这里）
+function installerTmpKey(item) {
   const version = item['latest-version'];
-  // logInfo(`item=${JSON.stringify(item)}, version=${version}`);
-  const idVersion = `${item.id}-${version || 'latest'}`.replace(/[^A-Za-z0-9._-]/g, '_');
-  const tmpDir = await ensureTmpDir(idVersion);
+  return `${item.id}-${version || 'latest'}`.replace(/[^A-Za-z0-9._-]/g, '_');
+}
 
-  const ctx = {
-    tmpDir: tmpDir,
-    downloadPath: '', // 下载时设置
-  };
-  const steps = Array.isArray(item?.installer?.install) ? item.installer.install : [];
-  const totalSteps = steps.length;
-
-  const buildProgressPayload = (completedUnits, step, index, phase) => {
-    const safeUnits = Number.isFinite(completedUnits) ? completedUnits : 0;
-    const ratio = totalSteps <= 0 ? (phase === 'done' ? 1 : 0) : Math.min(1, Math.max(0, safeUnits / totalSteps));
-    const label = (() => {
-      if (phase === 'done') return '完成';
-      if (phase === 'init') return '准备中…';
-      if (phase === 'error') return '发生错误';
-      const action = step?.action;
-      return STEP_PROGRESS_LABELS[action] || '处理中…';
-    })();
-    return {
-      ratio,
-      percent: Math.round(ratio * 100),
-      step: step?.action ?? null,
-      stepIndex: Number.isInteger(index) && index >= 0 ? index : null,
-      totalSteps,
-      label,
-      phase,
-    };
-  };
+// 批量预下载安装包（Rust 侧队列按设置的同时下载数 / 带宽上限并行执行）
+// 返回 Map<itemId, string>（下载后的文件路径），失败的包不包含在内（安装时会重新下载）
+export async function prefetchInstallerDownloads(items, onQueueProgress) {
+  const { listen } = await import('@tauri-apps/api/event');
+  const jobs = (Array.isArray(items) ? items : [])
+    .filter((item) => item?.installer?.source && (item?.installer?.install || []).some((s) => s?.action === 'download'))
+    .map((item) => ({ item, taskId: newDownloadTaskId() }));
+  const itemByTask = new Map(jobs.map((job) => [job.taskId, job.item]));
+  let finished = 0;
+
+  let unlisten = null;
+  if (typeof onQueueProgress === 'function') {
+    unlisten = await listen('download:queue', (evt) => {
+      const payload = evt?.payload;
+      if (!payload || !itemByTask.has(payload.taskId)) return;
+      if (payload.state === 'done') finished++;
+      try {
+        onQueueProgress({
+          item: itemByTask.get(payload.taskId),
+          state: payload.state,
+          queued: payload.queued,
+          active: payload.active,
+          done: finished,
+          total: jobs.length,
+        });
+      } catch {}
+    });
+  }
 
-  const emitProgress = (completedUnits, step, index, phase) => {
-    if (typeof onProgress !== 'function') return;
-    try {
-      onProgress(buildProgressPayload(completedUnits, step, index, phase));
-    } catch {
-      // UI 侧的异常被忽略
+  const results = new Map();
+  try {
+    await Promise.all(
+      jobs.map(async ({ item, taskId }) => {
+        try {
+          const tmpDir = await ensureTmpDir(installerTmpKey(item));
+          const path = await downloadFromSource(item.installer.source, tmpDir, { taskId });
+          results.set(item.id, path);
+        } catch (e) {
+          try {
+            await logError(`[prefetch ${item.id}] ${e?.message || e}`);
+          } catch {}
+        }
+      }),
+    );
+  } finally {
+    if (unlisten) {
+      try {
+        unlisten();
+      } catch {}
     }
+  }
+  return results;
+}
+
+// 加密压缩包的密码错误
+const EXTRACT_PASSWORD_CODES = ['EXTRACT_PASSWORD_REQUIRED', 'EXTRACT_PASSWORD_INCORRECT'];
+// 需要 BOOTH 登录的下载错误
+const BOOTH_AUTH_CODES = ['AUTH_REQUIRED', 'AUTH_WINDOW_MISSING'];
+
+// 默认的密码输入（调用方可通过 options.requestPassword 换成自己的对话框）；取消时返回 null
+async function promptArchivePassword({ item, incorrect }) {
+  const name = item?.name || item?.id || '';
+  const message = incorrect
+    ? `「${name}」的压缩包密码不正确，请重新输入：`
+    : `「${name}」的压缩包已加密，请输入密码（可在购买页面确认）：`;
+  const value = window.prompt(message, '');
+  return value ? value : null;
+}
+
+// 将 Rust 侧安装 / 卸载的错误（{ code, message, step, action, downloadPath, cause }）转换为 Error
+// cause 中的 entries（被拒绝的解压条目）和 files（内容不同的已有文件）也保留在 Error 上，供调用方显示
+function toInstallError(e) {
+  const message = e?.message || (typeof e === 'string' ? e : JSON.stringify(e)) || 'unknown error';
+  const err = new Error(message, { cause: e });
+  if (e && typeof e === 'object') {
+    err.code = e.code;
+    err.packageId = e.packageId || '';
+    err.step = e.step;
+    err.action = e.action;
+    err.downloadPath = e.downloadPath || '';
+    err.entries = Array.isArray(e.cause?.entries) ? e.cause.entries : [];
+    err.files = Array.isArray(e.cause?.files) ? e.cause.files : [];
+  }
+  return err;
+}
+
+// 将 install:progress 转换为 UI 用的进度（附加显示文本和 downloadTaskId）
+function toInstallProgress(payload, downloadTaskId) {
+  const ratio = Number.isFinite(payload?.ratio) ? payload.ratio : 0;
+  const phase = payload?.phase || 'running';
+  const label = (() => {
+    if (phase === 'done') return '完成';
+    if (phase === 'init') return '准备中…';
+    if (phase === 'error') return '发生错误';
+    return STEP_PROGRESS_LABELS[payload?.step] || '处理中…';
+  })();
+  return {
+    ratio,
+    percent: Math.round(ratio * 100),
+    step: payload?.step ?? null,
+    stepIndex: Number.isInteger(payload?.stepIndex) ? payload.stepIndex : null,
+    totalSteps: payload?.totalSteps || 0,
+    packageId: payload?.packageId || '',
+    label,
+    phase,
+    downloadTaskId,
   };
+}
+
+// 调用 install_package / install_plan / uninstall_package，并按 taskId 转发 install:progress
+// args 为命令参数（{ package, options } 或 { packages, options }）
+async function invokeInstallerCommand(command, args, onProgress) {
+  const { options } = args;
+  const { invoke } = await import('@tauri-apps/api/core');
+  let unlisten = null;
+  if (typeof onProgress === 'function') {
+    const { listen } = await import('@tauri-apps/api/event');
+    unlisten = await listen('install:progress', (evt) => {
+      const payload = evt?.payload;
+      if (!payload || payload.taskId !== options.taskId) return;
+      try {
+        onProgress(toInstallProgress(payload, options.taskId));
+      } catch {
+        // UI 侧的异常被忽略
+      }
+    });
+  }
+  try {
+    return await invoke(command, args);
+  } catch (e) {
+    throw toInstallError(e);
+  } finally {
+    if (unlisten) {
+      try {
+        unlisten();
+      } catch {}
+    }
+  }
+}
 
-  emitProgress(0, null, -1, 'init');
+// 安装执行（各步骤由 Rust 侧的 install_package 执行，日志和已安装状态的更新也在 Rust 侧进行）
+// options.downloadPath: 已预下载的文件路径（指定时跳过 download 步骤的下载）
+// options.requestPassword: 压缩包加密时的密码输入（({ item, incorrect }) => 密码 | null，省略时使用 window.prompt）
+export async function runInstallerForItem(item, dispatch, onProgress, options = {}) {
+  await ensureAviutlClosed();
+  const steps = Array.isArray(item?.installer?.install) ? item.installer.install : [];
+  const pkg = {
+    id: item.id,
+    version: String(item['latest-version'] || ''),
+    source: item?.installer?.source ?? null,
+    steps,
+  };
+  // taskId 同时作为 downloadTaskId 交给 UI（下载和解压中都用于 cancelTask，下载中还用于 pauseDownload）
+  const installOptions = { taskId: newDownloadTaskId(), downloadPath: options.downloadPath || null, password: null };
+  const requestPassword = typeof options.requestPassword === 'function' ? options.requestPassword : promptArchivePassword;
 
+  let boothLoginWaited = false;
   try {
-    await logInfo(`[installer ${item.id}] start version=${version || ''} steps=${steps.length}`);
-    for (let idx = 0; idx < steps.length; idx++) {
-      const step = steps[idx];
-      const runningUnits = idx;
-      emitProgress(runningUnits, step, idx, 'running');
+    for (;;) {
       try {
-        switch (step.action) {
-          case 'download': {
-            const src = item?.installer?.source;
-            if (!src) throw new Error(`Download source is not specified`);
-            // 1. Google Drive 下载
-            if (src.GoogleDrive && typeof src.GoogleDrive.id === 'string' && src.GoogleDrive.id) {
-              const fileId = src.GoogleDrive.id;
-              const stepSpan = 1 - STEP_PROGRESS_OFFSET;
-              const startUnits = runningUnits;
-              const maxUnits = idx + 1 - 0.01;
-              let unknownUnits = startUnits;
-              const unlisteners = [];
-              if (typeof onProgress === 'function') {
-                const { listen } = await import('@tauri-apps/api/event');
-                const register = async (eventName, handler) => {
-                  const unlisten = await listen(eventName, (evt) => {
-                    const payload = evt?.payload;
-                    if (!payload || payload.fileId !== fileId) return;
-                    handler(payload);
-                  });
-                  unlisteners.push(unlisten);
-                };
-                await register('drive:progress', (payload) => {
-                  const read = typeof payload.read === 'number' ? payload.read : 0;
-                  const total = typeof payload.total === 'number' ? payload.total : null;
-                  if (typeof total === 'number' && total > 0) {
-                    const ratio = Math.min(1, Math.max(0, total ? read / total : 0));
-                    const units = startUnits + stepSpan * ratio;
-                    emitProgress(units, step, idx, 'running');
-                  } else if (typeof read === 'number' && read > 0) {
-                    const increment = stepSpan * 0.05;
-                    unknownUnits = Math.min(maxUnits, unknownUnits + increment);
-                    emitProgress(unknownUnits, step, idx, 'running');
-                  }
-                });
-              }
-              try {
-                const { invoke } = await import('@tauri-apps/api/core');
-                await invoke('drive_download_to_file', { fileId, destPath: tmpDir });
-              } finally {
-                for (const unlisten of unlisteners) {
-                  try {
-                    unlisten();
-                  } catch {}
-                }
-              }
-              ctx.downloadPath = tmpDir;
-              logInfo(`[installer ${item.id}] downloading from Google Drive fileId=${src.GoogleDrive.id} to ${tmpDir}`);
-              break;
-            }
-            // 2. BOOTH 直链的情况
-            if (typeof src.booth === 'string' && src.booth) {
-              const boothUrl = src.booth;
-              logInfo(`[installer ${item.id}] downloading from BOOTH ${boothUrl} to ${tmpDir}`);
-              const stepSpan = 1 - STEP_PROGRESS_OFFSET;
-              const startUnits = runningUnits;
-              const maxUnits = idx + 1 - 0.01;
-              let unknownUnits = startUnits;
-              ctx.downloadPath = await downloadFileFromBoothUrl(boothUrl, tmpDir, {
-                onProgress: ({ read, total }) => {
-                  if (typeof total === 'number' && total > 0) {
-                    const ratio = Math.min(1, Math.max(0, total ? read / total : 0));
-                    const units = startUnits + stepSpan * ratio;
-                    emitProgress(units, step, idx, 'running');
-                  } else if (typeof read === 'number' && read > 0) {
-                    const increment = stepSpan * 0.05;
-                    unknownUnits = Math.min(maxUnits, unknownUnits + increment);
-                    emitProgress(unknownUnits, step, idx, 'running');
-                  }
-                },
-              });
-              break;
-            }
-            let url = '';
-            // 3. GitHub 的情况
-            if (src.github && src.github.owner && src.github.repo) {
-              url = await fetchGitHubURL(src.github);
-            }
-            // 4. 直接 URL 的情况
-            if (typeof src.direct === 'string' && src.direct) {
-              url = src.direct;
-            }
-            if (!url) throw new Error('Download source is not specified');
-            logInfo(`[installer ${item.id}] downloading from ${url} to ${tmpDir}`);
-            const stepSpan = 1 - STEP_PROGRESS_OFFSET;
-            const startUnits = runningUnits;
-            const maxUnits = idx + 1 - 0.01;
-            let unknownUnits = startUnits;
-            ctx.downloadPath = await downloadFileFromUrl(url, tmpDir, {
-              onProgress: ({ read, total }) => {
-                if (typeof total === 'number' && total > 0) {
-                  const ratio = Math.min(1, Math.max(0, total ? read / total : 0));
-                  const units = startUnits + stepSpan * ratio;
-                  emitProgress(units, step, idx, 'running');
-                } else if (typeof read === 'number' && read > 0) {
-                  const increment = stepSpan * 0.05;
-                  unknownUnits = Math.min(maxUnits, unknownUnits + increment);
-                  emitProgress(unknownUnits, step, idx, 'running');
-                }
-              },
-            });
-            break;
-          }
-          case 'extract': {
-            const fromRel = await expandMacros(step.from || ctx.downloadPath, ctx);
-            const toRel = await expandMacros(step.to || `{tmp}`, ctx);
-            logInfo(`[installer ${item.id}] extracting from ${fromRel} to ${toRel}`);
-            await extractZip(fromRel, toRel);
-            break;
-          }
-          case 'extract_sfx': {
-            const fromRel = await expandMacros(step.from || ctx.downloadPath, ctx);
-            const toRel = await expandMacros(step.to || `{tmp}`, ctx);
-            logInfo(`[installer ${item.id}] extracting SFX from ${fromRel} to ${toRel}`);
-            await extractSevenZipSfx(fromRel, toRel);
-            break;
-          }
-          case 'copy': {
-            const from = await expandMacros(step.from, ctx);
-            const to = await expandMacros(step.to, ctx);
-            const count = await copyPattern(from, to);
-            logInfo(`[installer ${item.id}] copy matched ${count} files (from=${from} to=${to})`);
-            if (count === 0) {
-              throw new Error(`copy matched 0 files (from=${from} to=${to})`);
-            }
-            break;
-          }
-          // 针对 aviutl2 本体的安装
-          case 'run': {
-            const pRaw = await expandMacros(step.path, ctx);
-            const args = await Promise.all((step.args || []).map((a) => expandMacros(String(a), ctx)));
-            await runInstaller(pRaw, args, !!step.elevate, ctx.tmpDir);
-            break;
-          }
-          case 'run_auo_setup': {
-            const pRaw = await expandMacros(step.path, ctx);
-            await runAuoSetup(pRaw);
-            break;
-          }
-          default:
-            throw new Error(`unsupported action: ${String(step.action)}`);
+        await invokeInstallerCommand('install_package', { package: pkg, options: installOptions }, onProgress);
+        break;
+      } catch (err) {
+        // 压缩包加密时要求输入密码，并用已下载的文件重新执行；取消输入时以原来的错误失败
+        if (EXTRACT_PASSWORD_CODES.includes(err.code)) {
+          const password = await requestPassword({ item, incorrect: err.code === 'EXTRACT_PASSWORD_INCORRECT' });
+          if (!password) throw err;
+          installOptions.password = password;
+          installOptions.downloadPath = err.downloadPath || installOptions.downloadPath;
+          continue;
         }
-        emitProgress(idx + 1, step, idx, 'step-complete');
-      } catch (e) {
-        emitProgress(runningUnits, step, idx, 'error');
-        const err = e instanceof Error ? e : new Error(String(e));
-        const prefix = `[installer ${item.id}] step ${idx + 1}/${steps.length} action=${step.action} failed`;
-        try {
-          await logError(`${prefix}:\n${err.message}\n${err.stack ?? '(no stack)'}`);
-        } catch {}
-        throw new Error(`${prefix}: ${err.message}`, { cause: e });
+        // 需要 BOOTH 登录时，等待登录完成后重试一次（中断的下载从 .part 续传）
+        if (BOOTH_AUTH_CODES.includes(err.code) && !boothLoginWaited) {
+          boothLoginWaited = true;
+          const waitLogin = prepareBoothLoginWait();
+          await ensureBoothAuthWindow();
+          await waitLogin;
+          continue;
+        }
+        throw err;
       }
     }
 
-    // 记录为已安装，并更新检测结果以进行最新判定
-    await addInstalledId(item.id, version);
+    // 更新检测结果以进行最新判定
     if (dispatch) {
       const map = await detectInstalledVersionsMap([item]);
       const detected = String((map && map[item.id]) || '');
@@ -1519,82 +1358,120 @@ export async function runInstallerForItem(item, dispatch, onProgress) {
     try {
       await recordPackageStateEvent('install', item.id);
     } catch {}
-    await logInfo(`[installer ${item.id}] completed version=${version || ''}`);
-    emitProgress(totalSteps, null, null, 'done');
-  } catch (e) {
-    const detail = (e && (e.message || (typeof e === 'object' ? JSON.stringify(e) : String(e)))) || 'unknown error';
-    try {
-      await logError(`[installer ${item.id}] error: ${detail}`);
-    } catch {}
-    throw e;
   } finally {
-    // 清理：删除包的临时工作文件夹（开发环境除外）
-    if (!import.meta.env?.DEV) {
-      try {
-        await deletePath(ctx.tmpDir);
-      } catch {}
-    }
     await closeBoothAuthWindow();
   }
 }
 
-// 执行卸载
-export async function runUninstallerForItem(item, dispatch) {
+// 解析依赖，返回包含缺失依赖的安装顺序 { steps: [{ id, requested, requiredBy }], satisfied }（被依赖的包在前）
+// 失败时 err.code 为 DEPENDENCY_CYCLE / DEPENDENCY_UNRESOLVED，详情在 err.cycle / err.unresolved（[{ id, requiredBy, version, reason }]）
+export async function resolveInstallPlan(ids) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  try {
+    return await invoke('resolve_install_plan', { ids });
+  } catch (e) {
+    const err = toInstallError(e);
+    err.cycle = Array.isArray(e?.cycle) ? e.cycle : [];
+    err.unresolved = Array.isArray(e?.unresolved) ? e.unresolved : [];
+    throw err;
+  }
+}
+
+// 按依赖顺序安装 ids 及其缺失的依赖（items 为目录的全部条目，用于按 ID 查找）
+// 整个计划由 Rust 侧的 install_plan 作为一个事务执行，任一包失败时已完成的包也会回滚
+// onProgress 收到的 ratio 为整体进度，并附加 batchIndex / batchTotal / packageId
+// 失败时 err.packageId 为失败的包
+// options.downloadPath: 已预下载的文件路径（仅在只指定一个包时对应该包）
+// options.requestPassword: 同 runInstallerForItem
+export async function runInstallPlan(ids, items, dispatch, onProgress, options = {}) {
+  const plan = await resolveInstallPlan(ids);
+  const idToItem = new Map((Array.isArray(items) ? items : []).map((it) => [String(it.id), it]));
+  const steps = Array.isArray(plan?.steps) ? plan.steps : [];
+  const total = steps.length;
+  const planItems = steps.map((step) => {
+    const item = idToItem.get(step.id);
+    if (!item) throw new Error(`package not found in catalog: ${step.id}`);
+    return item;
+  });
+  if (total === 0) return { plan };
   await ensureAviutlClosed();
-  const version = item['latest-version'];
-  const idVersion = `${item.id}-${version || 'latest'}`.replace(/[^A-Za-z0-9._-]/g, '_');
-  const tmpDir = await ensureTmpDir(idVersion);
-  const ctx = {
-    tmpDir: tmpDir,
-    downloadPath: '',
-  };
+  const packages = planItems.map((item) => ({
+    id: item.id,
+    version: String(item['latest-version'] || ''),
+    source: item?.installer?.source ?? null,
+    steps: Array.isArray(item?.installer?.install) ? item.installer.install : [],
+  }));
+  const planOptions = { taskId: newDownloadTaskId(), downloadPaths: {}, passwords: {} };
+  if (options.downloadPath && ids.length === 1) planOptions.downloadPaths[ids[0]] = options.downloadPath;
+  const requestPassword = typeof options.requestPassword === 'function' ? options.requestPassword : promptArchivePassword;
+  const nameOf = (id) => idToItem.get(id)?.name || id;
+  const batchProgress =
+    typeof onProgress === 'function'
+      ? (progress) => {
+          const index = Math.max(0, steps.findIndex((step) => step.id === progress?.packageId));
+          const name = nameOf(progress?.packageId);
+          const label = total > 1 ? `(${index + 1}/${total}) ${name}: ${progress?.label || ''}` : progress?.label;
+          onProgress({ ...progress, label, batchIndex: index, batchTotal: total });
+        }
+      : undefined;
 
+  let boothLoginWaited = false;
   try {
-    await logInfo(`[uninstall ${item.id}] start steps=${item.installer.uninstall.length}`);
-    // 按顺序执行卸载步骤
-    for (let i = 0; i < item.installer.uninstall.length; i++) {
-      const step = item.installer.uninstall[i];
+    for (;;) {
       try {
-        switch (step.action) {
-          case 'delete': {
-            const p = await expandMacros(step.path, ctx);
-            try {
-              const abs = isAbsPath(p) ? p : p; // 卸载路径原则上是绝对路径。如果是相对路径则直接使用
-              const ok = await deletePath(abs);
-              if (ok) await logInfo(`[uninstall ${item.id}] delete ok path="${p}"`);
-              else await logInfo(`[uninstall ${item.id}] delete skip (not found) path="${p}"`);
-            } catch (e) {
-              throw new Error(`delete failed path=${p}: ${e?.message || e}`, { cause: e });
-            }
-            break;
+        await invokeInstallerCommand('install_plan', { packages, options: planOptions }, batchProgress);
+        break;
+      } catch (err) {
+        // 压缩包加密时要求输入该包的密码，并用已下载的文件重新执行整个计划
+        if (EXTRACT_PASSWORD_CODES.includes(err.code) && idToItem.has(err.packageId)) {
+          const item = idToItem.get(err.packageId);
+          const password = await requestPassword({ item, incorrect: err.code === 'EXTRACT_PASSWORD_INCORRECT' });
+          if (password) {
+            planOptions.passwords[err.packageId] = password;
+            if (err.downloadPath) planOptions.downloadPaths[err.packageId] = err.downloadPath;
+            continue;
           }
-          case 'run': {
-            const pRaw = await expandMacros(step.path, ctx);
-            const args = await Promise.all((step.args || []).map((a) => expandMacros(String(a), ctx)));
-            await runInstaller(pRaw, args, !!step.elevate, ctx.tmpDir);
-            break;
-          }
-          default:
-            await logInfo(`[uninstall ${item.id}] skip unsupported action=${String(step.action)}`);
-            break;
         }
-      } catch (e) {
-        const msg = `[uninstall ${item.id}] step ${i + 1}/${item.installer.uninstall.length} action=${step.action} failed: ${e?.message || e}`;
-        try {
-          await logError(msg);
-        } catch {}
-        throw new Error(msg, { cause: e });
+        // 需要 BOOTH 登录时，等待登录完成后重试一次（中断的下载从 .part 续传）
+        else if (BOOTH_AUTH_CODES.includes(err.code) && !boothLoginWaited) {
+          boothLoginWaited = true;
+          const waitLogin = prepareBoothLoginWait();
+          await ensureBoothAuthWindow();
+          await waitLogin;
+          continue;
+        }
+        if (total > 1 && err.packageId) err.message = `${nameOf(err.packageId)}: ${err.message}`;
+        throw err;
       }
     }
-  } catch (e) {
-    const detail = e?.message || e;
-    try {
-      await logError(`[uninstall ${item.id}] error: ${detail}`);
-    } catch {}
-    throw e;
+
+    // 更新检测结果以进行最新判定
+    if (dispatch) {
+      const map = await detectInstalledVersionsMap(planItems);
+      for (const item of planItems) {
+        const detected = String((map && map[item.id]) || '');
+        dispatch({ type: 'SET_DETECTED_ONE', payload: { id: item.id, version: detected } });
+      }
+    }
+    for (const item of planItems) {
+      try {
+        await recordPackageStateEvent('install', item.id);
+      } catch {}
+    }
+  } finally {
+    await closeBoothAuthWindow();
   }
+  return { plan };
+}
+
+// 执行卸载（各步骤由 Rust 侧的 uninstall_package 执行，之后删除安装时记录在 manifest 中的文件）
+// 返回值: { removed, modified, missing, shared, replaced, failed }（modified 为安装后被用户修改而保留的文件，replaced 为安装前就已存在而保留的文件）
+export async function runUninstallerForItem(item, dispatch) {
+  await ensureAviutlClosed();
+  const steps = Array.isArray(item?.installer?.uninstall) ? item.installer.uninstall : [];
+  const pkg = { id: item.id, version: String(item['latest-version'] || ''), source: null, steps };
+  const report = await invokeInstallerCommand('uninstall_package', { package: pkg, options: { taskId: newDownloadTaskId() } });
 
-  await removeInstalledId(item.id);
   if (dispatch) {
     // 为了保持状态准确性而重新检测
     const map = await detectInstalledVersionsMap([item]);
@@ -1604,5 +1481,5 @@ export async function runUninstallerForItem(item, dispatch) {
   try {
     await recordPackageStateEvent('uninstall', item.id);
   } catch {}
-  await logInfo(`[uninstall ${item.id}] completed`);
+  return report;
 }